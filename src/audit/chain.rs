@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+
+use sea_orm::{sea_query::OnConflict, *};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::{
+    audit::AuditAction,
+    db::DbConn,
+    models::{audit_chain_head, audit_event},
+};
+
+/// 最初のレコードの `prev_hash`
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// `audit_chain_head` の唯一の行の ID
+pub const HEAD_ID: i32 = 1;
+
+/// 検証時に一度に読み込む件数
+const VERIFY_BATCH_SIZE: u64 = 1000;
+/// レポートに含める破損箇所の上限
+const MAX_REPORTED_BREAKS: usize = 100;
+
+/// レコードのハッシュを計算する（`id` と `hash` 自身は含めない）
+///
+/// 個人情報を含みうる列は `payload_hash` / `client_hash` を通して含めるので、それらを消去してもチェーンは繋がったままになる.
+pub fn hash_of(event: &audit_event::Model) -> String {
+    let canonical = json!([
+        event.prev_hash,
+        event.actor_id,
+        event.action,
+        event.target_type,
+        event.target_id,
+        event.payload_hash,
+        event.client_hash,
+        event.created_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    ]);
+    hex::encode(Sha256::digest(canonical.to_string().as_bytes()))
+}
+
+/// 変更内容のハッシュを計算する
+pub fn payload_hash_of(event: &audit_event::Model) -> String {
+    let canonical = json!([event.payload_salt, event.before, event.after]);
+    hex::encode(Sha256::digest(canonical.to_string().as_bytes()))
+}
+
+/// 接続元のハッシュを計算する
+///
+/// 変更内容とは別に消去できるよう、乱数もハッシュも分けている.
+pub fn client_hash_of(event: &audit_event::Model) -> String {
+    let canonical = json!([event.client_salt, event.ip_address, event.user_agent]);
+    hex::encode(Sha256::digest(canonical.to_string().as_bytes()))
+}
+
+/// 末尾のレコードのハッシュ（レコードがなければ GENESIS_HASH）
+pub async fn last_hash<C: ConnectionTrait>(db: &C) -> Result<String, DbErr> {
+    let last = audit_event::Entity::find()
+        .order_by_desc(audit_event::Column::Id)
+        .one(db)
+        .await?;
+    Ok(last
+        .map(|e| e.hash)
+        .unwrap_or_else(|| GENESIS_HASH.to_string()))
+}
+
+/// チェーンの末尾の行を排他ロックし、末尾のハッシュを返す
+///
+/// ロックはトランザクションの終了まで保持されるため、複数台から同時に追記してもチェーンは分岐しない.
+/// ロック付きの読み取りは常に最新のコミット済みの値を返すので、トランザクションの開始時点に関わらず正しく繋がる.
+pub async fn lock_head<C: ConnectionTrait>(db: &C) -> Result<String, DbErr> {
+    if let Some(head) = find_head_for_update(db).await? {
+        return Ok(head.last_hash);
+    }
+
+    // 行がまだ無い場合は既存のレコードの末尾から作る（同時に作ろうとした他の台とは主キーで競合する）
+    audit_chain_head::Entity::insert(audit_chain_head::ActiveModel {
+        id: Set(HEAD_ID),
+        last_hash: Set(last_hash(db).await?),
+    })
+    .on_conflict(
+        OnConflict::column(audit_chain_head::Column::Id)
+            .do_nothing_on([audit_chain_head::Column::Id])
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    find_head_for_update(db)
+        .await?
+        .map(|head| head.last_hash)
+        .ok_or_else(|| DbErr::RecordNotFound("audit_chain_head".to_string()))
+}
+
+async fn find_head_for_update<C: ConnectionTrait>(
+    db: &C,
+) -> Result<Option<audit_chain_head::Model>, DbErr> {
+    audit_chain_head::Entity::find_by_id(HEAD_ID)
+        .lock_exclusive()
+        .one(db)
+        .await
+}
+
+/// `lock_head` でロックした末尾を追記したレコードのハッシュに進める
+pub async fn advance_head<C: ConnectionTrait>(db: &C, hash: &str) -> Result<(), DbErr> {
+    audit_chain_head::ActiveModel {
+        id: Unchanged(HEAD_ID),
+        last_hash: Set(hash.to_string()),
+    }
+    .update(db)
+    .await?;
+    Ok(())
+}
+
+/// チェーンが途切れている箇所
+#[derive(Serialize, ToSchema)]
+pub struct ChainBreak {
+    pub id: i32,
+    /// `prev_mismatch`: 直前のレコードと繋がっていない（削除・挿入）
+    /// `hash_mismatch`: レコードの内容とハッシュが一致しない（改ざん）
+    /// `redaction_mismatch`: `user.redact` の記録と消去した列が一致しない（記録のない消去、消去したはずの列に値がある）
+    pub reason: &'static str,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VerifyReport {
+    /// 検証したレコード数
+    pub checked: u64,
+    pub valid: bool,
+    pub breaks: Vec<ChainBreak>,
+    /// 末尾のレコードのハッシュ（外部に控えておくと末尾の削除も検出できる）
+    pub last_hash: String,
+}
+
+/// `user.redact` の記録に残す、個人情報を消去したレコードの ID
+#[derive(Default, Serialize, Deserialize)]
+pub struct Redactions {
+    /// `before` / `after` を消去したレコード
+    pub payload: Vec<i32>,
+    /// `ip_address` / `user_agent` を消去したレコード
+    pub client: Vec<i32>,
+}
+
+/// 1件のレコードについて消去した列
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Redacted {
+    pub payload: bool,
+    pub client: bool,
+}
+
+/// 1件のレコードを検証し、見つかった破損の理由を返す
+///
+/// `prev_hash` は直前のレコードの `hash`、`redacted` は `user.redact` の記録にあるこのレコードの消去した列.
+/// 消去した列はハッシュを検証できない代わりに、値が残っていないことを確かめる.
+pub fn check_event(
+    event: &audit_event::Model,
+    prev_hash: &str,
+    redacted: Redacted,
+) -> Vec<&'static str> {
+    let mut reasons = Vec::new();
+    if event.prev_hash != prev_hash {
+        reasons.push("prev_mismatch");
+    }
+
+    let payload_valid = redacted.payload || payload_hash_of(event) == event.payload_hash;
+    let client_valid = redacted.client || client_hash_of(event) == event.client_hash;
+    if !payload_valid || !client_valid || hash_of(event) != event.hash {
+        reasons.push("hash_mismatch");
+    }
+
+    let payload_cleared =
+        event.payload_salt.is_none() && event.before.is_none() && event.after.is_none();
+    let client_cleared =
+        event.client_salt.is_none() && event.ip_address.is_none() && event.user_agent.is_none();
+    let redaction_valid = event.redacted_at.is_some() == (redacted.payload || redacted.client)
+        && (!redacted.payload || payload_cleared)
+        && (!redacted.client || client_cleared);
+    if !redaction_valid {
+        reasons.push("redaction_mismatch");
+    }
+    reasons
+}
+
+/// チェーンを先頭から辿って検証する
+pub async fn verify(db: &DbConn) -> Result<VerifyReport, DbErr> {
+    let redactions = load_redactions(db).await?;
+    let mut report = VerifyReport {
+        checked: 0,
+        valid: true,
+        breaks: Vec::new(),
+        last_hash: GENESIS_HASH.to_string(),
+    };
+    let mut last_id = 0;
+    loop {
+        let batch = audit_event::Entity::find()
+            .filter(audit_event::Column::Id.gt(last_id))
+            .order_by_asc(audit_event::Column::Id)
+            .limit(VERIFY_BATCH_SIZE)
+            .all(db)
+            .await?;
+        if batch.is_empty() {
+            break;
+        }
+        for event in batch {
+            let redacted = redactions.get(&event.id).copied().unwrap_or_default();
+            for reason in check_event(&event, &report.last_hash, redacted) {
+                report.push_break(event.id, reason);
+            }
+            report.checked += 1;
+            last_id = event.id;
+            report.last_hash = event.hash;
+        }
+    }
+    Ok(report)
+}
+
+/// `user.redact` の記録から、レコードごとの消去した列を集める
+///
+/// 記録自体もチェーンに含まれるので、記録の書き換えは記録のレコードのハッシュの不一致として検出される.
+async fn load_redactions(db: &DbConn) -> Result<HashMap<i32, Redacted>, DbErr> {
+    let events = audit_event::Entity::find()
+        .filter(audit_event::Column::Action.eq(AuditAction::UserRedact.as_str()))
+        .all(db)
+        .await?;
+    let mut redactions: HashMap<i32, Redacted> = HashMap::new();
+    for event in events {
+        let Some(recorded) = event
+            .after
+            .and_then(|after| serde_json::from_value::<Redactions>(after).ok())
+        else {
+            continue;
+        };
+        // 記録より後のレコードを消去したことにはできない
+        for id in recorded.payload.into_iter().filter(|id| *id < event.id) {
+            redactions.entry(id).or_default().payload = true;
+        }
+        for id in recorded.client.into_iter().filter(|id| *id < event.id) {
+            redactions.entry(id).or_default().client = true;
+        }
+    }
+    Ok(redactions)
+}
+
+impl VerifyReport {
+    fn push_break(&mut self, id: i32, reason: &'static str) {
+        self.valid = false;
+        if self.breaks.len() < MAX_REPORTED_BREAKS {
+            self.breaks.push(ChainBreak { id, reason });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn event(id: i32, prev_hash: &str) -> audit_event::Model {
+        let mut event = audit_event::Model {
+            id,
+            actor_id: "01HACTOR".to_string(),
+            action: "user.update".to_string(),
+            target_type: "user".to_string(),
+            target_id: "01HTARGET".to_string(),
+            before: Some(json!({ "name": format!("before-{}", id) })),
+            after: Some(json!({ "name": format!("after-{}", id) })),
+            ip_address: Some("192.0.2.1".to_string()),
+            user_agent: Some("curl/8.0".to_string()),
+            created_at: NaiveDate::from_ymd_opt(2025, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, id as u32)
+                .unwrap(),
+            payload_salt: Some(format!("payload-salt-{}", id)),
+            payload_hash: String::new(),
+            client_salt: Some(format!("client-salt-{}", id)),
+            client_hash: String::new(),
+            redacted_at: None,
+            prev_hash: prev_hash.to_string(),
+            hash: String::new(),
+        };
+        event.payload_hash = payload_hash_of(&event);
+        event.client_hash = client_hash_of(&event);
+        event.hash = hash_of(&event);
+        event
+    }
+
+    fn build_chain(len: i32) -> Vec<audit_event::Model> {
+        let mut chain: Vec<audit_event::Model> = Vec::new();
+        for id in 1..=len {
+            let prev_hash = chain
+                .last()
+                .map(|e| e.hash.clone())
+                .unwrap_or_else(|| GENESIS_HASH.to_string());
+            chain.push(event(id, &prev_hash));
+        }
+        chain
+    }
+
+    /// `verify` と同じ順に辿り、(ID, 理由) を集める
+    fn check_chain(
+        chain: &[audit_event::Model],
+        redactions: &HashMap<i32, Redacted>,
+    ) -> Vec<(i32, &'static str)> {
+        let mut breaks = Vec::new();
+        let mut prev_hash = GENESIS_HASH.to_string();
+        for event in chain {
+            let redacted = redactions.get(&event.id).copied().unwrap_or_default();
+            for reason in check_event(event, &prev_hash, redacted) {
+                breaks.push((event.id, reason));
+            }
+            prev_hash = event.hash.clone();
+        }
+        breaks
+    }
+
+    fn redact_payload(event: &mut audit_event::Model) {
+        event.before = None;
+        event.after = None;
+        event.payload_salt = None;
+        event.redacted_at = Some(event.created_at);
+    }
+
+    fn redact_client(event: &mut audit_event::Model) {
+        event.ip_address = None;
+        event.user_agent = None;
+        event.client_salt = None;
+        event.redacted_at = Some(event.created_at);
+    }
+
+    const PAYLOAD: Redacted = Redacted {
+        payload: true,
+        client: false,
+    };
+    const CLIENT: Redacted = Redacted {
+        payload: false,
+        client: true,
+    };
+
+    #[test]
+    fn accepts_intact_chain() {
+        let chain = build_chain(3);
+        assert_eq!(check_chain(&chain, &HashMap::new()), vec![]);
+    }
+
+    #[test]
+    fn detects_modified_columns() {
+        let mutations: [fn(&mut audit_event::Model); 6] = [
+            |e| e.actor_id = "01HOTHER".to_string(),
+            |e| e.action = "user.delete".to_string(),
+            |e| e.created_at += chrono::Duration::seconds(1),
+            |e| e.after = Some(json!({ "name": "forged" })),
+            |e| e.ip_address = Some("198.51.100.1".to_string()),
+            |e| e.user_agent = None,
+        ];
+        for mutate in mutations {
+            let mut chain = build_chain(3);
+            mutate(&mut chain[1]);
+            assert_eq!(
+                check_chain(&chain, &HashMap::new()),
+                vec![(2, "hash_mismatch")]
+            );
+        }
+    }
+
+    #[test]
+    fn detects_removed_event() {
+        let mut chain = build_chain(3);
+        chain.remove(1);
+        assert_eq!(
+            check_chain(&chain, &HashMap::new()),
+            vec![(3, "prev_mismatch")]
+        );
+    }
+
+    #[test]
+    fn accepts_recorded_redaction() {
+        let mut chain = build_chain(3);
+        redact_payload(&mut chain[0]);
+        redact_client(&mut chain[1]);
+        redact_payload(&mut chain[2]);
+        redact_client(&mut chain[2]);
+        let redactions = HashMap::from([
+            (1, PAYLOAD),
+            (2, CLIENT),
+            (
+                3,
+                Redacted {
+                    payload: true,
+                    client: true,
+                },
+            ),
+        ]);
+        assert_eq!(check_chain(&chain, &redactions), vec![]);
+    }
+
+    #[test]
+    fn verifies_remaining_columns_of_redacted_event() {
+        let mut chain = build_chain(2);
+        redact_client(&mut chain[0]);
+        chain[0].before = Some(json!({ "name": "forged" }));
+        assert_eq!(
+            check_chain(&chain, &HashMap::from([(1, CLIENT)])),
+            vec![(1, "hash_mismatch")]
+        );
+    }
+
+    #[test]
+    fn requires_redacted_columns_to_be_cleared() {
+        let mut chain = build_chain(2);
+        redact_payload(&mut chain[0]);
+        chain[0].after = Some(json!({ "name": "forged" }));
+        assert_eq!(
+            check_chain(&chain, &HashMap::from([(1, PAYLOAD)])),
+            vec![(1, "redaction_mismatch")]
+        );
+
+        let mut chain = build_chain(2);
+        redact_client(&mut chain[0]);
+        chain[0].ip_address = Some("198.51.100.1".to_string());
+        assert_eq!(
+            check_chain(&chain, &HashMap::from([(1, CLIENT)])),
+            vec![(1, "redaction_mismatch")]
+        );
+    }
+
+    #[test]
+    fn detects_unrecorded_redaction() {
+        let mut chain = build_chain(2);
+        redact_payload(&mut chain[0]);
+        assert_eq!(
+            check_chain(&chain, &HashMap::new()),
+            vec![(1, "hash_mismatch"), (1, "redaction_mismatch")]
+        );
+
+        // 記録にあるのに `redacted_at` が無い
+        let mut chain = build_chain(2);
+        redact_payload(&mut chain[0]);
+        chain[0].redacted_at = None;
+        assert_eq!(
+            check_chain(&chain, &HashMap::from([(1, PAYLOAD)])),
+            vec![(1, "redaction_mismatch")]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Context;
+use chrono::{NaiveDateTime, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use sea_orm::*;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{db::DbConn, models::audit_event};
+
+/// エクスポートの署名に使う鍵を読む環境変数
+const SIGNING_KEY_ENV: &str = "AUDIT_SIGNING_KEY";
+/// 一度に読み込む件数
+const EXPORT_BATCH_SIZE: u64 = 1000;
+
+/// 署名（HS256 の JWT）に含める内容
+#[derive(Serialize)]
+struct ExportClaims {
+    iss: &'static str,
+    iat: i64,
+    /// 対象期間（この日時以降）
+    since: NaiveDateTime,
+    /// 対象期間（この日時より前）
+    until: NaiveDateTime,
+    count: u64,
+    first_id: Option<i32>,
+    last_id: Option<i32>,
+    /// 署名行を除いた本文の SHA-256
+    sha256: String,
+}
+
+/// 期間内の監査ログを JSON Lines で書き出す
+///
+/// 1行に1レコードを出力し、最終行に `{"signature": "<JWT>"}` を付ける.
+/// JWT は `AUDIT_SIGNING_KEY` で署名され、本文の SHA-256 と件数を含む.
+pub async fn export_jsonl(
+    db: &DbConn,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+) -> anyhow::Result<String> {
+    let key = std::env::var(SIGNING_KEY_ENV)
+        .ok()
+        .filter(|k| !k.is_empty())
+        .with_context(|| format!("{} is not set", SIGNING_KEY_ENV))?;
+
+    let mut body = String::new();
+    let mut count = 0;
+    let mut first_id = None;
+    let mut last_id = None;
+    loop {
+        let batch = audit_event::Entity::find()
+            .filter(audit_event::Column::CreatedAt.gte(since))
+            .filter(audit_event::Column::CreatedAt.lt(until))
+            .filter(audit_event::Column::Id.gt(last_id.unwrap_or(0)))
+            .order_by_asc(audit_event::Column::Id)
+            .limit(EXPORT_BATCH_SIZE)
+            .all(db)
+            .await?;
+        if batch.is_empty() {
+            break;
+        }
+        for event in batch {
+            body.push_str(&serde_json::to_string(&event)?);
+            body.push('\n');
+            count += 1;
+            first_id.get_or_insert(event.id);
+            last_id = Some(event.id);
+        }
+    }
+
+    let claims = ExportClaims {
+        iss: "UniQUE-API",
+        iat: Utc::now().timestamp(),
+        since,
+        until,
+        count,
+        first_id,
+        last_id,
+        sha256: hex::encode(Sha256::digest(body.as_bytes())),
+    };
+    let signature = jsonwebtoken::encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(key.as_bytes()),
+    )?;
+    body.push_str(&json!({ "signature": signature }).to_string());
+    body.push('\n');
+    Ok(body)
+}
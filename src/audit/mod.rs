@@ -0,0 +1,407 @@
+use chrono::{Timelike, Utc};
+use sea_orm::{sea_query::Expr, *};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tracing::error;
+
+use crate::{
+    middleware::auth::{AuthUser, ClientInfo},
+    models::{audit_event, session},
+    utils::token,
+};
+
+pub mod chain;
+pub mod export;
+
+/// 監査ログに値を残さないフィールド
+const REDACTED_FIELDS: &[&str] = &["password_hash", "client_secret", "token_hash"];
+/// 伏せた値の代わりに残す文字列
+const REDACTED: &str = "[REDACTED]";
+
+/// 記録する操作の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditAction {
+    UserCreate,
+    UserUpdate,
+    UserDelete,
+    UserRestore,
+    UserPurge,
+    UserRedact,
+    UserExport,
+    EmailChange,
+    EmailChangeRevert,
+    EmailVerify,
+    DiscordLink,
+    DiscordUnlink,
+    DeletionRequest,
+    DeletionConfirm,
+    DeletionCancel,
+    DeletionComplete,
+    RoleCreate,
+    RoleUpdate,
+    RoleDelete,
+    RoleGrant,
+    RoleRevoke,
+    AppCreate,
+    AppUpdate,
+    AppDelete,
+    SessionDelete,
+    PasswordChange,
+    PasswordReset,
+    AccountLock,
+    AccountUnlock,
+    IpLock,
+    SuspensionExpire,
+    SystemPurge,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::UserCreate => "user.create",
+            AuditAction::UserUpdate => "user.update",
+            AuditAction::UserDelete => "user.delete",
+            AuditAction::UserRestore => "user.restore",
+            AuditAction::UserPurge => "user.purge",
+            AuditAction::UserRedact => "user.redact",
+            AuditAction::UserExport => "user.export",
+            AuditAction::EmailChange => "user.email_change",
+            AuditAction::EmailChangeRevert => "user.email_change_revert",
+            AuditAction::EmailVerify => "user.email_verify",
+            AuditAction::DiscordLink => "user.discord_link",
+            AuditAction::DiscordUnlink => "user.discord_unlink",
+            AuditAction::DeletionRequest => "user.deletion_request",
+            AuditAction::DeletionConfirm => "user.deletion_confirm",
+            AuditAction::DeletionCancel => "user.deletion_cancel",
+            AuditAction::DeletionComplete => "user.deletion_complete",
+            AuditAction::RoleCreate => "role.create",
+            AuditAction::RoleUpdate => "role.update",
+            AuditAction::RoleDelete => "role.delete",
+            AuditAction::RoleGrant => "user.role_grant",
+            AuditAction::RoleRevoke => "user.role_revoke",
+            AuditAction::AppCreate => "app.create",
+            AuditAction::AppUpdate => "app.update",
+            AuditAction::AppDelete => "app.delete",
+            AuditAction::SessionDelete => "session.delete",
+            AuditAction::PasswordChange => "user.password_change",
+            AuditAction::PasswordReset => "user.password_reset",
+            AuditAction::AccountLock => "user.lock",
+            AuditAction::AccountUnlock => "user.unlock",
+            AuditAction::IpLock => "ip.lock",
+            AuditAction::SuspensionExpire => "user.suspension_expire",
+            AuditAction::SystemPurge => "system.purge",
+        }
+    }
+
+    /// 操作対象の種類
+    pub fn target_type(&self) -> &'static str {
+        match self {
+            AuditAction::UserCreate
+            | AuditAction::UserUpdate
+            | AuditAction::UserDelete
+            | AuditAction::UserRestore
+            | AuditAction::UserPurge
+            | AuditAction::UserRedact
+            | AuditAction::UserExport
+            | AuditAction::EmailChange
+            | AuditAction::EmailChangeRevert
+            | AuditAction::EmailVerify
+            | AuditAction::DiscordLink
+            | AuditAction::DiscordUnlink
+            | AuditAction::DeletionRequest
+            | AuditAction::DeletionConfirm
+            | AuditAction::DeletionCancel
+            | AuditAction::DeletionComplete
+            | AuditAction::RoleGrant
+            | AuditAction::RoleRevoke
+            | AuditAction::PasswordChange
+            | AuditAction::PasswordReset
+            | AuditAction::AccountLock
+            | AuditAction::AccountUnlock
+            | AuditAction::SuspensionExpire => "user",
+            AuditAction::RoleCreate | AuditAction::RoleUpdate | AuditAction::RoleDelete => "role",
+            AuditAction::AppCreate | AuditAction::AppUpdate | AuditAction::AppDelete => "app",
+            AuditAction::SessionDelete => "session",
+            AuditAction::IpLock => "ip",
+            AuditAction::SystemPurge => "system",
+        }
+    }
+}
+
+/// 監査ログの1件分
+pub struct AuditEntry {
+    action: AuditAction,
+    target_id: String,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl AuditEntry {
+    /// 値の変化を伴わない操作
+    pub fn new(action: AuditAction, target_id: impl Into<String>) -> Self {
+        Self {
+            action,
+            target_id: target_id.into(),
+            before: None,
+            after: None,
+        }
+    }
+
+    /// 作成された値をそのまま記録する
+    pub fn created<T: Serialize>(
+        action: AuditAction,
+        target_id: impl Into<String>,
+        after: &T,
+    ) -> Self {
+        Self {
+            after: Some(snapshot(after)),
+            ..Self::new(action, target_id)
+        }
+    }
+
+    /// 変更前後で異なるフィールドのみを記録する
+    pub fn updated<T: Serialize>(
+        action: AuditAction,
+        target_id: impl Into<String>,
+        before: &T,
+        after: &T,
+    ) -> Self {
+        let (before, after) = diff(snapshot(before), snapshot(after));
+        Self {
+            before: Some(before),
+            after: Some(after),
+            ..Self::new(action, target_id)
+        }
+    }
+
+    /// 削除された値をそのまま記録する
+    pub fn deleted<T: Serialize>(
+        action: AuditAction,
+        target_id: impl Into<String>,
+        before: &T,
+    ) -> Self {
+        Self {
+            before: Some(snapshot(before)),
+            ..Self::new(action, target_id)
+        }
+    }
+
+    /// セッションの削除を記録する
+    ///
+    /// セッション ID は Cookie の値そのものなので、対象 ID にはハッシュを使い、値は伏せる.
+    pub fn session_deleted(session: &session::Model) -> Self {
+        let mut before = snapshot(session);
+        if let Value::Object(map) = &mut before {
+            map.insert("id".to_string(), Value::String(REDACTED.to_string()));
+        }
+        Self {
+            before: Some(before),
+            ..Self::new(AuditAction::SessionDelete, token::hash_token(&session.id))
+        }
+    }
+
+    /// 変更前の値として任意の JSON を付け加える
+    pub fn with_before(mut self, before: Value) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    /// 変更後の値として任意の JSON を付け加える
+    pub fn with_after(mut self, after: Value) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    /// 認証済みユーザーによる操作として記録する
+    pub async fn record<C>(self, db: &C, auth_user: &AuthUser) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait + TransactionTrait,
+    {
+        self.record_as(db, &auth_user.user_id, &auth_user.client)
+            .await
+    }
+
+    /// 操作者を明示して記録する（認証を経ないエンドポイント用）
+    ///
+    /// チェーンの末尾をロックしてから追記する.
+    /// 変更と同じトランザクションの中で呼ぶと、ロックはそのトランザクションの終了まで保持される.
+    pub async fn record_as<C>(
+        self,
+        db: &C,
+        actor_id: &str,
+        client: &ClientInfo,
+    ) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait + TransactionTrait,
+    {
+        let action = self.action;
+        async {
+            let txn = db.begin().await?;
+            self.append(&txn, actor_id, client).await?;
+            txn.commit().await
+        }
+        .await
+        .inspect_err(|e| error!("Failed to record audit event {}: {:?}", action.as_str(), e))
+    }
+
+    async fn append<C: ConnectionTrait>(
+        self,
+        db: &C,
+        actor_id: &str,
+        client: &ClientInfo,
+    ) -> Result<(), DbErr> {
+        let mut event = audit_event::Model {
+            id: 0,
+            actor_id: actor_id.to_string(),
+            action: self.action.as_str().to_string(),
+            target_type: self.action.target_type().to_string(),
+            target_id: self.target_id,
+            before: self.before,
+            after: self.after,
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            // DB に保存される精度に揃えておかないと検証時にハッシュが一致しない
+            created_at: Utc::now().naive_utc().with_nanosecond(0).unwrap(),
+            payload_salt: Some(token::generate_token()),
+            payload_hash: String::new(),
+            client_salt: Some(token::generate_token()),
+            client_hash: String::new(),
+            redacted_at: None,
+            prev_hash: chain::lock_head(db).await?,
+            hash: String::new(),
+        };
+        event.payload_hash = chain::payload_hash_of(&event);
+        event.client_hash = chain::client_hash_of(&event);
+        event.hash = chain::hash_of(&event);
+        chain::advance_head(db, &event.hash).await?;
+
+        let mut am: audit_event::ActiveModel = event.into();
+        am.id = NotSet;
+        am.insert(db).await?;
+        Ok(())
+    }
+}
+
+/// 削除するユーザーの個人情報を監査ログから消去する
+///
+/// ユーザーを対象とした操作の変更内容、ユーザーのセッションの削除の内容、ユーザー自身の操作の接続元を消す.
+/// 操作の種類・操作者・対象などは残るため、誰がいつ何をしたかは引き続き追える.
+/// 消した列は `payload_hash` / `client_hash` を通してチェーンに含まれているので、チェーンは繋がったままになる.
+/// どのレコードのどの列を消したかは `user.redact` としてチェーンに記録し、検証ではその記録と照合する.
+pub async fn redact_user<C>(db: &C, user_id: &str, actor_id: &str) -> Result<(), DbErr>
+where
+    C: ConnectionTrait + TransactionTrait,
+{
+    let find_ids = |condition: Condition| {
+        audit_event::Entity::find()
+            .select_only()
+            .column(audit_event::Column::Id)
+            .filter(condition)
+            .into_tuple::<i32>()
+            .all(db)
+    };
+    let redactions = chain::Redactions {
+        payload: find_ids(
+            Condition::any()
+                .add(
+                    Condition::all()
+                        .add(
+                            audit_event::Column::TargetType
+                                .eq(AuditAction::UserUpdate.target_type()),
+                        )
+                        .add(audit_event::Column::TargetId.eq(user_id)),
+                )
+                // セッションの削除は対象 ID がセッション ID のハッシュなので、変更内容の `user_id` で探す
+                .add(
+                    Condition::all()
+                        .add(
+                            audit_event::Column::TargetType
+                                .eq(AuditAction::SessionDelete.target_type()),
+                        )
+                        .add(Expr::cust_with_values(
+                            "JSON_UNQUOTE(JSON_EXTRACT(`before`, '$.user_id')) = ?",
+                            [user_id],
+                        )),
+                ),
+        )
+        .await?,
+        client: find_ids(Condition::all().add(audit_event::Column::ActorId.eq(user_id))).await?,
+    };
+    if redactions.payload.is_empty() && redactions.client.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now().naive_utc();
+    audit_event::Entity::update_many()
+        .col_expr(
+            audit_event::Column::PayloadSalt,
+            Expr::value(Option::<String>::None),
+        )
+        .col_expr(
+            audit_event::Column::Before,
+            Expr::value(sea_orm::Value::Json(None)),
+        )
+        .col_expr(
+            audit_event::Column::After,
+            Expr::value(sea_orm::Value::Json(None)),
+        )
+        .col_expr(audit_event::Column::RedactedAt, Expr::value(now))
+        .filter(audit_event::Column::Id.is_in(redactions.payload.clone()))
+        .exec(db)
+        .await?;
+    audit_event::Entity::update_many()
+        .col_expr(
+            audit_event::Column::ClientSalt,
+            Expr::value(Option::<String>::None),
+        )
+        .col_expr(
+            audit_event::Column::IpAddress,
+            Expr::value(Option::<String>::None),
+        )
+        .col_expr(
+            audit_event::Column::UserAgent,
+            Expr::value(Option::<String>::None),
+        )
+        .col_expr(audit_event::Column::RedactedAt, Expr::value(now))
+        .filter(audit_event::Column::Id.is_in(redactions.client.clone()))
+        .exec(db)
+        .await?;
+
+    AuditEntry::new(AuditAction::UserRedact, user_id)
+        .with_after(serde_json::to_value(&redactions).unwrap_or(Value::Null))
+        .record_as(db, actor_id, &ClientInfo::default())
+        .await
+}
+
+/// 値を JSON にし、秘匿すべきフィールドを伏せる
+fn snapshot<T: Serialize>(value: &T) -> Value {
+    let mut value = serde_json::to_value(value).unwrap_or(Value::Null);
+    if let Value::Object(map) = &mut value {
+        for field in REDACTED_FIELDS {
+            if let Some(v) = map.get_mut(*field) {
+                *v = Value::String(REDACTED.to_string());
+            }
+        }
+    }
+    value
+}
+
+/// 2つのオブジェクトを比べ、値の異なるフィールドだけを取り出す
+fn diff(before: Value, after: Value) -> (Value, Value) {
+    let (mut before, mut after) = match (before, after) {
+        (Value::Object(before), Value::Object(after)) => (before, after),
+        (before, after) => return (before, after),
+    };
+    let mut before_diff = Map::new();
+    let mut after_diff = Map::new();
+    let keys: Vec<String> = before.keys().chain(after.keys()).cloned().collect();
+    for key in keys {
+        let old = before.remove(&key);
+        let new = after.remove(&key);
+        if old != new {
+            before_diff.insert(key.clone(), old.unwrap_or(Value::Null));
+            after_diff.insert(key, new.unwrap_or(Value::Null));
+        }
+    }
+    (Value::Object(before_diff), Value::Object(after_diff))
+}
@@ -75,15 +75,6 @@ impl Permission {
         }
     }
 
-    /// Check whether this Permission contains the flag represented by the given string.
-    pub fn contains_str(&self, s: &str) -> bool {
-        if let Some(p) = Permission::from_str(s) {
-            self.contains(p)
-        } else {
-            false
-        }
-    }
-
     /// Given a raw bits value, return a tuple of (known_names, known_mask).
     /// `known_names` is a Vec<String> of matching `PermissionString` names.
     /// `known_mask` is the bitmask of all recognized bits.
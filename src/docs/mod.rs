@@ -35,18 +35,42 @@ use utoipa::OpenApi;
         crate::routes::sessions::get_session,
         crate::routes::sessions::delete_session,
         
+        // Audit endpoints
+        crate::routes::audit::get_audit_events,
+        crate::routes::audit::get_audit_event,
+        crate::routes::audit::verify_audit_chain,
+        crate::routes::audit::export_audit_events,
+
         // Email Verify endpoints (top-level)
         crate::routes::email_verify::get_email_verifications,
         crate::routes::email_verify::delete_email_verification,
+        crate::routes::email_verify::confirm_email_verification,
         
         // Users sub-routes: Email Verify
         crate::routes::users_sub::email_verify::get_email_verifications,
         crate::routes::users_sub::email_verify::post_challenge,
         crate::routes::users_sub::email_verify::delete_email_verification,
         
+        // Users sub-routes: Email Change
+        crate::routes::users_sub::email_change::get_email_changes,
+        crate::routes::users_sub::email_change::post_email_change,
+        crate::routes::users_sub::email_change::delete_email_changes,
+        crate::routes::users_sub::email_change::confirm_email_change,
+        crate::routes::users_sub::email_change::cancel_email_change,
+
         // Users sub-routes: Password
         crate::routes::users_sub::password::password_change,
         crate::routes::users_sub::password::password_reset,
+        crate::routes::users_sub::password::password_reset_confirm,
+        crate::routes::users_sub::lockout::get_lockout,
+        crate::routes::users_sub::lockout::delete_lockout,
+        crate::routes::users_sub::restore::restore_user,
+        crate::routes::users_sub::personal_data::export_personal_data,
+        crate::routes::users_sub::deletion::get_deletion_request,
+        crate::routes::users_sub::deletion::post_deletion_request,
+        crate::routes::users_sub::deletion::delete_deletion_request,
+        crate::routes::users_sub::deletion::confirm_deletion,
+        crate::routes::users_sub::deletion::cancel_deletion,
         
         // Users sub-routes: Permissions
         crate::routes::users_sub::permissions::get_permissions_bit,
@@ -59,6 +83,11 @@ use utoipa::OpenApi;
         // Users sub-routes: Search
         crate::routes::users_sub::search::search_users,
         
+        // Users sub-routes: Import / Export / Bulk
+        crate::routes::users_sub::transfer::import_users,
+        crate::routes::users_sub::transfer::export_users,
+        crate::routes::users_sub::bulk::bulk_users,
+        
         // Users sub-routes: Sessions
         crate::routes::users_sub::sessions::get_all_sessions,
         crate::routes::users_sub::sessions::get_session,
@@ -71,6 +100,10 @@ use utoipa::OpenApi;
     ),
     components(
         schemas(
+            // Errors
+            crate::error::ProblemDetails,
+            crate::error::FieldError,
+
             // Users
             crate::routes::users::PublicUserResponse,
             crate::routes::users::DetailedUserResponse,
@@ -98,15 +131,30 @@ use utoipa::OpenApi;
             // Sessions
             crate::routes::sessions::SessionResponse,
             
+            // Audit
+            crate::routes::audit::AuditEventResponse,
+            crate::routes::audit::AuditEventListResponse,
+            crate::routes::audit::AuditQuery,
+            crate::routes::audit::AuditExportQuery,
+            crate::audit::chain::VerifyReport,
+            crate::audit::chain::ChainBreak,
+
             // Email Verify
             crate::routes::email_verify::EmailVerificationResponse,
             
             // Users sub: Email Verify
             crate::routes::users_sub::email_verify::CreateVerifyChallenge,
             
+            // Users sub: Email Change
+            crate::routes::users_sub::email_change::EmailField,
+            crate::routes::users_sub::email_change::EmailChangeResponse,
+            crate::routes::users_sub::email_change::CreateEmailChange,
+
             // Users sub: Password
             crate::routes::users_sub::password::PasswordChange,
             crate::routes::users_sub::password::PasswordReset,
+            crate::routes::users_sub::password::PasswordResetConfirm,
+            crate::routes::users_sub::lockout::LockoutResponse,
             
             // Users sub: Permissions
             crate::routes::users_sub::permissions::PermissionsResponse,
@@ -115,6 +163,22 @@ use utoipa::OpenApi;
             crate::routes::users_sub::search::SearchUsersResponse,
             crate::routes::users_sub::search::SearchMetadata,
             
+            // Users sub: Import / Export
+            crate::routes::users_sub::transfer::ImportUser,
+            crate::routes::users_sub::transfer::ImportedUser,
+            crate::routes::users_sub::transfer::ImportUsersResponse,
+            crate::routes::users_sub::transfer::ExportFormat,
+            
+            // Users sub: Bulk
+            crate::routes::users_sub::bulk::BulkRequest,
+            crate::routes::users_sub::bulk::BulkAction,
+            crate::routes::users_sub::bulk::BulkStatus,
+            crate::routes::users_sub::bulk::BulkResult,
+            crate::routes::users_sub::bulk::BulkResponse,
+            crate::routes::users_sub::personal_data::PersonalDataExport,
+            crate::routes::users_sub::deletion::DeletionRequestResponse,
+            crate::routes::users_sub::deletion::CreateDeletionRequest,
+            
             // Users sub: Discord
             crate::routes::users_sub::discord::DiscordResponse,
             crate::routes::users_sub::discord::CreateDiscord,
@@ -126,6 +190,7 @@ use utoipa::OpenApi;
         (name = "apps", description = "アプリケーション管理エンドポイント"),
         (name = "sessions", description = "セッション管理エンドポイント"),
         (name = "email_verify", description = "Email検証エンドポイント"),
+        (name = "audit", description = "監査ログエンドポイント"),
     ),
     info(
         title = "UniQUE API",
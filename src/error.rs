@@ -0,0 +1,199 @@
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use sea_orm::{DbErr, SqlErr};
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+
+/// API のエラー
+/// RFC 7807 の `application/problem+json` としてレスポンスに変換される.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("認証が必要です")]
+    Unauthorized,
+    #[error("この操作を行う権限がありません")]
+    Forbidden,
+    #[error("変更が許可されていないフィールドが含まれています")]
+    ForbiddenFields(Vec<&'static str>),
+    #[error("{0}が見つかりません")]
+    NotFound(&'static str),
+    /// 一意制約に違反した（中身は重複したキーの名前）
+    #[error("既に同じ値のデータが存在します")]
+    Duplicate(Option<String>),
+    /// 外部キー制約に違反した
+    #[error("参照先のデータが存在しないか、他のデータから参照されています")]
+    InvalidReference,
+    #[error("{0}")]
+    Gone(String),
+    #[error("リクエストが多すぎます。しばらくしてから再度お試しください")]
+    TooManyRequests,
+    /// 認証の失敗が続いたため一時的に受け付けない（中身は再試行できるまでの秒数）
+    #[error("認証の失敗が続いたため、{0}秒間は再試行できません")]
+    Locked(u64),
+    /// レート制限を超えた（中身は再試行できるまでの秒数）
+    #[error("リクエストが多すぎます。{0}秒後に再度お試しください")]
+    RateLimited(u64),
+    #[error("入力内容に誤りがあります")]
+    Validation(Vec<FieldError>),
+    #[error("データベースエラー: {0}")]
+    Database(DbErr),
+    #[error("サーバーエラー: {0}")]
+    Internal(#[from] anyhow::Error),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden | ApiError::ForbiddenFields(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Duplicate(_) => StatusCode::CONFLICT,
+            ApiError::InvalidReference => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Gone(_) => StatusCode::GONE,
+            ApiError::TooManyRequests | ApiError::Locked(_) | ApiError::RateLimited(_) => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Database(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// クライアントが分岐に使う機械可読なエラーコード
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::Forbidden => "forbidden",
+            ApiError::ForbiddenFields(_) => "forbidden_fields",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Duplicate(_) => "duplicate",
+            ApiError::InvalidReference => "invalid_reference",
+            ApiError::Gone(_) => "gone",
+            ApiError::TooManyRequests => "too_many_requests",
+            ApiError::Locked(_) => "locked",
+            ApiError::RateLimited(_) => "rate_limited",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Database(_) | ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl From<DbErr> for ApiError {
+    fn from(e: DbErr) -> Self {
+        match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(message)) => {
+                ApiError::Duplicate(duplicate_key(&message))
+            }
+            Some(SqlErr::ForeignKeyConstraintViolation(_)) => ApiError::InvalidReference,
+            _ => match e {
+                DbErr::RecordNotFound(_) | DbErr::RecordNotUpdated => ApiError::NotFound("データ"),
+                e => ApiError::Database(e),
+            },
+        }
+    }
+}
+
+/// MySQL の一意制約違反のメッセージから重複したキーの名前を取り出す
+/// 例: `Duplicate entry 'foo' for key 'users.custom_id'` → `custom_id`
+fn duplicate_key(message: &str) -> Option<String> {
+    let key = message.rsplit_once("for key '")?.1.trim_end_matches('\'');
+    Some(key.rsplit('.').next().unwrap_or(key).to_string())
+}
+
+/// フィールド単位のエラー
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    /// 機械可読なエラーコード（`forbidden` / `required` など）
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(
+        field: impl Into<String>,
+        code: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            code: code.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// RFC 7807 Problem Details
+#[derive(Serialize, ToSchema)]
+pub struct ProblemDetails {
+    /// 常に `about:blank`（`code` で種類を判別する）
+    #[serde(rename = "type")]
+    pub type_: &'static str,
+    /// HTTP ステータスの説明
+    pub title: String,
+    pub status: u16,
+    /// 人間向けのエラーメッセージ
+    pub detail: String,
+    /// 機械可読なエラーコード
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let code = self.code();
+        // 内部エラーの詳細はログにだけ残す
+        let detail = match &self {
+            ApiError::Database(e) => {
+                error!("Database error: {:?}", e);
+                "サーバーエラーが発生しました".to_string()
+            }
+            ApiError::Internal(e) => {
+                error!("Internal error: {:?}", e);
+                "サーバーエラーが発生しました".to_string()
+            }
+            other => other.to_string(),
+        };
+        let retry_after = match self {
+            ApiError::Locked(secs) | ApiError::RateLimited(secs) => Some(secs.to_string()),
+            _ => None,
+        };
+        let errors = match self {
+            ApiError::ForbiddenFields(fields) => fields
+                .into_iter()
+                .map(|f| FieldError::new(f, "forbidden", "このフィールドは変更できません"))
+                .collect(),
+            ApiError::Duplicate(Some(field)) => {
+                vec![FieldError::new(field, "duplicate", "既に使用されています")]
+            }
+            ApiError::Validation(errors) => errors,
+            _ => Vec::new(),
+        };
+        let body = ProblemDetails {
+            type_: "about:blank",
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail,
+            code,
+            errors,
+        };
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            Json(body),
+        )
+            .into_response();
+        if let Some(value) = retry_after.and_then(|v| v.parse().ok()) {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+        response
+    }
+}
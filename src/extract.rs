@@ -0,0 +1,89 @@
+use axum::{
+    extract::{FromRequest, FromRequestParts, Query, Request, rejection::JsonRejection},
+    http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+use validator::{Validate, ValidationErrors};
+
+use crate::error::{ApiError, FieldError};
+
+/// 本文を JSON としてデシリアライズし、`Validate` で検証するエクストラクタ
+/// 検証に失敗した場合はフィールドごとのエラーを 422 で返す.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(value) = axum::Json::<T>::from_request(req, state)
+            .await
+            .map_err(|e: JsonRejection| ApiError::BadRequest(e.body_text()))?;
+        value.validate()?;
+        Ok(ValidatedJson(value))
+    }
+}
+
+/// クエリ文字列をデシリアライズし、`Validate` で検証するエクストラクタ
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| ApiError::BadRequest(e.body_text()))?;
+        value.validate()?;
+        Ok(ValidatedQuery(value))
+    }
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields: Vec<FieldError> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |e| {
+                    let message = e
+                        .message
+                        .as_ref()
+                        .map(|m| m.to_string())
+                        .unwrap_or_else(|| default_message(e));
+                    FieldError::new(field.to_string(), e.code.to_string(), message)
+                })
+            })
+            .collect();
+        fields.sort_by(|a, b| a.field.cmp(&b.field));
+        ApiError::Validation(fields)
+    }
+}
+
+/// 組み込みの検証でメッセージが指定されていない場合の既定のメッセージ
+fn default_message(e: &validator::ValidationError) -> String {
+    let param = |name: &str| e.params.get(name).map(|v| v.to_string());
+    match e.code.as_ref() {
+        "email" => "メールアドレスの形式が正しくありません".to_string(),
+        "length" => match (param("min"), param("max")) {
+            (Some(min), Some(max)) => format!("{}〜{}文字で入力してください", min, max),
+            (Some(min), None) => format!("{}文字以上で入力してください", min),
+            (None, Some(max)) => format!("{}文字以内で入力してください", max),
+            (None, None) => "長さが正しくありません".to_string(),
+        },
+        "range" => match (param("min"), param("max")) {
+            (Some(min), Some(max)) => format!("{}〜{}の範囲で指定してください", min, max),
+            (Some(min), None) => format!("{}以上を指定してください", min),
+            (None, Some(max)) => format!("{}以下を指定してください", max),
+            (None, None) => "範囲外の値です".to_string(),
+        },
+        _ => "値が正しくありません".to_string(),
+    }
+}
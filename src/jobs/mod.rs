@@ -0,0 +1,533 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{LazyLock, OnceLock},
+    time::Duration,
+};
+
+use chrono::Utc;
+use sea_orm::{
+    sea_query::{Expr, Query},
+    *,
+};
+use serde_json::json;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
+
+use crate::{
+    audit::{self, AuditAction, AuditEntry},
+    db::DbConn,
+    mailer::{self, Locale, Template},
+    middleware::auth::ClientInfo,
+    models::{
+        access_tokens, account_deletion_request, auths, code, consents, discord,
+        email_change_request, email_verification, id_tokens, job_lock, mail_queue,
+        oidc_authorizations, password_history, password_reset, refresh_tokens, session, token_sets,
+        user, user_app, user_role, webhook_delivery,
+    },
+    utils::retry_queue::MAX_ATTEMPTS,
+    webhook,
+};
+
+/// ジョブによる操作を監査ログに記録するときの操作者
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// 一度に停止を解除する件数
+const BATCH_SIZE: u64 = 500;
+/// 送信済み・送信を諦めたメールと Webhook の行を調査用に残しておく日数
+const QUEUE_RETENTION_DAYS: i64 = 7;
+
+/// このプロセスの ID（実行権の持ち主として記録する）
+static INSTANCE_ID: LazyLock<String> = LazyLock::new(|| ulid::Ulid::new().to_string());
+
+/// 定期ジョブの設定
+#[derive(Clone, Debug)]
+pub struct JobsConfig {
+    pub enabled: bool,
+    /// 期限切れの停止を解除する間隔
+    pub suspension_interval: Duration,
+    /// 期限切れのセッション・トークンを削除する間隔
+    pub purge_interval: Duration,
+    /// 論理削除したユーザーを完全に削除するまでの期間
+    pub deleted_user_retention: Duration,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            suspension_interval: Duration::from_secs(60),
+            purge_interval: Duration::from_secs(60 * 60),
+            deleted_user_retention: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+static CONFIG: OnceLock<JobsConfig> = OnceLock::new();
+
+fn secs_from_env(key: &str, default: Duration) -> anyhow::Result<Duration> {
+    match std::env::var(key) {
+        Ok(v) => match v.parse() {
+            Ok(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+            _ => anyhow::bail!("{} must be a positive integer", key),
+        },
+        Err(_) => Ok(default),
+    }
+}
+
+/// 環境変数から定期ジョブの設定を読み込む
+pub fn init_from_env() -> anyhow::Result<()> {
+    let default = JobsConfig::default();
+    let config = JobsConfig {
+        enabled: std::env::var("JOBS_ENABLED")
+            .map(|v| v != "false")
+            .unwrap_or(default.enabled),
+        suspension_interval: secs_from_env(
+            "JOB_SUSPENSION_INTERVAL_SECS",
+            default.suspension_interval,
+        )?,
+        purge_interval: secs_from_env("JOB_PURGE_INTERVAL_SECS", default.purge_interval)?,
+        deleted_user_retention: match std::env::var("USER_DELETE_RETENTION_DAYS") {
+            Ok(v) => match v.parse::<u64>() {
+                Ok(days) => Duration::from_secs(days * 24 * 60 * 60),
+                _ => anyhow::bail!("USER_DELETE_RETENTION_DAYS must be a non-negative integer"),
+            },
+            Err(_) => default.deleted_user_retention,
+        },
+    };
+    if CONFIG.set(config).is_err() {
+        info!("Jobs are already initialized");
+    }
+    Ok(())
+}
+
+fn config() -> &'static JobsConfig {
+    CONFIG.get_or_init(JobsConfig::default)
+}
+
+type JobFuture<'a> = Pin<Box<dyn Future<Output = Result<(), DbErr>> + Send + 'a>>;
+
+/// 定期ジョブ
+struct Job {
+    name: &'static str,
+    interval: Duration,
+    run: for<'a> fn(&'a DbConn) -> JobFuture<'a>,
+}
+
+/// 定期ジョブをそれぞれの間隔で実行し続ける
+pub async fn run_scheduler(db: DbConn) {
+    let config = config();
+    if !config.enabled {
+        info!("Scheduled jobs are disabled");
+        return;
+    }
+    let jobs = [
+        Job {
+            name: "lift_expired_suspensions",
+            interval: config.suspension_interval,
+            run: |db| Box::pin(lift_expired_suspensions(db)),
+        },
+        Job {
+            name: "purge_expired",
+            interval: config.purge_interval,
+            run: |db| Box::pin(purge_expired(db)),
+        },
+        Job {
+            name: "purge_deleted_users",
+            interval: config.purge_interval,
+            run: |db| Box::pin(purge_deleted_users(db)),
+        },
+        Job {
+            name: "process_account_deletions",
+            interval: config.purge_interval,
+            run: |db| Box::pin(process_account_deletions(db)),
+        },
+    ];
+    for job in jobs {
+        tokio::spawn(run_job(db.clone(), job));
+    }
+}
+
+async fn run_job(db: DbConn, job: Job) {
+    let mut interval = tokio::time::interval(job.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match acquire_lease(&db, job.name, job.interval).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                error!("Failed to acquire lease for job {}: {:?}", job.name, e);
+                continue;
+            }
+        }
+        if let Err(e) = (job.run)(&db).await {
+            error!("Job {} failed: {:?}", job.name, e);
+        }
+    }
+}
+
+/// ジョブの実行権を取得・延長する
+///
+/// 複数台で動かしている場合に 1 台だけが実行するよう、`job_locks` の行をリースとして使う.
+/// 期限は間隔の 1.5 倍とし、持ち主が落ちた場合は期限切れ後に他の台が引き継ぐ.
+async fn acquire_lease(db: &DbConn, name: &str, interval: Duration) -> Result<bool, DbErr> {
+    let now = Utc::now().naive_utc();
+    let ttl = chrono::Duration::from_std(interval * 3 / 2).unwrap_or(chrono::Duration::MAX);
+    let until = now.checked_add_signed(ttl).unwrap_or(now);
+    let owner = INSTANCE_ID.as_str();
+
+    let renewed = job_lock::Entity::update_many()
+        .col_expr(job_lock::Column::Owner, Expr::value(owner))
+        .col_expr(job_lock::Column::LockedUntil, Expr::value(until))
+        .filter(job_lock::Column::Name.eq(name))
+        .filter(
+            Condition::any()
+                .add(job_lock::Column::LockedUntil.lt(now))
+                .add(job_lock::Column::Owner.eq(owner)),
+        )
+        .exec(db)
+        .await?;
+    if renewed.rows_affected > 0 {
+        return Ok(true);
+    }
+
+    // 行がまだ無い場合は作る（同時に作ろうとした他の台とは主キーで競合する）
+    let inserted = job_lock::ActiveModel {
+        name: Set(name.to_string()),
+        owner: Set(owner.to_string()),
+        locked_until: Set(until),
+    }
+    .insert(db)
+    .await;
+    match inserted {
+        Ok(_) => Ok(true),
+        Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// 期限（`suspended_until`）を過ぎた停止を解除する
+async fn lift_expired_suspensions(db: &DbConn) -> Result<(), DbErr> {
+    let now = Utc::now().naive_utc();
+    let expired = user::Entity::find_active()
+        .filter(user::Column::IsSuspended.eq(true))
+        .filter(user::Column::SuspendedUntil.lte(now))
+        .order_by_asc(user::Column::SuspendedUntil)
+        .limit(BATCH_SIZE)
+        .all(db)
+        .await?;
+
+    for user in expired {
+        // 読んでから更新するまでに停止内容が変えられていれば触らない
+        let txn = db.begin().await?;
+        let lifted = user::Entity::update_many()
+            .col_expr(user::Column::IsSuspended, Expr::value(false))
+            .col_expr(
+                user::Column::SuspendedUntil,
+                Expr::value(Option::<chrono::NaiveDateTime>::None),
+            )
+            .col_expr(
+                user::Column::SuspendedReason,
+                Expr::value(Option::<String>::None),
+            )
+            .col_expr(user::Column::UpdatedAt, Expr::value(now))
+            .filter(user::Column::Id.eq(&user.id))
+            .filter(user::Column::IsSuspended.eq(true))
+            .filter(user::Column::SuspendedUntil.eq(user.suspended_until))
+            .exec(&txn)
+            .await?;
+        if lifted.rows_affected == 0 {
+            continue;
+        }
+
+        let after = user::Model {
+            is_suspended: Some(false),
+            suspended_until: None,
+            suspended_reason: None,
+            updated_at: Some(now),
+            ..user.clone()
+        };
+        AuditEntry::updated(AuditAction::SuspensionExpire, &user.id, &user, &after)
+            .record_as(&txn, SYSTEM_ACTOR, &ClientInfo::default())
+            .await?;
+        txn.commit().await?;
+        webhook::enqueue(
+            db,
+            "user.suspension_expired",
+            json!({
+                "user_id": user.id,
+                "suspended_until": user.suspended_until.map(|t| t.and_utc()),
+            }),
+        )
+        .await?;
+        info!("Suspension of user {} expired", user.id);
+    }
+    Ok(())
+}
+
+/// 期限切れのセッション・確認トークン・認可コードを削除する
+async fn purge_expired(db: &DbConn) -> Result<(), DbErr> {
+    let now = Utc::now();
+    let naive_now = now.naive_utc();
+    let mut purged: BTreeMap<&str, u64> = BTreeMap::new();
+
+    let res = session::Entity::delete_many()
+        .filter(session::Column::ExpiresAt.lt(now))
+        .exec(db)
+        .await?;
+    purged.insert("sessions", res.rows_affected);
+
+    let res = email_verification::Entity::delete_many()
+        .filter(email_verification::Column::ExpiresAt.lt(naive_now))
+        .exec(db)
+        .await?;
+    purged.insert("email_verifications", res.rows_affected);
+
+    let res = password_reset::Entity::delete_many()
+        .filter(password_reset::Column::ExpiresAt.lt(naive_now))
+        .exec(db)
+        .await?;
+    purged.insert("password_resets", res.rows_affected);
+
+    let res = email_change_request::Entity::delete_many()
+        .filter(email_change_request::Column::ExpiresAt.lt(naive_now))
+        .exec(db)
+        .await?;
+    purged.insert("email_change_requests", res.rows_affected);
+
+    // 確定済みの申請は確認コードの期限を過ぎても残す
+    let res = account_deletion_request::Entity::delete_many()
+        .filter(account_deletion_request::Column::ConfirmedAt.is_null())
+        .filter(account_deletion_request::Column::ExpiresAt.lt(naive_now))
+        .exec(db)
+        .await?;
+    purged.insert("account_deletion_requests", res.rows_affected);
+
+    // 認可に使われたコードはトークンの発行元として参照されているため残す
+    let res = code::Entity::delete_many()
+        .filter(code::Column::Exp.lt(now))
+        .filter(
+            Expr::col(code::Column::Id).not_in_subquery(
+                Query::select()
+                    .column(oidc_authorizations::Column::CodeId)
+                    .from(oidc_authorizations::Entity)
+                    .to_owned(),
+            ),
+        )
+        .exec(db)
+        .await?;
+    purged.insert("code", res.rows_affected);
+
+    // 送信したメールの行は送信時に消しているため、送信を諦めた行だけが残る
+    let queue_cutoff = naive_now - chrono::Duration::days(QUEUE_RETENTION_DAYS);
+    let res = mail_queue::Entity::delete_many()
+        .filter(mail_queue::Column::Body.is_null())
+        .filter(mail_queue::Column::CreatedAt.lt(queue_cutoff))
+        .exec(db)
+        .await?;
+    purged.insert("mail_queue", res.rows_affected);
+
+    let res = webhook_delivery::Entity::delete_many()
+        .filter(
+            Condition::any()
+                .add(webhook_delivery::Column::DeliveredAt.is_not_null())
+                .add(webhook_delivery::Column::Attempts.gte(MAX_ATTEMPTS)),
+        )
+        .filter(webhook_delivery::Column::CreatedAt.lt(queue_cutoff))
+        .exec(db)
+        .await?;
+    purged.insert("webhook_deliveries", res.rows_affected);
+
+    if purged.values().all(|&n| n == 0) {
+        return Ok(());
+    }
+    info!("Purged expired rows: {:?}", purged);
+    AuditEntry::new(AuditAction::SystemPurge, "purge_expired")
+        .with_after(json!(purged))
+        .record_as(db, SYSTEM_ACTOR, &ClientInfo::default())
+        .await?;
+    webhook::enqueue(db, "system.purged", json!(purged)).await?;
+    Ok(())
+}
+
+/// 保持期間を過ぎた論理削除済みのユーザーを完全に削除する
+async fn purge_deleted_users(db: &DbConn) -> Result<(), DbErr> {
+    let retention = chrono::Duration::from_std(config().deleted_user_retention)
+        .unwrap_or(chrono::Duration::MAX);
+    let now = Utc::now().naive_utc();
+    let cutoff = now.checked_sub_signed(retention).unwrap_or(now);
+    let expired = user::Entity::find()
+        .filter(user::Column::DeletedAt.lte(cutoff))
+        .order_by_asc(user::Column::DeletedAt)
+        .limit(BATCH_SIZE)
+        .all(db)
+        .await?;
+
+    for user in expired {
+        let txn = db.begin().await?;
+        purge_user(&txn, &user.id).await?;
+        // 削除したユーザーの内容は監査ログに残さない
+        AuditEntry::new(AuditAction::UserPurge, &user.id)
+            .record_as(&txn, SYSTEM_ACTOR, &ClientInfo::default())
+            .await?;
+        txn.commit().await?;
+
+        webhook::enqueue(
+            db,
+            "user.purged",
+            json!({
+                "user_id": user.id,
+                "deleted_at": user.deleted_at.map(|t| t.and_utc()),
+            }),
+        )
+        .await?;
+        info!("Purged deleted user {}", user.id);
+    }
+    Ok(())
+}
+
+/// 猶予期間を過ぎたアカウント削除の申請を処理し、ユーザーを完全に削除する
+async fn process_account_deletions(db: &DbConn) -> Result<(), DbErr> {
+    let now = Utc::now().naive_utc();
+    let due = account_deletion_request::Entity::find()
+        .filter(account_deletion_request::Column::ScheduledAt.lte(now))
+        .order_by_asc(account_deletion_request::Column::ScheduledAt)
+        .limit(BATCH_SIZE)
+        .all(db)
+        .await?;
+
+    for request in due {
+        // 論理削除済みのユーザーも本人の申請どおりに削除する
+        let Some(user) = user::Entity::find_by_id(&request.user_id).one(db).await? else {
+            account_deletion_request::Entity::delete_by_id(request.id)
+                .exec(db)
+                .await?;
+            continue;
+        };
+
+        let txn = db.begin().await?;
+        purge_user(&txn, &user.id).await?;
+        AuditEntry::new(AuditAction::DeletionComplete, &user.id)
+            .record_as(&txn, SYSTEM_ACTOR, &ClientInfo::default())
+            .await?;
+        txn.commit().await?;
+
+        let mail = Template::AccountDeleted { name: &user.name }
+            .to_mail(&user.external_email, Locale::from_env());
+        mailer::queue::enqueue(db, mail).await?;
+        webhook::enqueue(
+            db,
+            "user.deleted",
+            json!({
+                "user_id": user.id,
+                "requested_at": request.created_at.and_utc(),
+            }),
+        )
+        .await?;
+        info!("Deleted user {} on request", user.id);
+    }
+    Ok(())
+}
+
+/// ユーザーと、ユーザーを参照するすべての行を外部キーを壊さない順に削除する
+/// 監査ログは残し、ユーザーの個人情報だけを消去する.
+async fn purge_user(txn: &DatabaseTransaction, user_id: &str) -> Result<(), DbErr> {
+    audit::redact_user(txn, user_id, SYSTEM_ACTOR).await?;
+
+    // OIDC の認可: token_sets → 各トークン → oidc_authorizations → code / consents → auths
+    let auth_ids: Vec<i32> = auths::Entity::find()
+        .select_only()
+        .column(auths::Column::Id)
+        .filter(auths::Column::AuthUserId.eq(user_id))
+        .into_tuple()
+        .all(txn)
+        .await?;
+    let authorizations = oidc_authorizations::Entity::find()
+        .filter(oidc_authorizations::Column::AuthId.is_in(auth_ids.clone()))
+        .all(txn)
+        .await?;
+    let authorization_ids: Vec<i32> = authorizations.iter().map(|a| a.id).collect();
+
+    token_sets::Entity::delete_many()
+        .filter(token_sets::Column::OidcAuthorizationId.is_in(authorization_ids.clone()))
+        .exec(txn)
+        .await?;
+    access_tokens::Entity::delete_many()
+        .filter(access_tokens::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    refresh_tokens::Entity::delete_many()
+        .filter(refresh_tokens::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    id_tokens::Entity::delete_many()
+        .filter(id_tokens::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    oidc_authorizations::Entity::delete_many()
+        .filter(oidc_authorizations::Column::Id.is_in(authorization_ids))
+        .exec(txn)
+        .await?;
+    code::Entity::delete_many()
+        .filter(code::Column::Id.is_in(authorizations.iter().map(|a| a.code_id)))
+        .exec(txn)
+        .await?;
+    // 同意は他の認可からも参照されうるため、参照が無くなったものだけ削除する
+    consents::Entity::delete_many()
+        .filter(consents::Column::Id.is_in(authorizations.iter().map(|a| a.consent_id)))
+        .filter(
+            Expr::col(consents::Column::Id).not_in_subquery(
+                Query::select()
+                    .column(oidc_authorizations::Column::ConsentId)
+                    .from(oidc_authorizations::Entity)
+                    .to_owned(),
+            ),
+        )
+        .exec(txn)
+        .await?;
+    auths::Entity::delete_many()
+        .filter(auths::Column::Id.is_in(auth_ids))
+        .exec(txn)
+        .await?;
+
+    session::Entity::delete_many()
+        .filter(session::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    discord::Entity::delete_many()
+        .filter(discord::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    user_role::Entity::delete_many()
+        .filter(user_role::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    user_app::Entity::delete_many()
+        .filter(user_app::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    email_verification::Entity::delete_many()
+        .filter(email_verification::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    password_reset::Entity::delete_many()
+        .filter(password_reset::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    password_history::Entity::delete_many()
+        .filter(password_history::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    email_change_request::Entity::delete_many()
+        .filter(email_change_request::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    account_deletion_request::Entity::delete_many()
+        .filter(account_deletion_request::Column::UserId.eq(user_id))
+        .exec(txn)
+        .await?;
+    user::Entity::delete_by_id(user_id).exec(txn).await?;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::future::BoxFuture;
+
+use super::{FailureState, LockoutStore};
+
+/// プロセス内のメモリに失敗回数を保持する LockoutStore
+/// NOTE: API サーバーを複数台で動かす場合はサーバーごとに別々に数えられる.
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, (FailureState, DateTime<Utc>)>>,
+}
+
+impl LockoutStore for MemoryStore {
+    fn load<'a>(&'a self, key: &'a str) -> BoxFuture<'a, anyhow::Result<Option<FailureState>>> {
+        Box::pin(async move {
+            let entries = self.entries.lock().unwrap();
+            Ok(entries
+                .get(key)
+                .filter(|(_, expires_at)| *expires_at > Utc::now())
+                .map(|(state, _)| state.clone()))
+        })
+    }
+
+    fn increment<'a>(
+        &'a self,
+        key: &'a str,
+        ttl: Duration,
+    ) -> BoxFuture<'a, anyhow::Result<FailureState>> {
+        Box::pin(async move {
+            let mut entries = self.entries.lock().unwrap();
+            // 期限切れのエントリは書き込みのついでに掃除する
+            let now = Utc::now();
+            entries.retain(|_, (_, e)| *e > now);
+            let failures = entries.get(key).map_or(0, |(s, _)| s.failures);
+            let state = FailureState {
+                failures: failures.saturating_add(1),
+                last_failure_at: now,
+            };
+            entries.insert(key.to_string(), (state.clone(), now + ttl));
+            Ok(state)
+        })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.entries.lock().unwrap().remove(key);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,259 @@
+pub mod memory;
+
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Duration, Utc};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    db::DbConn,
+    error::ApiError,
+    middleware::auth::ClientInfo,
+};
+
+/// 認証失敗の状態
+/// 共有ストア（Redis など）に置けるようシリアライズ可能にしておく.
+/// 待ち時間・ロックの期限はポリシーから求めるため保存しない.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct FailureState {
+    /// 連続した失敗回数
+    pub failures: u32,
+    #[schema(value_type = String, format = "date-time")]
+    pub last_failure_at: DateTime<Utc>,
+}
+
+/// 失敗回数の保存先の抽象
+/// 複数台構成にする場合は共有ストアでこの trait を実装して差し替える.
+pub trait LockoutStore: Send + Sync {
+    /// 期限切れのものは `None` を返すこと
+    fn load<'a>(&'a self, key: &'a str) -> BoxFuture<'a, anyhow::Result<Option<FailureState>>>;
+    /// 失敗回数を1つ増やし、`last_failure_at` を現在時刻、期限を現在から `ttl` 後にする
+    /// 同時に呼ばれても数え漏れのないよう、読み出しと書き込みを不可分に行うこと.
+    /// 期限切れのものは 0 回から数え直す.
+    fn increment<'a>(
+        &'a self,
+        key: &'a str,
+        ttl: Duration,
+    ) -> BoxFuture<'a, anyhow::Result<FailureState>>;
+    fn remove<'a>(&'a self, key: &'a str) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+/// 失敗回数を数える単位
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Subject<'a> {
+    /// ユーザーID
+    Account(&'a str),
+    /// 接続元 IP アドレス
+    Ip(&'a str),
+}
+
+impl Subject<'_> {
+    fn key(&self) -> String {
+        match self {
+            Subject::Account(id) => format!("account:{}", id),
+            Subject::Ip(ip) => format!("ip:{}", ip),
+        }
+    }
+}
+
+/// ロックアウトの設定
+pub struct LockoutPolicy {
+    /// アカウント単位でロックするまでの失敗回数
+    pub account_max_failures: u32,
+    /// IP 単位でロックするまでの失敗回数
+    pub ip_max_failures: u32,
+    /// 1回目の失敗後の待ち時間（秒）。以降失敗するごとに倍になる
+    pub base_delay_secs: i64,
+    /// ロックの期間（秒）
+    pub lockout_secs: i64,
+    /// 最後の失敗からこの秒数が経つと失敗回数をリセットする
+    pub reset_after_secs: i64,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            account_max_failures: 5,
+            ip_max_failures: 50,
+            base_delay_secs: 1,
+            lockout_secs: 15 * 60,
+            reset_after_secs: 15 * 60,
+        }
+    }
+}
+
+impl LockoutPolicy {
+    fn max_failures(&self, subject: &Subject<'_>) -> u32 {
+        match subject {
+            Subject::Account(_) => self.account_max_failures,
+            Subject::Ip(_) => self.ip_max_failures,
+        }
+    }
+
+    /// `failures` 回目の失敗の後に待たせる時間（秒）
+    fn delay_secs(&self, failures: u32, max_failures: u32) -> i64 {
+        if failures >= max_failures {
+            return self.lockout_secs;
+        }
+        let exp = failures.saturating_sub(1).min(30);
+        self.base_delay_secs
+            .saturating_mul(1 << exp)
+            .min(self.lockout_secs)
+    }
+
+    /// 失敗回数を保持する期間
+    /// 待ち時間は `lockout_secs` を超えないため、最長の待ち時間が明けてから `reset_after_secs` 後に消える.
+    fn ttl(&self) -> Duration {
+        Duration::seconds(self.lockout_secs.saturating_add(self.reset_after_secs))
+    }
+}
+
+pub struct Lockout {
+    store: Box<dyn LockoutStore>,
+    policy: LockoutPolicy,
+}
+
+static LOCKOUT: OnceLock<Lockout> = OnceLock::new();
+
+fn env_parse<T: std::str::FromStr>(key: &str, default: T) -> anyhow::Result<T> {
+    match std::env::var(key) {
+        Ok(v) => v
+            .parse()
+            .map_err(|_| anyhow::anyhow!("{} has an invalid value: {}", key, v)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// 環境変数からロックアウトを初期化する
+/// - `LOCKOUT_BACKEND`: `memory`（既定）
+/// - `LOCKOUT_ACCOUNT_MAX_FAILURES`: アカウント単位でロックするまでの失敗回数（既定: 5）
+/// - `LOCKOUT_IP_MAX_FAILURES`: IP 単位でロックするまでの失敗回数（既定: 50）
+/// - `LOCKOUT_BASE_DELAY_SECS`: 1回目の失敗後の待ち時間（既定: 1）
+/// - `LOCKOUT_DURATION_SECS`: ロックの期間（既定: 900）
+/// - `LOCKOUT_RESET_AFTER_SECS`: 失敗回数をリセットするまでの時間（既定: 900）
+pub fn init_from_env() -> anyhow::Result<()> {
+    let backend = std::env::var("LOCKOUT_BACKEND").unwrap_or_else(|_| "memory".to_string());
+    let store: Box<dyn LockoutStore> = match backend.as_str() {
+        "memory" => Box::new(memory::MemoryStore::default()),
+        other => anyhow::bail!("Unknown LOCKOUT_BACKEND: {}", other),
+    };
+    let default = LockoutPolicy::default();
+    let policy = LockoutPolicy {
+        account_max_failures: env_parse(
+            "LOCKOUT_ACCOUNT_MAX_FAILURES",
+            default.account_max_failures,
+        )?,
+        ip_max_failures: env_parse("LOCKOUT_IP_MAX_FAILURES", default.ip_max_failures)?,
+        base_delay_secs: env_parse("LOCKOUT_BASE_DELAY_SECS", default.base_delay_secs)?,
+        lockout_secs: env_parse("LOCKOUT_DURATION_SECS", default.lockout_secs)?,
+        reset_after_secs: env_parse("LOCKOUT_RESET_AFTER_SECS", default.reset_after_secs)?,
+    };
+    if policy.account_max_failures == 0 || policy.ip_max_failures == 0 {
+        anyhow::bail!("LOCKOUT_*_MAX_FAILURES must be at least 1");
+    }
+    if LOCKOUT.set(Lockout { store, policy }).is_err() {
+        info!("Lockout is already initialized");
+    }
+    info!("Lockout backend: {}", backend);
+    Ok(())
+}
+
+/// 登録済みのロックアウトを取得する（未登録の場合はメモリ上・既定値）
+pub fn lockout() -> &'static Lockout {
+    LOCKOUT.get_or_init(|| Lockout {
+        store: Box::new(memory::MemoryStore::default()),
+        policy: LockoutPolicy::default(),
+    })
+}
+
+/// 認証の対象となるアカウントと接続元 IP
+pub fn subjects<'a>(user_id: &'a str, client: &'a ClientInfo) -> Vec<Subject<'a>> {
+    let mut subjects = vec![Subject::Account(user_id)];
+    if let Some(ref ip) = client.ip_address {
+        subjects.push(Subject::Ip(ip));
+    }
+    subjects
+}
+
+impl Lockout {
+    /// いずれかの対象が待ち時間中・ロック中であれば 429 を返す
+    pub async fn check(&self, subjects: &[Subject<'_>]) -> Result<(), ApiError> {
+        let now = Utc::now();
+        let mut retry_after = 0;
+        for subject in subjects {
+            let locked_until = self
+                .store
+                .load(&subject.key())
+                .await?
+                .map(|s| self.locked_until(subject, &s))
+                .filter(|until| *until > now);
+            if let Some(until) = locked_until {
+                retry_after = retry_after.max((until - now).num_seconds() + 1);
+            }
+        }
+        if retry_after > 0 {
+            return Err(ApiError::Locked(retry_after as u64));
+        }
+        Ok(())
+    }
+
+    /// 認証の失敗を記録する
+    /// この失敗でロックされた対象は監査ログに残す.
+    pub async fn record_failure(
+        &self,
+        db: &DbConn,
+        subjects: &[Subject<'_>],
+        actor_id: &str,
+        client: &ClientInfo,
+    ) -> Result<(), ApiError> {
+        for subject in subjects {
+            let key = subject.key();
+            let state = self.store.increment(&key, self.policy.ttl()).await?;
+            let failures = state.failures;
+            let locked_until = self.locked_until(subject, &state);
+
+            if failures >= self.policy.max_failures(subject) {
+                warn!("Locked {} after {} failures", key, failures);
+                let entry = match subject {
+                    Subject::Account(id) => AuditEntry::new(AuditAction::AccountLock, *id),
+                    Subject::Ip(ip) => AuditEntry::new(AuditAction::IpLock, *ip),
+                };
+                entry
+                    .with_after(json!({ "failures": failures, "locked_until": locked_until }))
+                    .record_as(db, actor_id, client)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 認証に成功したらアカウントの失敗回数をリセットする
+    /// IP 単位の失敗回数は他のアカウントへの試行を含むためリセットしない.
+    pub async fn record_success(&self, user_id: &str) -> Result<(), ApiError> {
+        self.store.remove(&Subject::Account(user_id).key()).await?;
+        Ok(())
+    }
+
+    /// 失敗の状態から、次の試行を受け付けるまでの日時を求める
+    pub fn locked_until(&self, subject: &Subject<'_>, state: &FailureState) -> DateTime<Utc> {
+        let max_failures = self.policy.max_failures(subject);
+        state.last_failure_at
+            + Duration::seconds(self.policy.delay_secs(state.failures, max_failures))
+    }
+
+    /// 現在の失敗状態
+    pub async fn status(&self, subject: Subject<'_>) -> Result<Option<FailureState>, ApiError> {
+        Ok(self.store.load(&subject.key()).await?)
+    }
+
+    /// ロックを解除し、失敗回数をリセットする
+    pub async fn unlock(&self, subject: Subject<'_>) -> Result<(), ApiError> {
+        self.store.remove(&subject.key()).await?;
+        Ok(())
+    }
+}
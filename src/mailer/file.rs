@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use futures::future::BoxFuture;
+use ulid::Ulid;
+
+use super::{Mail, Mailer};
+
+/// メールを 1 通ずつ `.eml` ファイルとして書き出す Mailer（開発・テスト用）
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn from_env() -> Self {
+        let dir = std::env::var("MAIL_FILE_DIR").unwrap_or_else(|_| "./mails".to_string());
+        Self { dir: dir.into() }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send<'a>(&'a self, mail: &'a Mail) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            let path = self.dir.join(format!("{}.eml", Ulid::new()));
+            let content = format!(
+                "To: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+                mail.to, mail.subject, mail.body
+            );
+            tokio::fs::write(path, content).await?;
+            Ok(())
+        })
+    }
+}
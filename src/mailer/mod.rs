@@ -0,0 +1,71 @@
+pub mod file;
+pub mod queue;
+pub mod smtp;
+pub mod templates;
+
+use std::sync::OnceLock;
+
+use futures::future::BoxFuture;
+use tracing::info;
+
+pub use templates::{Locale, Template};
+
+/// 送信するメール
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// メール送信処理の抽象
+/// 実際の送信手段（SMTP など）はこの trait を実装して差し替える.
+pub trait Mailer: Send + Sync {
+    fn send<'a>(&'a self, mail: &'a Mail) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+/// 実際には送信せずログへ出力するだけの Mailer（開発用）
+/// 本文にはリンクのトークンなどが含まれるため、宛先と件名だけを出力する.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send<'a>(&'a self, mail: &'a Mail) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            info!("[mail] to={} subject={}", mail.to, mail.subject);
+            Ok(())
+        })
+    }
+}
+
+static MAILER: OnceLock<Box<dyn Mailer>> = OnceLock::new();
+
+/// 環境変数 `MAIL_BACKEND` に応じて Mailer を初期化する
+/// - `smtp`: SMTP サーバー経由で送信
+/// - `file`: `MAIL_FILE_DIR` にファイルとして書き出す
+/// - `log` (既定): ログに出力するだけ
+pub fn init_from_env() -> anyhow::Result<()> {
+    let backend = std::env::var("MAIL_BACKEND").unwrap_or_else(|_| "log".to_string());
+    let mailer: Box<dyn Mailer> = match backend.as_str() {
+        "smtp" => Box::new(smtp::SmtpMailer::from_env()?),
+        "file" => Box::new(file::FileMailer::from_env()),
+        "log" => Box::new(LogMailer),
+        other => anyhow::bail!("Unknown MAIL_BACKEND: {}", other),
+    };
+    if MAILER.set(mailer).is_err() {
+        info!("Mailer is already initialized");
+    }
+    info!("Mailer backend: {}", backend);
+    Ok(())
+}
+
+/// 登録済みの Mailer を取得する（未登録の場合は LogMailer）
+pub fn mailer() -> &'static dyn Mailer {
+    MAILER.get_or_init(|| Box::new(LogMailer)).as_ref()
+}
+
+/// メール本文中のリンクに使う公開 URL
+pub fn public_url(path: &str) -> String {
+    let base_url =
+        std::env::var("PUBLIC_BASE_URL").unwrap_or_else(|_| "https://uniproject.jp".to_string());
+    format!("{}{}", base_url.trim_end_matches('/'), path)
+}
@@ -0,0 +1,94 @@
+use chrono::Utc;
+use sea_orm::*;
+use tracing::{error, info, warn};
+
+use super::{Mail, mailer};
+use crate::{
+    db::DbConn,
+    models::mail_queue,
+    utils::retry_queue::{self, MAX_ATTEMPTS, RetryQueue},
+};
+
+impl RetryQueue for mail_queue::Entity {
+    fn id_column() -> Self::Column {
+        mail_queue::Column::Id
+    }
+    fn attempts_column() -> Self::Column {
+        mail_queue::Column::Attempts
+    }
+    fn next_attempt_at_column() -> Self::Column {
+        mail_queue::Column::NextAttemptAt
+    }
+}
+
+/// メールを送信キューに積む
+/// 実際の送信は `run_worker` が行う.
+/// 本文にはリンクのトークンなどが含まれるため、送信した行は削除し、送信を諦めた行は本文を消す.
+pub async fn enqueue(db: &DbConn, mail: Mail) -> Result<(), DbErr> {
+    let now = Utc::now().naive_utc();
+    let am = mail_queue::ActiveModel {
+        to_address: Set(mail.to),
+        subject: Set(mail.subject),
+        body: Set(Some(mail.body)),
+        attempts: Set(0),
+        last_error: Set(None),
+        next_attempt_at: Set(now),
+        created_at: Set(Some(now)),
+        ..Default::default()
+    };
+    am.insert(db).await?;
+    Ok(())
+}
+
+/// 送信キューを定期的に処理し続ける
+pub async fn run_worker(db: DbConn) {
+    retry_queue::run("mail queue", || process_due(&db)).await;
+}
+
+/// 送信時刻を迎えたメールを送信する
+async fn process_due(db: &DbConn) -> Result<(), DbErr> {
+    let due = retry_queue::due::<mail_queue::Entity>(Utc::now().naive_utc())
+        .filter(mail_queue::Column::Body.is_not_null())
+        .all(db)
+        .await?;
+
+    for item in due {
+        let Some(body) = item.body.clone() else {
+            continue;
+        };
+        if !retry_queue::claim::<mail_queue::Entity, _>(db, item.id, item.attempts).await? {
+            continue;
+        }
+        let attempts = item.attempts + 1;
+
+        let mail = Mail {
+            to: item.to_address.clone(),
+            subject: item.subject.clone(),
+            body,
+        };
+        let mut am: mail_queue::ActiveModel = item.into();
+        am.attempts = Set(attempts);
+        match mailer().send(&mail).await {
+            Ok(()) => {
+                info!("Mail sent to {}", mail.to);
+                am.delete(db).await?;
+                continue;
+            }
+            Err(e) => {
+                if attempts >= MAX_ATTEMPTS {
+                    error!("Giving up sending mail to {}: {:?}", mail.to, e);
+                    am.body = Set(None);
+                } else {
+                    warn!(
+                        "Failed to send mail to {} (attempt {}): {:?}",
+                        mail.to, attempts, e
+                    );
+                }
+                am.last_error = Set(Some(e.to_string()));
+                am.next_attempt_at = Set(Utc::now().naive_utc() + retry_queue::backoff(attempts));
+            }
+        }
+        am.update(db).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use anyhow::Context;
+use futures::future::BoxFuture;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+
+use super::{Mail, Mailer};
+
+/// SMTP サーバー経由で送信する Mailer
+///
+/// | 環境変数        | 説明                                        |
+/// | --------------- | ------------------------------------------- |
+/// | `SMTP_HOST`     | SMTP サーバー（必須）                       |
+/// | `SMTP_PORT`     | ポート番号                                  |
+/// | `SMTP_TLS`      | `starttls`（既定） / `tls` / `none`         |
+/// | `SMTP_USERNAME` | 認証ユーザー名                              |
+/// | `SMTP_PASSWORD` | 認証パスワード                              |
+/// | `MAIL_FROM`     | 差出人（例: `UniQUE <noreply@uniproject.jp>`） |
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let host = std::env::var("SMTP_HOST").context("SMTP_HOST not set")?;
+        let tls = std::env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string());
+
+        let mut builder = match tls.as_str() {
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?,
+        };
+        if let Ok(port) = std::env::var("SMTP_PORT") {
+            builder = builder.port(port.parse().context("SMTP_PORT is not a number")?);
+        }
+        if let (Ok(username), Ok(password)) = (
+            std::env::var("SMTP_USERNAME"),
+            std::env::var("SMTP_PASSWORD"),
+        ) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        let from = std::env::var("MAIL_FROM")
+            .unwrap_or_else(|_| "UniQUE <noreply@uniproject.jp>".to_string())
+            .parse()
+            .context("MAIL_FROM is not a valid mailbox")?;
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send<'a>(&'a self, mail: &'a Mail) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(mail.to.parse()?)
+                .subject(&mail.subject)
+                .header(ContentType::TEXT_PLAIN)
+                .body(mail.body.clone())?;
+            self.transport.send(message).await?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,427 @@
+use chrono::NaiveDateTime;
+
+use super::Mail;
+
+/// メール本文の言語
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locale {
+    Ja,
+    En,
+}
+
+impl Locale {
+    /// 環境変数 `MAIL_LOCALE`（`ja` / `en`）から既定の言語を決める
+    pub fn from_env() -> Self {
+        match std::env::var("MAIL_LOCALE").as_deref() {
+            Ok("en") => Locale::En,
+            _ => Locale::Ja,
+        }
+    }
+}
+
+/// 送信するメールの種類と差し込む値
+pub enum Template<'a> {
+    /// Email検証
+    EmailVerification {
+        name: &'a str,
+        url: &'a str,
+        expires_at: NaiveDateTime,
+    },
+    /// メールアドレス変更時の新アドレスの確認
+    EmailChangeVerification {
+        name: &'a str,
+        new_address: &'a str,
+        url: &'a str,
+        expires_at: NaiveDateTime,
+    },
+    /// メールアドレス変更を旧アドレスへ知らせる通知
+    EmailChangeNotice {
+        name: &'a str,
+        new_address: &'a str,
+        cancel_url: &'a str,
+    },
+    /// パスワード再設定
+    PasswordReset {
+        name: &'a str,
+        url: &'a str,
+        ttl_minutes: i64,
+    },
+    /// 新しい端末からのログイン通知
+    // NOTE: ログイン自体は認証サーバー側で行うため、この API からはまだ送信していない.
+    #[allow(dead_code)]
+    NewLogin {
+        name: &'a str,
+        ip_address: &'a str,
+        user_agent: &'a str,
+        logged_in_at: NaiveDateTime,
+    },
+    /// アカウント停止のお知らせ
+    Suspension {
+        name: &'a str,
+        until: Option<NaiveDateTime>,
+        reason: Option<&'a str>,
+    },
+    /// アカウント削除の申請の確認
+    AccountDeletionConfirmation {
+        name: &'a str,
+        url: &'a str,
+        expires_at: NaiveDateTime,
+        cooling_off_days: i64,
+    },
+    /// アカウント削除の予定と取り消し方法
+    AccountDeletionScheduled {
+        name: &'a str,
+        scheduled_at: NaiveDateTime,
+        cancel_url: &'a str,
+    },
+    /// アカウント削除の完了
+    AccountDeleted { name: &'a str },
+}
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M (UTC)";
+
+impl Template<'_> {
+    /// (件名, 本文) を組み立てる
+    pub fn render(&self, locale: Locale) -> (String, String) {
+        match (self, locale) {
+            (
+                Template::EmailVerification {
+                    name,
+                    url,
+                    expires_at,
+                },
+                Locale::Ja,
+            ) => (
+                "メールアドレスの確認".to_string(),
+                format!(
+                    "{name} さん\n\n\
+                     UniQUE に登録されたメールアドレスの確認をお願いします。\n\
+                     以下のリンクを開くと確認が完了します。\n\n\
+                     {url}\n\n\
+                     有効期限: {}\n\
+                     お心当たりがない場合はこのメールを破棄してください。\n",
+                    expires_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (
+                Template::EmailVerification {
+                    name,
+                    url,
+                    expires_at,
+                },
+                Locale::En,
+            ) => (
+                "Verify your email address".to_string(),
+                format!(
+                    "Hi {name},\n\n\
+                     Please confirm the email address registered to your UniQUE account\n\
+                     by opening the link below.\n\n\
+                     {url}\n\n\
+                     This link expires at {}.\n\
+                     If you did not request this, you can safely ignore this email.\n",
+                    expires_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (
+                Template::EmailChangeVerification {
+                    name,
+                    new_address,
+                    url,
+                    expires_at,
+                },
+                Locale::Ja,
+            ) => (
+                "新しいメールアドレスの確認".to_string(),
+                format!(
+                    "{name} さん\n\n\
+                     UniQUE アカウントのメールアドレスを {new_address} に変更するリクエストを受け付けました。\n\
+                     以下のリンクを開くと変更が完了します。\n\n\
+                     {url}\n\n\
+                     有効期限: {}\n\
+                     お心当たりがない場合はこのメールを破棄してください。\n",
+                    expires_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (
+                Template::EmailChangeVerification {
+                    name,
+                    new_address,
+                    url,
+                    expires_at,
+                },
+                Locale::En,
+            ) => (
+                "Confirm your new email address".to_string(),
+                format!(
+                    "Hi {name},\n\n\
+                     We received a request to change the email address of your UniQUE account\n\
+                     to {new_address}. Open the link below to complete the change.\n\n\
+                     {url}\n\n\
+                     This link expires at {}.\n\
+                     If you did not request this, you can safely ignore this email.\n",
+                    expires_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (
+                Template::EmailChangeNotice {
+                    name,
+                    new_address,
+                    cancel_url,
+                },
+                Locale::Ja,
+            ) => (
+                "メールアドレス変更のお知らせ".to_string(),
+                format!(
+                    "{name} さん\n\n\
+                     UniQUE アカウントのメールアドレスを {new_address} に変更するリクエストがありました。\n\
+                     新しいアドレスで確認が行われると変更が完了します。\n\n\
+                     お心当たりがない場合は、以下のリンクから変更を取り消してください。\n\
+                     変更が完了した後も7日間は、このリンクから元のアドレスに戻せます。\n\n\
+                     {cancel_url}\n"
+                ),
+            ),
+            (
+                Template::EmailChangeNotice {
+                    name,
+                    new_address,
+                    cancel_url,
+                },
+                Locale::En,
+            ) => (
+                "Your email address is being changed".to_string(),
+                format!(
+                    "Hi {name},\n\n\
+                     Someone requested to change the email address of your UniQUE account\n\
+                     to {new_address}. The change takes effect once the new address is confirmed.\n\n\
+                     If this wasn't you, cancel the change using the link below.\n\
+                     The link also restores your previous address for 7 days after the change.\n\n\
+                     {cancel_url}\n"
+                ),
+            ),
+            (
+                Template::PasswordReset {
+                    name,
+                    url,
+                    ttl_minutes,
+                },
+                Locale::Ja,
+            ) => (
+                "パスワード再設定のご案内".to_string(),
+                format!(
+                    "{name} さん\n\n\
+                     パスワード再設定のリクエストを受け付けました。\n\
+                     以下のリンクから新しいパスワードを設定してください。\n\n\
+                     {url}\n\n\
+                     このリンクの有効期限は{ttl_minutes}分です。\n\
+                     お心当たりがない場合はこのメールを破棄してください。\n"
+                ),
+            ),
+            (
+                Template::PasswordReset {
+                    name,
+                    url,
+                    ttl_minutes,
+                },
+                Locale::En,
+            ) => (
+                "Reset your password".to_string(),
+                format!(
+                    "Hi {name},\n\n\
+                     We received a request to reset your UniQUE password.\n\
+                     Use the link below to choose a new one.\n\n\
+                     {url}\n\n\
+                     This link expires in {ttl_minutes} minutes.\n\
+                     If you did not request this, you can safely ignore this email.\n"
+                ),
+            ),
+            (
+                Template::NewLogin {
+                    name,
+                    ip_address,
+                    user_agent,
+                    logged_in_at,
+                },
+                Locale::Ja,
+            ) => (
+                "新しいログインがありました".to_string(),
+                format!(
+                    "{name} さん\n\n\
+                     UniQUE アカウントへの新しいログインがありました。\n\n\
+                     日時: {}\n\
+                     IPアドレス: {ip_address}\n\
+                     ブラウザ: {user_agent}\n\n\
+                     お心当たりがない場合は、すぐにパスワードを変更してください。\n",
+                    logged_in_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (
+                Template::NewLogin {
+                    name,
+                    ip_address,
+                    user_agent,
+                    logged_in_at,
+                },
+                Locale::En,
+            ) => (
+                "New sign-in to your account".to_string(),
+                format!(
+                    "Hi {name},\n\n\
+                     There was a new sign-in to your UniQUE account.\n\n\
+                     Time: {}\n\
+                     IP address: {ip_address}\n\
+                     Browser: {user_agent}\n\n\
+                     If this wasn't you, please change your password immediately.\n",
+                    logged_in_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (
+                Template::Suspension {
+                    name,
+                    until,
+                    reason,
+                },
+                Locale::Ja,
+            ) => (
+                "アカウント停止のお知らせ".to_string(),
+                format!(
+                    "{name} さん\n\n\
+                     UniQUE アカウントが停止されました。\n\n\
+                     停止期限: {}\n\
+                     理由: {}\n\n\
+                     ご不明な点は運営までお問い合わせください。\n",
+                    until
+                        .map(|u| u.format(DATETIME_FORMAT).to_string())
+                        .unwrap_or_else(|| "無期限".to_string()),
+                    reason.unwrap_or("（記載なし）")
+                ),
+            ),
+            (
+                Template::Suspension {
+                    name,
+                    until,
+                    reason,
+                },
+                Locale::En,
+            ) => (
+                "Your account has been suspended".to_string(),
+                format!(
+                    "Hi {name},\n\n\
+                     Your UniQUE account has been suspended.\n\n\
+                     Until: {}\n\
+                     Reason: {}\n\n\
+                     Please contact the administrators if you have any questions.\n",
+                    until
+                        .map(|u| u.format(DATETIME_FORMAT).to_string())
+                        .unwrap_or_else(|| "indefinitely".to_string()),
+                    reason.unwrap_or("(not specified)")
+                ),
+            ),
+            (
+                Template::AccountDeletionConfirmation {
+                    name,
+                    url,
+                    expires_at,
+                    cooling_off_days,
+                },
+                Locale::Ja,
+            ) => (
+                "アカウント削除の確認".to_string(),
+                format!(
+                    "{name} さん\n\n\
+                     UniQUE アカウントの削除の申請を受け付けました。\n\
+                     以下のリンクを開くと申請が確定し、{cooling_off_days}日後にアカウントが削除されます。\n\
+                     それまでの間は申請を取り消せます。\n\n\
+                     {url}\n\n\
+                     有効期限: {}\n\
+                     お心当たりがない場合はこのメールを破棄し、パスワードを変更してください。\n",
+                    expires_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (
+                Template::AccountDeletionConfirmation {
+                    name,
+                    url,
+                    expires_at,
+                    cooling_off_days,
+                },
+                Locale::En,
+            ) => (
+                "Confirm your account deletion".to_string(),
+                format!(
+                    "Hi {name},\n\n\
+                     We received a request to delete your UniQUE account.\n\
+                     Open the link below to confirm. Your account will be deleted\n\
+                     {cooling_off_days} days later, and you can cancel until then.\n\n\
+                     {url}\n\n\
+                     This link expires at {}.\n\
+                     If you did not request this, ignore this email and change your password.\n",
+                    expires_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (
+                Template::AccountDeletionScheduled {
+                    name,
+                    scheduled_at,
+                    cancel_url,
+                },
+                Locale::Ja,
+            ) => (
+                "アカウント削除の予定".to_string(),
+                format!(
+                    "{name} さん\n\n\
+                     UniQUE アカウントの削除が確定しました。\n\n\
+                     削除予定: {}\n\n\
+                     削除されるとプロフィールや連携などのデータは元に戻せません。\n\
+                     取り消す場合は、削除予定までに以下のリンクを開いてください。\n\n\
+                     {cancel_url}\n",
+                    scheduled_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (
+                Template::AccountDeletionScheduled {
+                    name,
+                    scheduled_at,
+                    cancel_url,
+                },
+                Locale::En,
+            ) => (
+                "Your account is scheduled for deletion".to_string(),
+                format!(
+                    "Hi {name},\n\n\
+                     Your UniQUE account is scheduled for deletion.\n\n\
+                     Scheduled at: {}\n\n\
+                     Once deleted, your profile and linked data cannot be recovered.\n\
+                     To cancel, open the link below before the scheduled time.\n\n\
+                     {cancel_url}\n",
+                    scheduled_at.format(DATETIME_FORMAT)
+                ),
+            ),
+            (Template::AccountDeleted { name }, Locale::Ja) => (
+                "アカウント削除完了のお知らせ".to_string(),
+                format!(
+                    "{name} さん\n\n\
+                     お申し込みのとおり、UniQUE アカウントを削除しました。\n\
+                     これまでご利用いただきありがとうございました。\n"
+                ),
+            ),
+            (Template::AccountDeleted { name }, Locale::En) => (
+                "Your account has been deleted".to_string(),
+                format!(
+                    "Hi {name},\n\n\
+                     As requested, your UniQUE account has been deleted.\n\
+                     Thank you for using UniQUE.\n"
+                ),
+            ),
+        }
+    }
+
+    /// 宛先を指定して Mail を組み立てる
+    pub fn to_mail(&self, to: &str, locale: Locale) -> Mail {
+        let (subject, body) = self.render(locale);
+        Mail {
+            to: to.to_string(),
+            subject,
+            body,
+        }
+    }
+}
@@ -3,13 +3,23 @@ use dotenvy::dotenv;
 use std::net::SocketAddr;
 use utoipa::OpenApi;
 
+mod audit;
 mod constants;
 mod db;
 mod docs;
+mod error;
+mod extract;
+mod jobs;
+mod lockout;
+mod mailer;
 mod middleware;
+mod migration;
 mod models;
+mod password_policy;
 mod routes;
+mod search;
 mod utils;
+mod webhook;
 
 #[tokio::main]
 async fn main() {
@@ -19,6 +29,18 @@ async fn main() {
         .with_test_writer()
         .init();
     let db = db::connect().await.expect("DB connection failed");
+    migration::run(&db).await.expect("DB migration failed");
+    mailer::init_from_env().expect("Mailer initialization failed");
+    password_policy::init_from_env().expect("Password policy initialization failed");
+    lockout::init_from_env().expect("Lockout initialization failed");
+    middleware::rate_limit::init_from_env().expect("Rate limiter initialization failed");
+    search::init_from_env().expect("User search initialization failed");
+    webhook::init_from_env().expect("Webhook initialization failed");
+    jobs::init_from_env().expect("Jobs initialization failed");
+    tokio::spawn(mailer::queue::run_worker(db.clone()));
+    tokio::spawn(search::run_worker(db.clone()));
+    tokio::spawn(webhook::run_worker(db.clone()));
+    tokio::spawn(jobs::run_scheduler(db.clone()));
 
     let app = Router::new()
         .route("/api-docs/openapi.json", get(openapi_json))
@@ -28,18 +50,30 @@ async fn main() {
         .merge(routes::apps::routes())
         .merge(routes::sessions::routes())
         .merge(routes::email_verify::routes())
+        .merge(routes::audit::routes())
+        // 認証ミドルウェアより内側に置き、ログインユーザー単位で数えられるようにする
+        .layer(axum::middleware::from_fn(
+            middleware::rate_limit::rate_limit_middleware,
+        ))
         .layer(axum::middleware::from_fn_with_state(
             db.clone(),
             middleware::auth::auth_middleware,
         ))
+        // 認証に失敗したリクエストも数えられるよう、IP 単位の制限は認証より外側に置く
+        .layer(axum::middleware::from_fn(
+            middleware::rate_limit::ip_rate_limit_middleware,
+        ))
         .with_state(db);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 8001));
     println!("UniQUE API running at http://{}", addr);
     println!("Swagger UI available at http://{}/swagger-ui", addr);
-    axum::serve(tokio::net::TcpListener::bind(addr).await.unwrap(), app)
-        .await
-        .unwrap();
+    axum::serve(
+        tokio::net::TcpListener::bind(addr).await.unwrap(),
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
@@ -1,19 +1,58 @@
+use std::net::SocketAddr;
+
 use axum::{
-    extract::{Request, State},
-    http::StatusCode,
+    extract::{ConnectInfo, Request, State},
     middleware::Next,
     response::Response,
 };
 use sea_orm::*;
 
-use crate::{db::DbConn, models::session};
+use crate::{db::DbConn, error::ApiError, models::session};
 
 /// 認証されたユーザー情報を保持する構造体
 #[derive(Clone, Debug)]
 pub struct AuthUser {
     pub user_id: String,
-    pub session_id: String,
     pub is_system: Option<bool>,
+    pub client: ClientInfo,
+}
+
+/// リクエスト元の情報（監査ログなどに使う）
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    /// リクエストから接続元 IP と User-Agent を取り出す
+    /// `TRUST_PROXY_HEADERS=true` の場合のみ `X-Forwarded-For` を信用する.
+    pub fn from_request(req: &Request) -> Self {
+        let forwarded = std::env::var("TRUST_PROXY_HEADERS")
+            .is_ok_and(|v| v == "true")
+            .then(|| {
+                req.headers()
+                    .get("x-forwarded-for")
+                    .and_then(|h| h.to_str().ok())
+                    .and_then(|v| v.split(',').next())
+                    .map(|v| v.trim().to_string())
+            })
+            .flatten();
+        let ip_address = forwarded.or_else(|| {
+            req.extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        });
+        let user_agent = req
+            .headers()
+            .get("user-agent")
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
+        Self {
+            ip_address,
+            user_agent,
+        }
+    }
 }
 
 /// セッショントークンからユーザーを認証するミドルウェア
@@ -21,7 +60,10 @@ pub async fn auth_middleware(
     State(db): State<DbConn>,
     mut req: Request,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, ApiError> {
+    let client = ClientInfo::from_request(&req);
+    req.extensions_mut().insert(client.clone());
+
     // swagger-uiのみ許可
     if req.uri().path().starts_with("/swagger-ui")
         || req.uri().path().starts_with("/api-docs")
@@ -30,15 +72,37 @@ pub async fn auth_middleware(
         return Ok(next.run(req).await);
     }
 
+    // パスワードリセットはログインできないユーザーが使うため認証不要
+    if req.uri().path() == "/users/password/reset"
+        || req.uri().path() == "/users/password/reset/confirm"
+    {
+        return Ok(next.run(req).await);
+    }
+
+    // Email検証の確定は検証コード自体が資格情報となるため認証不要
+    if req.uri().path().starts_with("/email_verify/") && req.uri().path().ends_with("/confirm") {
+        return Ok(next.run(req).await);
+    }
+
+    // メールアドレス変更の確定・取り消しも同様
+    if req.uri().path().starts_with("/users/email_change/") {
+        return Ok(next.run(req).await);
+    }
+
+    // アカウント削除の確定・取り消しも同様
+    if req.uri().path().starts_with("/users/deletion/") {
+        return Ok(next.run(req).await);
+    }
+
     // ヘッダーからAPI_KEYを取得
     let api_key = req.headers().get("x-api-key").and_then(|h| h.to_str().ok());
 
-    if (api_key.is_some() && api_key.unwrap() == std::env::var("API_KEY").unwrap_or_default()) {
+    if api_key.is_some_and(|k| k == std::env::var("API_KEY").unwrap_or_default()) {
         // API_KEYが一致する場合、システムユーザーとして認証情報を設定
         req.extensions_mut().insert(AuthUser {
             user_id: "system".to_string(),
-            session_id: "system".to_string(),
             is_system: Some(true),
+            client,
         });
         return Ok(next.run(req).await);
     }
@@ -48,7 +112,7 @@ pub async fn auth_middleware(
         .headers()
         .get("Cookie")
         .and_then(|h| h.to_str().ok())
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .ok_or(ApiError::Unauthorized)?;
 
     // unique-sid Cookie を抽出
     let token = cookie_header
@@ -57,27 +121,26 @@ pub async fn auth_middleware(
             let cookie = cookie.trim();
             cookie.strip_prefix("unique-sid=")
         })
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .ok_or(ApiError::Unauthorized)?;
 
     // セッション検証
     let found_session = session::Entity::find()
         .filter(session::Column::Id.eq(token))
         .one(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
-    let session_model = found_session.ok_or(StatusCode::UNAUTHORIZED)?;
+    let session_model = found_session.ok_or(ApiError::Unauthorized)?;
 
     // セッションが有効か確認
     if !session_model.is_enable {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err(ApiError::Unauthorized);
     }
 
     // リクエストに認証情報を追加
     req.extensions_mut().insert(AuthUser {
         user_id: session_model.user_id.clone(),
-        session_id: session_model.id.clone(),
         is_system: Some(false),
+        client,
     });
 
     Ok(next.run(req).await)
@@ -1,2 +1,3 @@
 pub mod auth;
 pub mod permission_check;
+pub mod rate_limit;
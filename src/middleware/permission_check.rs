@@ -1,10 +1,10 @@
-use axum::http::StatusCode;
 use sea_orm::*;
 use tracing::info;
 
 use crate::{
     constants::permissions::Permission,
     db::DbConn,
+    error::ApiError,
     middleware::auth::AuthUser,
     models::{role, user::Entity as User},
 };
@@ -13,23 +13,18 @@ use crate::{
 pub async fn get_user_permissions(
     auth_user: &AuthUser,
     db: &DbConn,
-) -> Result<Permission, StatusCode> {
-    let user = User::find_by_id(&auth_user.user_id)
+) -> Result<Permission, ApiError> {
+    let user = User::find_active_by_id(&auth_user.user_id)
         .one(db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
 
-    let roles = user
-        .find_related(role::Entity)
-        .all(db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let roles = user.find_related(role::Entity).all(db).await?;
 
     let mut user_permissions = Permission::empty();
     for role_model in roles {
         let bits = role_model.permission as u32;
-        let (names, mask) = Permission::names_from_bits(bits);
+        let (_, mask) = Permission::names_from_bits(bits);
         if mask != bits {
             info!(
                 "Role {} has unknown permission bits: {:#x}",
@@ -49,13 +44,13 @@ pub async fn require_permission(
     auth_user: &AuthUser,
     required: Permission,
     db: &DbConn,
-) -> Result<(), StatusCode> {
+) -> Result<(), ApiError> {
     if auth_user.is_system.unwrap_or(false) {
         return Ok(());
     }
     let user_permissions = get_user_permissions(auth_user, db).await?;
     if !user_permissions.contains(required) {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::Forbidden);
     }
     Ok(())
 }
@@ -66,7 +61,7 @@ pub async fn require_permission_or_self(
     required: Permission,
     target_user_id: &str,
     db: &DbConn,
-) -> Result<(), StatusCode> {
+) -> Result<(), ApiError> {
     // 自分自身のリソースならOK
     if auth_user.user_id == target_user_id {
         return Ok(());
@@ -0,0 +1,310 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{HeaderMap, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::info;
+
+use crate::{
+    error::ApiError,
+    middleware::auth::{AuthUser, ClientInfo},
+    utils::token,
+};
+
+/// 保持するバケット数がこれを超えたら、満タンに戻ったものを捨てる
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// トークンバケットの設定
+#[derive(Clone, Copy, Debug)]
+pub struct Rule {
+    /// バケットの容量（連続して受け付けられるリクエスト数）
+    pub capacity: u32,
+    /// 空のバケットが満タンに戻るまでの秒数
+    pub window_secs: u32,
+}
+
+impl Rule {
+    const fn new(capacity: u32, window_secs: u32) -> Self {
+        Self {
+            capacity,
+            window_secs,
+        }
+    }
+
+    /// 1秒あたりに補充されるトークン数
+    fn refill_per_sec(&self) -> f64 {
+        self.capacity as f64 / self.window_secs as f64
+    }
+}
+
+/// ルートごとの上書き設定（パスはルーティング定義のパターン）
+/// 検索や認証を経ないエンドポイントは既定より厳しくする.
+const ROUTE_RULES: &[(Method, &str, Rule)] = &[
+    (Method::GET, "/users/search", Rule::new(30, 60)),
+    (Method::GET, "/roles/search", Rule::new(30, 60)),
+    (Method::POST, "/users/password/reset", Rule::new(5, 15 * 60)),
+    (
+        Method::POST,
+        "/users/password/reset/confirm",
+        Rule::new(10, 15 * 60),
+    ),
+    (
+        Method::PUT,
+        "/users/{id}/password/change",
+        Rule::new(10, 15 * 60),
+    ),
+    (Method::POST, "/users/{id}/deletion", Rule::new(10, 15 * 60)),
+    (Method::GET, "/audit/export", Rule::new(5, 60)),
+    (Method::GET, "/users/export", Rule::new(5, 60)),
+    (Method::GET, "/users/{id}/export", Rule::new(5, 60)),
+    (Method::POST, "/users/import", Rule::new(10, 60)),
+    (Method::POST, "/users/bulk", Rule::new(10, 60)),
+];
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    /// 経過時間分のトークンを補充する
+    fn refill(&mut self, rule: &Rule, now: Instant) {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rule.refill_per_sec()).min(rule.capacity as f64);
+        self.updated_at = now;
+    }
+}
+
+/// 1回の判定結果（レスポンスヘッダーに使う）
+struct Decision {
+    allowed: bool,
+    rule: Rule,
+    remaining: u32,
+    /// バケットが満タンに戻るまでの秒数
+    reset_secs: u64,
+    /// 次のリクエストを受け付けられるまでの秒数
+    retry_after_secs: u64,
+}
+
+/// プロセス内のトークンバケットによるレート制限
+/// NOTE: API サーバーを複数台で動かす場合はサーバーごとに数えられる.
+pub struct RateLimiter {
+    default_rule: Rule,
+    /// 接続元 IP 単位の既定の設定
+    /// 同じ IP から複数のユーザーが使う場合があるため、ユーザー単位より緩くする.
+    ip_rule: Rule,
+    enabled: bool,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+static RATE_LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            default_rule: Rule::new(120, 60),
+            ip_rule: Rule::new(600, 60),
+            enabled: true,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// 環境変数からレート制限を初期化する
+/// - `RATE_LIMIT_ENABLED`: `false` で無効化（既定: `true`）
+/// - `RATE_LIMIT_CAPACITY`: 既定のバケット容量（既定: 120）
+/// - `RATE_LIMIT_WINDOW_SECS`: 既定のバケットが満タンに戻るまでの秒数（既定: 60）
+/// - `RATE_LIMIT_IP_CAPACITY`: 接続元 IP 単位の既定のバケット容量（既定: 600）
+/// - `RATE_LIMIT_IP_WINDOW_SECS`: 接続元 IP 単位の既定のバケットが満タンに戻るまでの秒数（既定: 60）
+pub fn init_from_env() -> anyhow::Result<()> {
+    let default = RateLimiter::default();
+    let parse = |key: &str, default: u32| -> anyhow::Result<u32> {
+        match std::env::var(key) {
+            Ok(v) => match v.parse() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => anyhow::bail!("{} must be a positive integer", key),
+            },
+            Err(_) => Ok(default),
+        }
+    };
+    let limiter = RateLimiter {
+        default_rule: Rule::new(
+            parse("RATE_LIMIT_CAPACITY", default.default_rule.capacity)?,
+            parse("RATE_LIMIT_WINDOW_SECS", default.default_rule.window_secs)?,
+        ),
+        ip_rule: Rule::new(
+            parse("RATE_LIMIT_IP_CAPACITY", default.ip_rule.capacity)?,
+            parse("RATE_LIMIT_IP_WINDOW_SECS", default.ip_rule.window_secs)?,
+        ),
+        enabled: std::env::var("RATE_LIMIT_ENABLED").map_or(true, |v| v != "false"),
+        ..default
+    };
+    info!(
+        "Rate limit: enabled={} capacity={} window={}s ip_capacity={} ip_window={}s",
+        limiter.enabled,
+        limiter.default_rule.capacity,
+        limiter.default_rule.window_secs,
+        limiter.ip_rule.capacity,
+        limiter.ip_rule.window_secs
+    );
+    if RATE_LIMITER.set(limiter).is_err() {
+        info!("Rate limiter is already initialized");
+    }
+    Ok(())
+}
+
+/// 登録済みのレート制限を取得する（未登録の場合は既定値）
+fn limiter() -> &'static RateLimiter {
+    RATE_LIMITER.get_or_init(RateLimiter::default)
+}
+
+impl RateLimiter {
+    fn rule_for(&self, method: &Method, path: &str, default: Rule) -> (Rule, &'static str) {
+        ROUTE_RULES
+            .iter()
+            .find(|(m, p, _)| m == method && *p == path)
+            .map(|(_, p, rule)| (*rule, *p))
+            .unwrap_or((default, "*"))
+    }
+
+    fn acquire(&self, key: String, rule: Rule) -> Decision {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_THRESHOLD {
+            let window = self
+                .default_rule
+                .window_secs
+                .max(self.ip_rule.window_secs)
+                .max(
+                    ROUTE_RULES
+                        .iter()
+                        .map(|(_, _, r)| r.window_secs)
+                        .max()
+                        .unwrap_or(0),
+                );
+            buckets.retain(|_, b| now.duration_since(b.updated_at).as_secs() < window as u64);
+        }
+
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: rule.capacity as f64,
+            updated_at: now,
+        });
+        bucket.refill(&rule, now);
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let rate = rule.refill_per_sec();
+        Decision {
+            allowed,
+            rule,
+            remaining: bucket.tokens.floor() as u32,
+            reset_secs: ((rule.capacity as f64 - bucket.tokens) / rate).ceil() as u64,
+            retry_after_secs: ((1.0 - bucket.tokens).max(0.0) / rate).ceil() as u64,
+        }
+    }
+}
+
+/// 認証済みのリクエストを数える単位（API キーまたはログインユーザー）
+fn identity(req: &Request) -> Option<String> {
+    let auth_user = req.extensions().get::<AuthUser>()?;
+    if auth_user.is_system == Some(true) {
+        let api_key = req
+            .headers()
+            .get("x-api-key")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default();
+        // キーそのものをメモリに残さない
+        return Some(format!("api_key:{}", &token::hash_token(api_key)[..16]));
+    }
+    Some(format!("user:{}", auth_user.user_id))
+}
+
+fn insert_headers(headers: &mut HeaderMap, decision: &Decision) {
+    // 内側の層で付けたヘッダーの方が残りが少なければそちらを残す
+    let inner_remaining = headers
+        .get("ratelimit-remaining")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u32>().ok());
+    if inner_remaining.is_some_and(|r| r <= decision.remaining) {
+        return;
+    }
+    let values = [
+        ("ratelimit-limit", decision.rule.capacity.to_string()),
+        ("ratelimit-remaining", decision.remaining.to_string()),
+        ("ratelimit-reset", decision.reset_secs.to_string()),
+        (
+            "ratelimit-policy",
+            format!("{};w={}", decision.rule.capacity, decision.rule.window_secs),
+        ),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+/// 接続元 IP 単位でリクエスト数を制限するミドルウェア
+/// 認証に失敗したリクエストも数えるよう、認証ミドルウェアより外側に置くこと.
+pub async fn ip_rate_limit_middleware(req: Request, next: Next) -> Response {
+    let limiter = limiter();
+    if !limiter.enabled {
+        return next.run(req).await;
+    }
+
+    let ip = ClientInfo::from_request(&req)
+        .ip_address
+        .unwrap_or_else(|| "unknown".to_string());
+    let key = format!("ip:{}", ip);
+    limit(limiter, req, next, key, limiter.ip_rule).await
+}
+
+/// API キー・ログインユーザー単位でリクエスト数を制限するミドルウェア
+/// 認証ミドルウェアより内側に置き、`AuthUser` を参照できるようにすること.
+/// 認証を経ないリクエストは IP 単位の制限のみを受ける.
+pub async fn rate_limit_middleware(req: Request, next: Next) -> Response {
+    let limiter = limiter();
+    if !limiter.enabled {
+        return next.run(req).await;
+    }
+
+    let Some(key) = identity(&req) else {
+        return next.run(req).await;
+    };
+    limit(limiter, req, next, key, limiter.default_rule).await
+}
+
+/// ルートごとのバケットからトークンを取り、超過していれば 429 を返す
+async fn limit(
+    limiter: &RateLimiter,
+    req: Request,
+    next: Next,
+    identity: String,
+    default: Rule,
+) -> Response {
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let (rule, scope) = limiter.rule_for(req.method(), &path, default);
+    let key = format!("{}|{}", scope, identity);
+    let decision = limiter.acquire(key, rule);
+
+    let mut response = if decision.allowed {
+        next.run(req).await
+    } else {
+        ApiError::RateLimited(decision.retry_after_secs.max(1)).into_response()
+    };
+    insert_headers(response.headers_mut(), &decision);
+    response
+}
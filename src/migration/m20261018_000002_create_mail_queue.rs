@@ -0,0 +1,52 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MailQueue::Table)
+                    .if_not_exists()
+                    .col(pk_auto(MailQueue::Id))
+                    .col(string(MailQueue::ToAddress))
+                    .col(string(MailQueue::Subject))
+                    .col(text(MailQueue::Body))
+                    .col(integer(MailQueue::Attempts).default(0))
+                    .col(text_null(MailQueue::LastError))
+                    .col(date_time(MailQueue::NextAttemptAt))
+                    .col(date_time_null(MailQueue::SentAt))
+                    .col(date_time_null(MailQueue::CreatedAt))
+                    .index(
+                        Index::create()
+                            .name("idx_mail_queue_next_attempt_at")
+                            .col(MailQueue::NextAttemptAt),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MailQueue::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MailQueue {
+    Table,
+    Id,
+    ToAddress,
+    Subject,
+    Body,
+    Attempts,
+    LastError,
+    NextAttemptAt,
+    SentAt,
+    CreatedAt,
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+const CODE_INDEX: &str = "idx_email_verifications_verification_code";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.has_index("email_verifications", CODE_INDEX).await? {
+            return Ok(());
+        }
+        manager
+            .create_index(
+                Index::create()
+                    .name(CODE_INDEX)
+                    .table(EmailVerifications::Table)
+                    .col(EmailVerifications::VerificationCode)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(CODE_INDEX)
+                    .table(EmailVerifications::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum EmailVerifications {
+    Table,
+    VerificationCode,
+}
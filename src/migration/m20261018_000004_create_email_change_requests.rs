@@ -0,0 +1,57 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EmailChangeRequests::Table)
+                    .if_not_exists()
+                    .col(pk_auto(EmailChangeRequests::Id))
+                    .col(string(EmailChangeRequests::UserId))
+                    .col(string(EmailChangeRequests::Field))
+                    .col(string(EmailChangeRequests::NewAddress))
+                    .col(string_uniq(EmailChangeRequests::VerificationCodeHash))
+                    .col(string_uniq(EmailChangeRequests::CancelCodeHash))
+                    .col(date_time_null(EmailChangeRequests::CreatedAt))
+                    .col(date_time(EmailChangeRequests::ExpiresAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_email_change_requests_user_id")
+                            .from(EmailChangeRequests::Table, EmailChangeRequests::UserId)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EmailChangeRequests::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum EmailChangeRequests {
+    Table,
+    Id,
+    UserId,
+    Field,
+    NewAddress,
+    VerificationCodeHash,
+    CancelCodeHash,
+    CreatedAt,
+    ExpiresAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
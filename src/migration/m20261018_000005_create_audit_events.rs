@@ -0,0 +1,70 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditEvents::Table)
+                    .if_not_exists()
+                    .col(pk_auto(AuditEvents::Id))
+                    .col(string(AuditEvents::ActorId))
+                    .col(string(AuditEvents::Action))
+                    .col(string(AuditEvents::TargetType))
+                    .col(string(AuditEvents::TargetId))
+                    .col(json_null(AuditEvents::Before))
+                    .col(json_null(AuditEvents::After))
+                    .col(string_null(AuditEvents::IpAddress))
+                    .col(text_null(AuditEvents::UserAgent))
+                    .col(date_time(AuditEvents::CreatedAt))
+                    .index(
+                        Index::create()
+                            .name("idx_audit_events_actor_id")
+                            .col(AuditEvents::ActorId),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_audit_events_target")
+                            .col(AuditEvents::TargetType)
+                            .col(AuditEvents::TargetId),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_audit_events_action")
+                            .col(AuditEvents::Action),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_audit_events_created_at")
+                            .col(AuditEvents::CreatedAt),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditEvents::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuditEvents {
+    Table,
+    Id,
+    ActorId,
+    Action,
+    TargetType,
+    TargetId,
+    Before,
+    After,
+    IpAddress,
+    UserAgent,
+    CreatedAt,
+}
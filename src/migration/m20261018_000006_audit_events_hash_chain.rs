@@ -0,0 +1,67 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+const HASH_INDEX: &str = "idx_audit_events_hash";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AuditEvents::Table)
+                    .add_column(string(AuditEvents::PrevHash).default(""))
+                    .add_column(string(AuditEvents::Hash).default(""))
+                    .to_owned(),
+            )
+            .await?;
+        // チェーン導入前のレコードはハッシュを持たないため、一意制約を満たす値で埋める
+        // （検証ではチェーンの外のレコードとして報告される）
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE `audit_events` SET `hash` = SHA2(CONCAT('unchained:', `id`), 256) \
+                 WHERE `hash` = ''",
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name(HASH_INDEX)
+                    .table(AuditEvents::Table)
+                    .col(AuditEvents::Hash)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(HASH_INDEX)
+                    .table(AuditEvents::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AuditEvents::Table)
+                    .drop_column(AuditEvents::Hash)
+                    .drop_column(AuditEvents::PrevHash)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuditEvents {
+    Table,
+    PrevHash,
+    Hash,
+}
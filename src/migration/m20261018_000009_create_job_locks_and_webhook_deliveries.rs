@@ -0,0 +1,74 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(JobLocks::Table)
+                    .if_not_exists()
+                    .col(string(JobLocks::Name).primary_key())
+                    .col(string(JobLocks::Owner))
+                    .col(date_time(JobLocks::LockedUntil))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebhookDeliveries::Table)
+                    .if_not_exists()
+                    .col(pk_auto(WebhookDeliveries::Id))
+                    .col(string_uniq(WebhookDeliveries::EventId))
+                    .col(string(WebhookDeliveries::Event))
+                    .col(json(WebhookDeliveries::Payload))
+                    .col(integer(WebhookDeliveries::Attempts).default(0))
+                    .col(text_null(WebhookDeliveries::LastError))
+                    .col(date_time(WebhookDeliveries::NextAttemptAt))
+                    .col(date_time_null(WebhookDeliveries::DeliveredAt))
+                    .col(date_time(WebhookDeliveries::CreatedAt))
+                    .index(
+                        Index::create()
+                            .name("idx_webhook_deliveries_next_attempt_at")
+                            .col(WebhookDeliveries::NextAttemptAt),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WebhookDeliveries::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(JobLocks::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum JobLocks {
+    Table,
+    Name,
+    Owner,
+    LockedUntil,
+}
+
+#[derive(DeriveIden)]
+enum WebhookDeliveries {
+    Table,
+    Id,
+    EventId,
+    Event,
+    Payload,
+    Attempts,
+    LastError,
+    NextAttemptAt,
+    DeliveredAt,
+    CreatedAt,
+}
@@ -0,0 +1,60 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+const DELETED_AT_INDEX: &str = "idx_users_deleted_at";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 導入前に手動で追加していた環境でも失敗しないよう、無いものだけを追加する
+        if !manager.has_column("users", "deleted_at").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Users::Table)
+                        .add_column(date_time_null(Users::DeletedAt))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        if !manager.has_index("users", DELETED_AT_INDEX).await? {
+            manager
+                .create_index(
+                    Index::create()
+                        .name(DELETED_AT_INDEX)
+                        .table(Users::Table)
+                        .col(Users::DeletedAt)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(DELETED_AT_INDEX)
+                    .table(Users::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    DeletedAt,
+}
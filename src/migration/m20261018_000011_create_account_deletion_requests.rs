@@ -0,0 +1,69 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountDeletionRequests::Table)
+                    .if_not_exists()
+                    .col(pk_auto(AccountDeletionRequests::Id))
+                    .col(string_uniq(AccountDeletionRequests::UserId))
+                    .col(string_uniq(AccountDeletionRequests::ConfirmationCodeHash))
+                    .col(string_null(AccountDeletionRequests::CancelCodeHash).unique_key())
+                    .col(date_time(AccountDeletionRequests::CreatedAt))
+                    .col(date_time(AccountDeletionRequests::ExpiresAt))
+                    .col(date_time_null(AccountDeletionRequests::ConfirmedAt))
+                    .col(date_time_null(AccountDeletionRequests::ScheduledAt))
+                    .index(
+                        Index::create()
+                            .name("idx_account_deletion_requests_scheduled_at")
+                            .col(AccountDeletionRequests::ScheduledAt),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_account_deletion_requests_user_id")
+                            .from(
+                                AccountDeletionRequests::Table,
+                                AccountDeletionRequests::UserId,
+                            )
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(AccountDeletionRequests::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AccountDeletionRequests {
+    Table,
+    Id,
+    UserId,
+    ConfirmationCodeHash,
+    CancelCodeHash,
+    CreatedAt,
+    ExpiresAt,
+    ConfirmedAt,
+    ScheduledAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 送信済みの行は本文を残さず削除するようになったため、残っている行も消す
+        manager
+            .get_connection()
+            .execute_unprepared("DELETE FROM `mail_queue` WHERE `sent_at` IS NOT NULL")
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MailQueue::Table)
+                    .modify_column(text_null(MailQueue::Body))
+                    .drop_column(MailQueue::SentAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DELETE FROM `mail_queue` WHERE `body` IS NULL")
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MailQueue::Table)
+                    .modify_column(text(MailQueue::Body))
+                    .add_column(date_time_null(MailQueue::SentAt))
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MailQueue {
+    Table,
+    Body,
+    SentAt,
+}
@@ -0,0 +1,43 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 変更前のアドレスを持たない未確定の申請は取り消せないため、作り直してもらう
+        manager
+            .get_connection()
+            .execute_unprepared("DELETE FROM `email_change_requests`")
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(EmailChangeRequests::Table)
+                    .add_column(string(EmailChangeRequests::OldAddress))
+                    .add_column(date_time_null(EmailChangeRequests::ConfirmedAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(EmailChangeRequests::Table)
+                    .drop_column(EmailChangeRequests::ConfirmedAt)
+                    .drop_column(EmailChangeRequests::OldAddress)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum EmailChangeRequests {
+    Table,
+    OldAddress,
+    ConfirmedAt,
+}
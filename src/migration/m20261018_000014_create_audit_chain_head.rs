@@ -0,0 +1,34 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 行は最初の追記時に既存のレコードの末尾から作られる
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditChainHead::Table)
+                    .if_not_exists()
+                    .col(integer(AuditChainHead::Id).primary_key())
+                    .col(string(AuditChainHead::LastHash))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditChainHead::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuditChainHead {
+    Table,
+    Id,
+    LastHash,
+}
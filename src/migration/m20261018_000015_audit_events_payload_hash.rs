@@ -0,0 +1,42 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // ハッシュの計算方法が変わるため、既存のレコードは検証でハッシュの不一致として報告される
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AuditEvents::Table)
+                    .add_column(string_null(AuditEvents::PayloadSalt))
+                    .add_column(string(AuditEvents::PayloadHash).default(""))
+                    .add_column(date_time_null(AuditEvents::RedactedAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AuditEvents::Table)
+                    .drop_column(AuditEvents::RedactedAt)
+                    .drop_column(AuditEvents::PayloadHash)
+                    .drop_column(AuditEvents::PayloadSalt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuditEvents {
+    Table,
+    PayloadSalt,
+    PayloadHash,
+    RedactedAt,
+}
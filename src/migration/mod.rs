@@ -0,0 +1,87 @@
+use sea_orm::*;
+use sea_orm_migration::prelude::*;
+
+use crate::db::DbConn;
+
+mod m20261018_000001_create_password_resets;
+mod m20261018_000002_create_mail_queue;
+mod m20261018_000003_email_verifications_code_unique;
+mod m20261018_000004_create_email_change_requests;
+mod m20261018_000005_create_audit_events;
+mod m20261018_000006_audit_events_hash_chain;
+mod m20261018_000007_create_password_history;
+mod m20261018_000008_users_name_kana;
+mod m20261018_000009_create_job_locks_and_webhook_deliveries;
+mod m20261018_000010_users_deleted_at;
+mod m20261018_000011_create_account_deletion_requests;
+mod m20261018_000012_mail_queue_clear_body;
+mod m20261018_000013_email_change_requests_revert;
+mod m20261018_000014_create_audit_chain_head;
+mod m20261018_000015_audit_events_payload_hash;
+mod m20261018_000016_audit_events_client_hash;
+
+/// マイグレーションを直列化する MySQL の名前付きロック
+const LOCK_NAME: &str = "unique_api_migration";
+/// 他の台の適用が終わるのを待つ上限（秒）
+const LOCK_TIMEOUT_SECS: i32 = 300;
+
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20261018_000001_create_password_resets::Migration),
+            Box::new(m20261018_000002_create_mail_queue::Migration),
+            Box::new(m20261018_000003_email_verifications_code_unique::Migration),
+            Box::new(m20261018_000004_create_email_change_requests::Migration),
+            Box::new(m20261018_000005_create_audit_events::Migration),
+            Box::new(m20261018_000006_audit_events_hash_chain::Migration),
+            Box::new(m20261018_000007_create_password_history::Migration),
+            Box::new(m20261018_000008_users_name_kana::Migration),
+            Box::new(m20261018_000009_create_job_locks_and_webhook_deliveries::Migration),
+            Box::new(m20261018_000010_users_deleted_at::Migration),
+            Box::new(m20261018_000011_create_account_deletion_requests::Migration),
+            Box::new(m20261018_000012_mail_queue_clear_body::Migration),
+            Box::new(m20261018_000013_email_change_requests_revert::Migration),
+            Box::new(m20261018_000014_create_audit_chain_head::Migration),
+            Box::new(m20261018_000015_audit_events_payload_hash::Migration),
+            Box::new(m20261018_000016_audit_events_client_hash::Migration),
+        ]
+    }
+}
+
+/// 未適用のマイグレーションを適用する
+///
+/// 複数台が同時に起動しても1台ずつ適用するよう、名前付きロックを取ってから実行する.
+/// 名前付きロックは接続に紐づくため、トランザクションで接続を1本に固定して取得から解放までを行う
+/// （DDL は暗黙にコミットされるので、マイグレーション自体はトランザクションにならない）.
+pub async fn run(db: &DbConn) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+    let locked = txn
+        .query_one(Statement::from_sql_and_values(
+            DbBackend::MySql,
+            "SELECT GET_LOCK(?, ?) AS locked",
+            [LOCK_NAME.into(), LOCK_TIMEOUT_SECS.into()],
+        ))
+        .await?
+        .map(|row| row.try_get::<Option<i64>>("", "locked"))
+        .transpose()?
+        .flatten();
+    if locked != Some(1) {
+        return Err(DbErr::Custom(format!(
+            "Timed out waiting for migration lock {}",
+            LOCK_NAME
+        )));
+    }
+
+    let result = Migrator::up(&txn, None).await;
+    txn.execute(Statement::from_sql_and_values(
+        DbBackend::MySql,
+        "SELECT RELEASE_LOCK(?)",
+        [LOCK_NAME.into()],
+    ))
+    .await?;
+    txn.commit().await?;
+    result
+}
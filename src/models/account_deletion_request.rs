@@ -0,0 +1,45 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 本人によるアカウント削除の申請（1ユーザーにつき1件）
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Deserialize, Serialize)]
+#[sea_orm(table_name = "account_deletion_requests")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    #[serde(skip_serializing, skip_deserializing)]
+    pub id: i32,
+    #[sea_orm(unique)]
+    pub user_id: String,
+    /// 外部メールアドレスへ送る確認コードの SHA-256
+    #[sea_orm(unique)]
+    #[serde(skip_serializing)]
+    pub confirmation_code_hash: String,
+    /// 確認後に送る取り消しコードの SHA-256
+    #[sea_orm(unique, nullable)]
+    #[serde(skip_serializing)]
+    pub cancel_code_hash: Option<String>,
+    pub created_at: DateTime,
+    /// 確認コードの有効期限
+    pub expires_at: DateTime,
+    pub confirmed_at: Option<DateTime>,
+    /// 削除を実行する日時（確認から猶予期間後）
+    pub scheduled_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
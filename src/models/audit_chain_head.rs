@@ -0,0 +1,17 @@
+use sea_orm::entity::prelude::*;
+
+/// 監査ログのチェーンの末尾（追記を複数台の間で直列化するためのロック行を兼ねる）
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "audit_chain_head")]
+pub struct Model {
+    /// 常に `audit::chain::HEAD_ID` の1行だけ
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i32,
+    /// 末尾のレコードの `hash`
+    pub last_hash: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,46 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Deserialize, Serialize)]
+#[sea_orm(table_name = "audit_events")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    /// 操作したユーザーのID（API キーの場合は `system`）
+    pub actor_id: String,
+    /// 操作の種類（`user.update` など）
+    pub action: String,
+    /// 操作対象の種類（`user` / `role` / `app` / `session`）
+    pub target_type: String,
+    pub target_id: String,
+    /// 変更前の値（変更のあったフィールドのみ）
+    #[sea_orm(column_type = "Json", nullable)]
+    pub before: Option<Json>,
+    /// 変更後の値（変更のあったフィールドのみ）
+    #[sea_orm(column_type = "Json", nullable)]
+    pub after: Option<Json>,
+    pub ip_address: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub user_agent: Option<String>,
+    pub created_at: DateTime,
+    /// `payload_hash` に混ぜる乱数（消去すると、残ったハッシュから元の値を推測できなくなる）
+    pub payload_salt: Option<String>,
+    /// `before` / `after` の SHA-256（`audit::chain::payload_hash_of` 参照）
+    pub payload_hash: String,
+    /// `client_hash` に混ぜる乱数
+    pub client_salt: Option<String>,
+    /// `ip_address` / `user_agent` の SHA-256（`audit::chain::client_hash_of` 参照）
+    pub client_hash: String,
+    /// ユーザーの削除に伴って個人情報を消去した日時（消去した列は `user.redact` の記録に残る）
+    pub redacted_at: Option<DateTime>,
+    /// 直前のレコードの `hash`（最初のレコードは 0 埋め）
+    pub prev_hash: String,
+    /// このレコードの SHA-256（`audit::chain::hash_of` 参照）
+    #[sea_orm(unique)]
+    pub hash: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
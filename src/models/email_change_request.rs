@@ -0,0 +1,47 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Deserialize, Serialize)]
+#[sea_orm(table_name = "email_change_requests")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    #[serde(skip_serializing, skip_deserializing)]
+    pub id: i32,
+    pub user_id: String,
+    /// 変更対象のカラム（`email` / `external_email`）
+    pub field: String,
+    /// 変更前のアドレス（確定後の取り消しで元に戻すため）
+    pub old_address: String,
+    pub new_address: String,
+    /// 新しいアドレスへ送る確認コードの SHA-256
+    #[sea_orm(unique)]
+    #[serde(skip_serializing)]
+    pub verification_code_hash: String,
+    /// 旧アドレスへ送る取り消しコードの SHA-256
+    #[sea_orm(unique)]
+    #[serde(skip_serializing)]
+    pub cancel_code_hash: String,
+    pub created_at: Option<DateTime>,
+    /// 未確定の間は確認コードの有効期限、確定後は取り消しを受け付ける期限
+    pub expires_at: DateTime,
+    /// 新しいアドレスで確認された日時
+    pub confirmed_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
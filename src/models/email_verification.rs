@@ -10,6 +10,8 @@ pub struct Model {
     #[serde(skip_serializing, skip_deserializing)]
     pub id: i32,
     pub user_id: String,
+    #[sea_orm(unique)]
+    #[serde(skip_serializing)]
     pub verification_code: String,
     pub created_at: Option<DateTime>,
     pub expires_at: DateTime,
@@ -0,0 +1,17 @@
+use sea_orm::entity::prelude::*;
+
+/// 定期ジョブの実行権（複数台のうち1台だけが実行するためのリース）
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "job_locks")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub name: String,
+    /// 実行権を持つプロセスの ID
+    pub owner: String,
+    pub locked_until: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,16 +1,22 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.17
 
-pub mod prelude;
-
 pub mod access_tokens;
+pub mod account_deletion_request;
 pub mod app;
+pub mod audit_chain_head;
+pub mod audit_event;
 pub mod auths;
 pub mod code;
 pub mod consents;
 pub mod discord;
+pub mod email_change_request;
 pub mod email_verification;
 pub mod id_tokens;
+pub mod job_lock;
+pub mod mail_queue;
 pub mod oidc_authorizations;
+pub mod password_history;
+pub mod password_reset;
 pub mod redirect_uris;
 pub mod refresh_tokens;
 pub mod role;
@@ -19,3 +25,4 @@ pub mod token_sets;
 pub mod user;
 pub mod user_app;
 pub mod user_role;
+pub mod webhook_delivery;
@@ -11,6 +11,7 @@ pub struct Model {
     #[sea_orm(unique)]
     pub custom_id: String,
     pub name: String,
+    pub name_kana: Option<String>,
     #[serde(skip_serializing)]
     pub password_hash: Option<String>,
     #[sea_orm(unique)]
@@ -28,6 +29,8 @@ pub struct Model {
     pub suspended_until: Option<DateTime>,
     #[sea_orm(column_type = "Text", nullable)]
     pub suspended_reason: Option<String>,
+    /// 論理削除した日時（保持期間を過ぎると定期ジョブが完全に削除する）
+    pub deleted_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -44,6 +47,14 @@ pub enum Relation {
     Roles,
     #[sea_orm(has_many = "super::email_verification::Entity")]
     EmailVerifications,
+    #[sea_orm(has_many = "super::password_reset::Entity")]
+    PasswordResets,
+    #[sea_orm(has_many = "super::password_history::Entity")]
+    PasswordHistory,
+    #[sea_orm(has_many = "super::email_change_request::Entity")]
+    EmailChangeRequests,
+    #[sea_orm(has_one = "super::account_deletion_request::Entity")]
+    AccountDeletionRequest,
 }
 
 /* ---------- one-to-many ---------- */
@@ -72,6 +83,30 @@ impl Related<super::email_verification::Entity> for Entity {
     }
 }
 
+impl Related<super::password_reset::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordResets.def()
+    }
+}
+
+impl Related<super::password_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordHistory.def()
+    }
+}
+
+impl Related<super::email_change_request::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EmailChangeRequests.def()
+    }
+}
+
+impl Related<super::account_deletion_request::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AccountDeletionRequest.def()
+    }
+}
+
 /* ---------- many-to-many ---------- */
 
 // User -> user_role (中間テーブル)
@@ -110,4 +145,16 @@ impl Related<super::app::Entity> for Entity {
     }
 }
 
+impl Entity {
+    /// 論理削除されていないユーザーを検索する
+    pub fn find_active() -> Select<Entity> {
+        Self::find().filter(Column::DeletedAt.is_null())
+    }
+
+    /// 論理削除されていないユーザーを ID で検索する
+    pub fn find_active_by_id(id: impl Into<String>) -> Select<Entity> {
+        Self::find_by_id(id.into()).filter(Column::DeletedAt.is_null())
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,88 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use sha2::{Digest, Sha256};
+
+/// 漏洩済みパスワードの一覧を保持するブルームフィルタ
+///
+/// 一覧をそのままメモリに載せずに済むよう、ビット列としてのみ保持する.
+/// 偽陽性（漏洩していないパスワードを漏洩済みと判定すること）はあり得るが、偽陰性はない.
+pub struct BreachedList {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BreachedList {
+    /// 目標とする偽陽性率
+    const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+    /// 1行に1パスワードのテキストファイルを読み込む
+    /// 空行と `#` で始まる行は無視する.
+    ///
+    /// 一覧の全件をメモリに載せないよう、件数を数える走査と挿入する走査の2回に分けて読む.
+    pub fn from_path(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let mut count = 0;
+        for_each_entry(BufReader::new(File::open(path)?), |_| count += 1)?;
+
+        let mut list = Self::with_capacity(count);
+        for_each_entry(BufReader::new(File::open(path)?), |password| {
+            list.insert(password)
+        })?;
+        Ok(list)
+    }
+
+    fn with_capacity(n: usize) -> Self {
+        let n = n.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-n * Self::FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as u64;
+        let num_bits = num_bits.max(64);
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().max(1.0) as u32;
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    /// SHA-256 の先頭 16 バイトから2つのハッシュ値を作り、二重ハッシュ法で各位置を求める
+    fn positions(&self, password: &str) -> impl Iterator<Item = u64> + '_ {
+        let digest = Sha256::digest(password.as_bytes());
+        let h1 = u64::from_le_bytes(digest[0..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap()) | 1;
+        (0..self.num_hashes as u64)
+            .map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+    }
+
+    fn insert(&mut self, password: &str) {
+        let positions: Vec<u64> = self.positions(password).collect();
+        for pos in positions {
+            self.bits[(pos / 64) as usize] |= 1 << (pos % 64);
+        }
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        self.positions(password)
+            .all(|pos| self.bits[(pos / 64) as usize] & (1 << (pos % 64)) != 0)
+    }
+}
+
+/// 1行ずつ読み、空行とコメント行を除いた各パスワードを渡す
+fn for_each_entry(mut reader: impl BufRead, mut f: impl FnMut(&str)) -> std::io::Result<()> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let entry = line.trim_end_matches(['\n', '\r']);
+        if entry.is_empty() || entry.starts_with('#') {
+            continue;
+        }
+        f(entry);
+    }
+}
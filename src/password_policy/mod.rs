@@ -0,0 +1,254 @@
+pub mod breached;
+
+use std::sync::OnceLock;
+
+use chrono::Utc;
+use sea_orm::*;
+use tracing::info;
+
+use crate::{
+    error::{ApiError, FieldError},
+    models::password_history,
+    utils::password,
+};
+
+use breached::BreachedList;
+
+/// パスワードの上限文字数（ハッシュ計算のコストを抑えるため固定）
+const MAX_LENGTH: usize = 128;
+/// ユーザー情報との照合に使う部分文字列の最小長
+const MIN_USER_INFO_LEN: usize = 3;
+
+/// パスワードポリシー
+pub struct PasswordPolicy {
+    /// 最小文字数
+    pub min_length: usize,
+    /// 英小文字・英大文字・数字・記号のうち含める必要がある種類数
+    pub min_classes: usize,
+    /// 再利用を禁止する直近のパスワードの数（0 で無効）
+    pub history_size: usize,
+    /// 漏洩済みパスワードの一覧
+    pub breached: Option<BreachedList>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            min_classes: 2,
+            history_size: 5,
+            breached: None,
+        }
+    }
+}
+
+static POLICY: OnceLock<PasswordPolicy> = OnceLock::new();
+
+fn env_usize(key: &str, default: usize) -> anyhow::Result<usize> {
+    match std::env::var(key) {
+        Ok(v) => v
+            .parse()
+            .map_err(|_| anyhow::anyhow!("{} must be a non-negative integer", key)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// 環境変数からパスワードポリシーを初期化する
+/// - `PASSWORD_MIN_LENGTH`: 最小文字数（既定: 8）
+/// - `PASSWORD_MIN_CLASSES`: 必要な文字種の数 1〜4（既定: 2）
+/// - `PASSWORD_HISTORY_SIZE`: 再利用を禁止する直近のパスワード数（既定: 5）
+/// - `PASSWORD_BREACHED_LIST`: 漏洩済みパスワード一覧のファイルパス（1行1パスワード）
+pub fn init_from_env() -> anyhow::Result<()> {
+    let default = PasswordPolicy::default();
+    let min_length = env_usize("PASSWORD_MIN_LENGTH", default.min_length)?;
+    let min_classes = env_usize("PASSWORD_MIN_CLASSES", default.min_classes)?;
+    let history_size = env_usize("PASSWORD_HISTORY_SIZE", default.history_size)?;
+    if !(1..=MAX_LENGTH).contains(&min_length) {
+        anyhow::bail!("PASSWORD_MIN_LENGTH must be between 1 and {}", MAX_LENGTH);
+    }
+    if !(1..=4).contains(&min_classes) {
+        anyhow::bail!("PASSWORD_MIN_CLASSES must be between 1 and 4");
+    }
+
+    let breached = match std::env::var("PASSWORD_BREACHED_LIST") {
+        Ok(path) => {
+            let list = BreachedList::from_path(&path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+            info!("Loaded breached password list: {}", path);
+            Some(list)
+        }
+        Err(_) => None,
+    };
+
+    let policy = PasswordPolicy {
+        min_length,
+        min_classes,
+        history_size,
+        breached,
+    };
+    if POLICY.set(policy).is_err() {
+        info!("Password policy is already initialized");
+    }
+    Ok(())
+}
+
+/// 登録済みのポリシーを取得する（未登録の場合は既定値）
+pub fn policy() -> &'static PasswordPolicy {
+    POLICY.get_or_init(PasswordPolicy::default)
+}
+
+/// パスワードと照合するユーザー情報
+pub struct UserContext<'a> {
+    /// 既存ユーザーの場合のみ指定する（履歴との照合に使う）
+    pub user_id: Option<&'a str>,
+    /// 現在設定されているパスワードのハッシュ
+    pub current_hash: Option<&'a str>,
+    pub custom_id: &'a str,
+    pub name: &'a str,
+}
+
+fn count_classes(password: &str) -> usize {
+    [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_numeric()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ]
+    .into_iter()
+    .filter(|&b| b)
+    .count()
+}
+
+/// custom_id や名前（空白区切りの各部分を含む）がパスワードに含まれているか
+fn contains_user_info(password: &str, ctx: &UserContext<'_>) -> bool {
+    let password = password.to_lowercase();
+    std::iter::once(ctx.custom_id)
+        .chain(std::iter::once(ctx.name))
+        .chain(ctx.name.split_whitespace())
+        .map(str::to_lowercase)
+        .filter(|s| s.chars().count() >= MIN_USER_INFO_LEN)
+        .any(|s| password.contains(&s))
+}
+
+impl PasswordPolicy {
+    /// DB を参照しない規則を検証し、違反した規則をすべて返す
+    fn check_rules(&self, field: &str, password: &str, ctx: &UserContext<'_>) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let len = password.chars().count();
+        if len < self.min_length || len > MAX_LENGTH {
+            errors.push(FieldError::new(
+                field,
+                "length",
+                format!("{}〜{}文字で入力してください", self.min_length, MAX_LENGTH),
+            ));
+        }
+        if count_classes(password) < self.min_classes {
+            errors.push(FieldError::new(
+                field,
+                "weak_password",
+                format!(
+                    "英小文字・英大文字・数字・記号のうち{}種類以上を含めてください",
+                    self.min_classes
+                ),
+            ));
+        }
+        if contains_user_info(password, ctx) {
+            errors.push(FieldError::new(
+                field,
+                "contains_user_info",
+                "ユーザーIDや名前を含むパスワードは使用できません",
+            ));
+        }
+        if self.breached.as_ref().is_some_and(|b| b.contains(password)) {
+            errors.push(FieldError::new(
+                field,
+                "breached",
+                "このパスワードは漏洩が確認されているため使用できません",
+            ));
+        }
+        errors
+    }
+
+    /// 直近に使用したパスワードと同じかどうか
+    async fn is_reused<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        user_id: &str,
+        password_hash: &str,
+    ) -> Result<bool, DbErr> {
+        if self.history_size == 0 {
+            return Ok(false);
+        }
+        let recent = password_history::Entity::find()
+            .filter(password_history::Column::UserId.eq(user_id))
+            .order_by_desc(password_history::Column::Id)
+            .limit(self.history_size as u64)
+            .all(db)
+            .await?;
+        Ok(recent.iter().any(|h| h.password_hash == password_hash))
+    }
+}
+
+/// パスワードがポリシーを満たすか検証する
+/// 違反している場合は `field` に対するエラーをまとめて 422 として返す.
+pub async fn check<C: ConnectionTrait>(
+    db: &C,
+    field: &str,
+    password: &str,
+    ctx: &UserContext<'_>,
+) -> Result<(), ApiError> {
+    let policy = policy();
+    let mut errors = policy.check_rules(field, password, ctx);
+    if let Some(user_id) = ctx.user_id {
+        let hash = password::hash_password(password);
+        if ctx.current_hash == Some(hash.as_str()) || policy.is_reused(db, user_id, &hash).await? {
+            errors.push(FieldError::new(
+                field,
+                "reused",
+                "最近使用したパスワードは使用できません",
+            ));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::Validation(errors))
+    }
+}
+
+/// 新しく設定したパスワードのハッシュを履歴に追加し、保持数を超えた古い履歴を削除する
+pub async fn record_history<C: ConnectionTrait>(
+    db: &C,
+    user_id: &str,
+    password_hash: &str,
+) -> Result<(), DbErr> {
+    let history_size = policy().history_size;
+    if history_size == 0 {
+        return Ok(());
+    }
+    password_history::ActiveModel {
+        user_id: Set(user_id.to_string()),
+        password_hash: Set(password_hash.to_string()),
+        created_at: Set(Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    let stale: Vec<i32> = password_history::Entity::find()
+        .select_only()
+        .column(password_history::Column::Id)
+        .filter(password_history::Column::UserId.eq(user_id))
+        .order_by_desc(password_history::Column::Id)
+        .offset(history_size as u64)
+        .into_tuple()
+        .all(db)
+        .await?;
+    if !stale.is_empty() {
+        password_history::Entity::delete_many()
+            .filter(password_history::Column::Id.is_in(stale))
+            .exec(db)
+            .await?;
+    }
+    Ok(())
+}
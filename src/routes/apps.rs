@@ -8,19 +8,22 @@ use axum::{
 use chrono::Utc;
 use sea_orm::*;
 use serde::Serialize;
-use serde_json;
 use sha2::Digest;
 use ulid::Ulid;
 use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 use crate::{
+    audit::{AuditAction, AuditEntry},
     constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    extract::{ValidatedJson, ValidatedQuery},
     middleware::{auth::AuthUser, permission_check},
     models::{
         app::{self, Entity as App},
         user_app,
     },
-    routes::common_dtos::array_dto::ApiResponse,
+    routes::common_dtos::{array_dto::ApiResponse, pagination::CursorParams},
 };
 
 /// =======================
@@ -64,11 +67,13 @@ pub struct GetAllAppsQuery {
     path = "/apps",
     tag = "apps",
     params(
-        GetAllAppsQuery
+        GetAllAppsQuery,
+        CursorParams
     ),
     responses(
         (status = 200, description = "アプリケーション一覧の取得に成功", body = ApiResponse<Vec<AppResponse>>),
-        (status = 403, description = "権限なし"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "クエリに誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -78,13 +83,15 @@ pub async fn get_all_apps(
     State(db): State<DbConn>,
     auth_user: axum::Extension<AuthUser>,
     Query(query): Query<GetAllAppsQuery>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedQuery(page): ValidatedQuery<CursorParams>,
+) -> Result<impl IntoResponse, ApiError> {
     // all=trueの場合はAPP_READ権限をチェック
     if query.all {
         permission_check::require_permission(&auth_user, Permission::APP_READ, &db).await?;
     }
 
-    let apps = App::find().all(&db).await.unwrap();
+    let apps = page.apply(App::find(), app::Column::Id).all(&db).await?;
+    let (apps, next_cursor) = page.finish(apps, |a| &a.id);
 
     // client_secretは常に除外
     let responses: Vec<AppResponse> = apps
@@ -99,7 +106,10 @@ pub async fn get_all_apps(
         })
         .collect();
 
-    Ok((StatusCode::OK, Json(ApiResponse { data: responses })))
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse::paginated(responses, next_cursor)),
+    ))
 }
 
 /// 特定のアプリケーションを取得するための関数
@@ -113,7 +123,7 @@ pub async fn get_all_apps(
     ),
     responses(
         (status = 200, description = "アプリ情報の取得に成功", body = AppResponse),
-        (status = 404, description = "アプリが見つからない"),
+        (status = 404, description = "アプリが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -123,11 +133,8 @@ pub async fn get_app(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let app = App::find_by_id(id.clone())
-        .one(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<impl IntoResponse, ApiError> {
+    let app = App::find_by_id(id.clone()).one(&db).await?;
 
     if let Some(app) = app {
         // 所有者かどうかを確認
@@ -135,8 +142,7 @@ pub async fn get_app(
             .filter(user_app::Column::AppId.eq(&id))
             .filter(user_app::Column::UserId.eq(&auth_user.user_id))
             .one(&db)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .await?
             .is_some();
 
         let response = AppResponse {
@@ -154,12 +160,13 @@ pub async fn get_app(
 
         Ok((StatusCode::OK, Json(response)))
     } else {
-        Err(StatusCode::NOT_FOUND)
+        Err(ApiError::NotFound("アプリ"))
     }
 }
 
-#[derive(serde::Deserialize, ToSchema)]
+#[derive(serde::Deserialize, ToSchema, Validate)]
 pub struct CreateApp {
+    #[validate(length(min = 1, max = 100))]
     pub name: String,
     pub is_enable: Option<bool>,
 }
@@ -172,6 +179,7 @@ pub struct CreateApp {
     request_body = CreateApp,
     responses(
         (status = 201, description = "アプリケーションの作成に成功", body = AppResponse),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -179,9 +187,9 @@ pub struct CreateApp {
 )]
 pub async fn create_app(
     State(db): State<DbConn>,
-    _auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<CreateApp>,
-) -> Result<impl IntoResponse, StatusCode> {
+    auth_user: axum::Extension<AuthUser>,
+    ValidatedJson(payload): ValidatedJson<CreateApp>,
+) -> Result<impl IntoResponse, ApiError> {
     let client_secret = {
         let uuid = uuid::Uuid::new_v4().to_string();
         let hash = sha2::Sha256::digest(uuid.as_bytes());
@@ -196,7 +204,12 @@ pub async fn create_app(
         is_enable: Set(Some(payload.is_enable.unwrap_or(true))),
         client_secret: Set(client_secret.clone()),
     };
-    let res = am.insert(&db).await.unwrap();
+    let txn = db.begin().await?;
+    let res = am.insert(&txn).await?;
+    AuditEntry::created(AuditAction::AppCreate, &res.id, &res)
+        .record(&txn, &auth_user)
+        .await?;
+    txn.commit().await?;
 
     let response = AppResponse {
         id: res.id,
@@ -220,8 +233,9 @@ pub async fn create_app(
     request_body = CreateApp,
     responses(
         (status = 200, description = "アプリの更新に成功", body = AppResponse),
-        (status = 404, description = "アプリが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "アプリが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -231,17 +245,22 @@ pub async fn put_app(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<CreateApp>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedJson(payload): ValidatedJson<CreateApp>,
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::APP_UPDATE, &db).await?;
 
-    let found = app::Entity::find_by_id(id).one(&db).await.unwrap();
+    let found = app::Entity::find_by_id(id).one(&db).await?;
     if let Some(app_model) = found {
-        let mut am: app::ActiveModel = app_model.into();
+        let mut am: app::ActiveModel = app_model.clone().into();
         am.name = Set(payload.name);
         am.is_enable = Set(payload.is_enable);
         am.updated_at = Set(Some(Utc::now()));
-        let res = am.update(&db).await.unwrap();
+        let txn = db.begin().await?;
+        let res = am.update(&txn).await?;
+        AuditEntry::updated(AuditAction::AppUpdate, &res.id, &app_model, &res)
+            .record(&txn, &auth_user)
+            .await?;
+        txn.commit().await?;
 
         let response = AppResponse {
             id: res.id,
@@ -254,11 +273,12 @@ pub async fn put_app(
 
         return Ok((StatusCode::OK, Json(response)));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("アプリ"))
 }
 
-#[derive(serde::Deserialize, ToSchema)]
+#[derive(serde::Deserialize, ToSchema, Validate)]
 pub struct UpdateApp {
+    #[validate(length(min = 1, max = 100))]
     pub name: Option<String>,
     pub is_enable: Option<bool>,
 }
@@ -274,8 +294,9 @@ pub struct UpdateApp {
     request_body = UpdateApp,
     responses(
         (status = 200, description = "アプリの部分更新に成功", body = AppResponse),
-        (status = 404, description = "アプリが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "アプリが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -285,13 +306,13 @@ pub async fn patch_update_app(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<UpdateApp>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedJson(payload): ValidatedJson<UpdateApp>,
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::APP_UPDATE, &db).await?;
 
-    let found = app::Entity::find_by_id(id).one(&db).await.unwrap();
+    let found = app::Entity::find_by_id(id).one(&db).await?;
     if let Some(app) = found {
-        let mut am: app::ActiveModel = app.into();
+        let mut am: app::ActiveModel = app.clone().into();
         if let Some(name) = payload.name {
             am.name = Set(name);
         }
@@ -299,7 +320,12 @@ pub async fn patch_update_app(
             am.is_enable = Set(Some(is_enable));
         }
         am.updated_at = Set(Some(Utc::now()));
-        let res = am.update(&db).await.unwrap();
+        let txn = db.begin().await?;
+        let res = am.update(&txn).await?;
+        AuditEntry::updated(AuditAction::AppUpdate, &res.id, &app, &res)
+            .record(&txn, &auth_user)
+            .await?;
+        txn.commit().await?;
 
         let response = AppResponse {
             id: res.id,
@@ -312,7 +338,7 @@ pub async fn patch_update_app(
 
         return Ok((StatusCode::OK, Json(response)));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("アプリ"))
 }
 
 /// アプリケーションを削除するための関数
@@ -327,8 +353,9 @@ pub async fn patch_update_app(
     ),
     responses(
         (status = 204, description = "アプリの削除に成功"),
-        (status = 404, description = "アプリが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "アプリが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "他のデータから参照されているため削除できない", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -338,14 +365,19 @@ pub async fn delete_app(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::APP_DELETE, &db).await?;
 
-    let found = App::find_by_id(id).one(&db).await.unwrap();
+    let found = App::find_by_id(id).one(&db).await?;
     if let Some(app) = found {
-        let am: app::ActiveModel = app.into();
-        am.delete(&db).await.unwrap();
+        let am: app::ActiveModel = app.clone().into();
+        let txn = db.begin().await?;
+        am.delete(&txn).await?;
+        AuditEntry::deleted(AuditAction::AppDelete, &app.id, &app)
+            .record(&txn, &auth_user)
+            .await?;
+        txn.commit().await?;
         return Ok((StatusCode::NO_CONTENT, Json::<Option<app::Model>>(None)));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("アプリ"))
 }
@@ -0,0 +1,327 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    http::header,
+    response::IntoResponse,
+    routing::get,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    audit::{self, chain::VerifyReport},
+    constants::permissions::Permission,
+    error::{ApiError, FieldError, ProblemDetails},
+    middleware::{auth::AuthUser, permission_check},
+    models::audit_event,
+    routes::users_sub::search::SearchMetadata,
+};
+
+// =======================
+// DTO（レスポンス専用）
+// =======================
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditEventResponse {
+    pub id: i32,
+    pub actor_id: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    #[schema(value_type = Object)]
+    pub before: Option<serde_json::Value>,
+    #[schema(value_type = Object)]
+    pub after: Option<serde_json::Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: NaiveDateTime,
+    /// ユーザーの削除に伴って個人情報を消去した日時
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub redacted_at: Option<NaiveDateTime>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl From<audit_event::Model> for AuditEventResponse {
+    fn from(model: audit_event::Model) -> Self {
+        Self {
+            id: model.id,
+            actor_id: model.actor_id,
+            action: model.action,
+            target_type: model.target_type,
+            target_id: model.target_id,
+            before: model.before,
+            after: model.after,
+            ip_address: model.ip_address,
+            user_agent: model.user_agent,
+            created_at: model.created_at,
+            redacted_at: model.redacted_at,
+            prev_hash: model.prev_hash,
+            hash: model.hash,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditEventListResponse {
+    pub data: Vec<AuditEventResponse>,
+    pub meta: SearchMetadata,
+}
+
+pub fn routes() -> Router<DbConn> {
+    Router::new()
+        .route("/audit", get(get_audit_events))
+        .route("/audit/verify", get(verify_audit_chain))
+        .route("/audit/export", get(export_audit_events))
+        .route("/audit/{id}", get(get_audit_event))
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams, ToSchema)]
+#[serde(default)]
+pub struct AuditQuery {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    /// 操作したユーザーのID
+    pub actor_id: Option<String>,
+    /// 操作の種類（`user.update` など）
+    pub action: Option<String>,
+    /// 操作対象の種類（`user` / `role` / `app` / `session` / `ip`）
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub ip_address: Option<String>,
+    /// この日時以降（`YYYY-MM-DD` または `YYYY-MM-DD HH:MM:SS`）
+    pub since: Option<String>,
+    /// この日時より前（`YYYY-MM-DD` または `YYYY-MM-DD HH:MM:SS`）
+    pub until: Option<String>,
+}
+
+impl AuditQuery {
+    const DEFAULT_PAGE: usize = 1;
+    const DEFAULT_PER_PAGE: usize = 50;
+    const MAX_PER_PAGE: usize = 200;
+
+    pub fn page(&self) -> usize {
+        self.page.unwrap_or(Self::DEFAULT_PAGE).max(1)
+    }
+
+    pub fn per_page(&self) -> usize {
+        self.per_page
+            .unwrap_or(Self::DEFAULT_PER_PAGE)
+            .clamp(1, Self::MAX_PER_PAGE)
+    }
+}
+
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .ok()
+}
+
+fn invalid_datetime(field: &str) -> ApiError {
+    ApiError::Validation(vec![FieldError::new(
+        field,
+        "invalid_datetime",
+        "YYYY-MM-DD または YYYY-MM-DD HH:MM:SS の形式で指定してください",
+    )])
+}
+
+/// 監査ログを検索するための関数
+/// AUDIT_READ 権限が必要です
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "監査ログの取得に成功", body = AuditEventListResponse),
+        (status = 422, description = "日時の形式が不正", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn get_audit_events(
+    State(db): State<DbConn>,
+    Query(params): Query<AuditQuery>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission(&auth_user, Permission::AUDIT_READ, &db).await?;
+
+    let page = params.page();
+    let per_page = params.per_page();
+
+    let mut cond = Condition::all();
+    if let Some(ref v) = params.actor_id {
+        cond = cond.add(audit_event::Column::ActorId.eq(v));
+    }
+    if let Some(ref v) = params.action {
+        cond = cond.add(audit_event::Column::Action.eq(v));
+    }
+    if let Some(ref v) = params.target_type {
+        cond = cond.add(audit_event::Column::TargetType.eq(v));
+    }
+    if let Some(ref v) = params.target_id {
+        cond = cond.add(audit_event::Column::TargetId.eq(v));
+    }
+    if let Some(ref v) = params.ip_address {
+        cond = cond.add(audit_event::Column::IpAddress.eq(v));
+    }
+    // 監査ログでは条件を黙って無視しないよう、不正な日時は 400 にする
+    if let Some(ref v) = params.since {
+        let since = parse_datetime(v).ok_or_else(|| invalid_datetime("since"))?;
+        cond = cond.add(audit_event::Column::CreatedAt.gte(since));
+    }
+    if let Some(ref v) = params.until {
+        let until = parse_datetime(v).ok_or_else(|| invalid_datetime("until"))?;
+        cond = cond.add(audit_event::Column::CreatedAt.lt(until));
+    }
+
+    let paginator = audit_event::Entity::find()
+        .filter(cond)
+        .order_by_desc(audit_event::Column::Id)
+        .paginate(&db, per_page as u64);
+    let total = paginator.num_items().await?;
+    let events = paginator.fetch_page((page - 1) as u64).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(AuditEventListResponse {
+            data: events.into_iter().map(AuditEventResponse::from).collect(),
+            meta: SearchMetadata {
+                page,
+                per_page,
+                total,
+                total_pages: total.div_ceil(per_page as u64),
+            },
+        }),
+    ))
+}
+
+/// 監査ログを1件取得するための関数
+/// AUDIT_READ 権限が必要です
+#[utoipa::path(
+    get,
+    path = "/audit/{id}",
+    tag = "audit",
+    params(
+        ("id" = i32, Path, description = "監査ログID")
+    ),
+    responses(
+        (status = 200, description = "監査ログの取得に成功", body = AuditEventResponse),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "監査ログが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn get_audit_event(
+    State(db): State<DbConn>,
+    Path(id): Path<i32>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission(&auth_user, Permission::AUDIT_READ, &db).await?;
+
+    let event = audit_event::Entity::find_by_id(id)
+        .one(&db)
+        .await?
+        .ok_or(ApiError::NotFound("監査ログ"))?;
+
+    Ok((StatusCode::OK, Json(AuditEventResponse::from(event))))
+}
+
+/// 監査ログのハッシュチェーンを検証するための関数
+/// AUDIT_READ 権限が必要です
+///
+/// 各レコードが直前のレコードのハッシュを正しく引き継いでいるか、
+/// 内容とハッシュが一致しているかを先頭からすべて確認します。
+#[utoipa::path(
+    get,
+    path = "/audit/verify",
+    tag = "audit",
+    responses(
+        (status = 200, description = "検証完了（`valid` が false の場合は `breaks` に破損箇所）", body = VerifyReport),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn verify_audit_chain(
+    State(db): State<DbConn>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission(&auth_user, Permission::AUDIT_READ, &db).await?;
+
+    let report = audit::chain::verify(&db).await?;
+    Ok((StatusCode::OK, Json(report)))
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct AuditExportQuery {
+    /// この日時以降（`YYYY-MM-DD` または `YYYY-MM-DD HH:MM:SS`）
+    pub since: String,
+    /// この日時より前（`YYYY-MM-DD` または `YYYY-MM-DD HH:MM:SS`）
+    pub until: String,
+}
+
+/// 期間内の監査ログを署名付きの JSON Lines で書き出すための関数
+/// AUDIT_READ 権限が必要です
+///
+/// 1行に1レコードを出力し、最終行に `{"signature": "<JWT>"}` を付けます。
+/// JWT は `AUDIT_SIGNING_KEY` を鍵とする HS256 で、署名行を除いた本文の SHA-256 を含みます。
+#[utoipa::path(
+    get,
+    path = "/audit/export",
+    tag = "audit",
+    params(AuditExportQuery),
+    responses(
+        (status = 200, description = "エクスポート成功", content_type = "application/x-ndjson", body = String),
+        (status = 422, description = "日時の形式または範囲が不正", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "署名鍵が未設定、またはサーバーエラー", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "リクエストが多すぎる", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn export_audit_events(
+    State(db): State<DbConn>,
+    Query(params): Query<AuditExportQuery>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission(&auth_user, Permission::AUDIT_READ, &db).await?;
+
+    let since = parse_datetime(&params.since).ok_or_else(|| invalid_datetime("since"))?;
+    let until = parse_datetime(&params.until).ok_or_else(|| invalid_datetime("until"))?;
+    if since >= until {
+        return Err(ApiError::Validation(vec![FieldError::new(
+            "until",
+            "invalid_range",
+            "until には since より後の日時を指定してください",
+        )]));
+    }
+
+    let body = audit::export::export_jsonl(&db, since, until).await?;
+    let filename = format!(
+        "attachment; filename=\"audit_{}_{}.jsonl\"",
+        since.format("%Y%m%d%H%M%S"),
+        until.format("%Y%m%d%H%M%S")
+    );
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        body,
+    ))
+}
@@ -4,4 +4,20 @@ use utoipa::ToSchema;
 #[derive(Serialize, ToSchema)]
 pub struct ApiResponse<T> {
     pub data: T,
+    /// 続きがある場合に次のページの取得に使うカーソル
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            data,
+            next_cursor: None,
+        }
+    }
+
+    pub fn paginated(data: T, next_cursor: Option<String>) -> Self {
+        Self { data, next_cursor }
+    }
 }
@@ -1 +1,2 @@
 pub mod array_dto;
+pub mod pagination;
@@ -0,0 +1,58 @@
+use sea_orm::{ColumnTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+/// カーソル方式のページネーションのクエリ
+/// ID（ULID）の昇順に並べ、前回のレスポンスの `next_cursor` を `cursor` に渡すと続きを取得できる.
+#[derive(Debug, Default, Deserialize, IntoParams, ToSchema, Validate)]
+#[serde(default)]
+pub struct CursorParams {
+    /// 1回に取得する件数（既定: 50）
+    #[validate(range(min = 1, max = 200))]
+    pub limit: Option<u64>,
+    /// このIDより後から取得する
+    pub cursor: Option<String>,
+}
+
+impl CursorParams {
+    const DEFAULT_LIMIT: u64 = 50;
+
+    pub fn limit(&self) -> u64 {
+        self.limit.unwrap_or(Self::DEFAULT_LIMIT)
+    }
+
+    /// クエリにカーソル条件と並び順を付ける
+    /// 続きがあるかを判定するため `limit` より1件多く取得する.
+    pub fn apply<Q, C>(&self, query: Q, id: C) -> Q
+    where
+        Q: QueryFilter + QueryOrder + QuerySelect,
+        C: ColumnTrait,
+    {
+        let query = match self.cursor {
+            Some(ref cursor) => query.filter(id.gt(cursor.as_str())),
+            None => query,
+        };
+        query.order_by_asc(id).limit(self.limit() + 1)
+    }
+
+    /// `apply` したクエリの結果から余分な1件を除き、次のカーソルを返す
+    pub fn finish<T>(&self, rows: Vec<T>, id_of: impl Fn(&T) -> &str) -> (Vec<T>, Option<String>) {
+        self.finish_with(rows, |row| id_of(row).to_string())
+    }
+
+    /// `finish` と同じだが、ID 以外の値をカーソルにする
+    pub fn finish_with<T>(
+        &self,
+        mut rows: Vec<T>,
+        cursor_of: impl Fn(&T) -> String,
+    ) -> (Vec<T>, Option<String>) {
+        let limit = self.limit() as usize;
+        if rows.len() <= limit {
+            return (rows, None);
+        }
+        rows.truncate(limit);
+        let next_cursor = rows.last().map(cursor_of);
+        (rows, next_cursor)
+    }
+}
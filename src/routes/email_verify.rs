@@ -1,5 +1,5 @@
 use axum::{
-    Json, Router,
+    Extension, Json, Router,
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
@@ -10,13 +10,22 @@ use sea_orm::*;
 use serde::Serialize;
 use utoipa::ToSchema;
 
-use crate::{middleware::auth::AuthUser, models::email_verification};
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    middleware::{
+        auth::{AuthUser, ClientInfo},
+        permission_check,
+    },
+    models::{email_verification, user},
+};
 
+/// Email検証チャレンジの情報
+/// 検証コードはメールでのみ本人に届けるため、レスポンスには含めない.
 #[derive(Serialize, ToSchema)]
 pub struct EmailVerificationResponse {
-    pub id: i32,
     pub user_id: String,
-    pub verification_code: String,
     #[schema(value_type = String, format = "date-time")]
     pub created_at: Option<chrono::NaiveDateTime>,
     #[schema(value_type = String, format = "date-time")]
@@ -26,9 +35,7 @@ pub struct EmailVerificationResponse {
 impl From<email_verification::Model> for EmailVerificationResponse {
     fn from(model: email_verification::Model) -> Self {
         Self {
-            id: model.id,
             user_id: model.user_id,
-            verification_code: model.verification_code,
             created_at: model.created_at,
             expires_at: model.expires_at,
         }
@@ -36,113 +43,171 @@ impl From<email_verification::Model> for EmailVerificationResponse {
 }
 
 pub fn routes() -> Router<DbConn> {
-    Router::new().route(
-        "/email_verify/{id}",
-        get(get_email_verifications).delete(delete_email_verification),
-    )
+    Router::new()
+        .route(
+            "/email_verify/{code}",
+            get(get_email_verifications).delete(delete_email_verification),
+        )
+        .route(
+            "/email_verify/{code}/confirm",
+            post(confirm_email_verification),
+        )
     //.merge(users_sub::books::routes())
 }
 
+/// Email検証チャレンジを取得するための関数
+/// 本人以外は USER_READ 権限が必要です
 #[utoipa::path(
     get,
-    path = "/email_verify/{id}",
+    path = "/email_verify/{code}",
     tag = "email_verify",
     params(
-        ("id" = String, Path, description = "Email検証ID or 検証コード")
+        ("code" = String, Path, description = "検証コード")
     ),
     responses(
-        (status = 200, description = "Email検証情報取得成功", body = serde_json::Value),
-        (status = 404, description = "Email検証が見つからない"),
-        (status = 500, description = "サーバーエラー")
+        (status = 200, description = "Email検証情報取得成功", body = EmailVerificationResponse),
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Email検証が見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "サーバーエラー", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("session_token" = [])
     )
 )]
 pub async fn get_email_verifications(
     State(db): State<DbConn>,
-    Path(id): Path<String>,
-    _auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
-    if id.chars().all(|c| c.is_numeric()) {
-        let verifications = email_verification::Entity::find_by_id(
-            id.parse::<i32>().map_err(|_| StatusCode::BAD_REQUEST)?,
-        )
-        .filter(email_verification::Column::ExpiresAt.gt(Utc::now().naive_utc()))
-        .one(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-        if let Some(verification) = verifications {
-            return Ok((
-                StatusCode::OK,
-                Json(EmailVerificationResponse::from(verification)),
-            ));
-        }
-
-        return Err(StatusCode::NOT_FOUND);
-    }
-
+    Path(code): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
     let verification = email_verification::Entity::find()
-        .filter(email_verification::Column::VerificationCode.eq(id))
+        .filter(email_verification::Column::VerificationCode.eq(code))
         .filter(email_verification::Column::ExpiresAt.gt(Utc::now().naive_utc()))
         .one(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?
+        .ok_or(ApiError::NotFound("Email検証"))?;
 
-    if let Some(verification) = verification {
-        return Ok((
-            StatusCode::OK,
-            Json(EmailVerificationResponse::from(verification)),
-        ));
-    }
+    permission_check::require_permission_or_self(
+        &auth_user,
+        Permission::USER_READ,
+        &verification.user_id,
+        &db,
+    )
+    .await?;
 
-    Err(StatusCode::NOT_FOUND)
+    Ok((
+        StatusCode::OK,
+        Json(EmailVerificationResponse::from(verification)),
+    ))
 }
 
 /// ユーザーのEmail検証チャレンジを削除するための関数
+/// 本人以外は USER_UPDATE 権限が必要です
 #[utoipa::path(
     delete,
-    path = "/email_verify/{id}",
+    path = "/email_verify/{code}",
     tag = "email_verify",
     params(
-        ("id" = String, Path, description = "Email検証ID or 検証コード")
+        ("code" = String, Path, description = "検証コード")
     ),
     responses(
         (status = 204, description = "Email検証削除成功"),
-        (status = 404, description = "Email検証が見つからない"),
-        (status = 500, description = "サーバーエラー")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Email検証が見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "サーバーエラー", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("session_token" = [])
     )
 )]
 pub async fn delete_email_verification(
     State(db): State<DbConn>,
-    Path(id): Path<String>,
-    _auth_user: axum::Extension<AuthUser>,
-) -> Result<StatusCode, StatusCode> {
-    if id.chars().all(|c| c.is_numeric()) {
-        email_verification::Entity::delete_by_id(
-            id.parse::<i32>().map_err(|_| StatusCode::BAD_REQUEST)?,
-        )
-        .exec(&db)
-        .await
-        .map_err(|e| match e {
-            DbErr::RecordNotFound(_) => StatusCode::NOT_FOUND,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
-
-        return Ok(StatusCode::NO_CONTENT);
-    }
-
+    Path(code): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<StatusCode, ApiError> {
     let found = email_verification::Entity::find()
-        .filter(email_verification::Column::VerificationCode.eq(id))
+        .filter(email_verification::Column::VerificationCode.eq(code))
         .one(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    if let Some(found) = found {
-        let am: email_verification::ActiveModel = found.into();
-        am.delete(&db)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        return Ok(StatusCode::NO_CONTENT);
+        .await?
+        .ok_or(ApiError::NotFound("Email検証"))?;
+
+    permission_check::require_permission_or_self(
+        &auth_user,
+        Permission::USER_UPDATE,
+        &found.user_id,
+        &db,
+    )
+    .await?;
+
+    let am: email_verification::ActiveModel = found.into();
+    am.delete(&db).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Email検証チャレンジを消費してユーザーのメールアドレスを検証済みにするための関数
+///
+/// 検証コードそのものが資格情報となるため、ログインしていなくても呼び出せます。
+/// 一度使用した検証コード（および同じユーザーの他の検証コード）は削除されます。
+#[utoipa::path(
+    post,
+    path = "/email_verify/{code}/confirm",
+    tag = "email_verify",
+    params(
+        ("code" = String, Path, description = "検証コード")
+    ),
+    responses(
+        (status = 204, description = "Email検証成功"),
+        (status = 404, description = "Email検証が見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 410, description = "Email検証の有効期限切れ", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "サーバーエラー", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn confirm_email_verification(
+    State(db): State<DbConn>,
+    Extension(client): Extension<ClientInfo>,
+    Path(code): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let txn = db.begin().await?;
+
+    let verification = email_verification::Entity::find()
+        .filter(email_verification::Column::VerificationCode.eq(&code))
+        .one(&txn)
+        .await?
+        .ok_or(ApiError::NotFound("Email検証"))?;
+
+    if verification.expires_at <= Utc::now().naive_utc() {
+        return Err(ApiError::Gone(
+            "検証コードの有効期限が切れています".to_string(),
+        ));
     }
 
-    Err(StatusCode::NOT_FOUND)
+    // 先に自分自身を削除し、同時に使われた場合は片方だけ成功させる
+    let consumed = email_verification::Entity::delete_many()
+        .filter(email_verification::Column::VerificationCode.eq(&code))
+        .exec(&txn)
+        .await?;
+    if consumed.rows_affected == 0 {
+        return Err(ApiError::NotFound("Email検証"));
+    }
+
+    // 同じユーザーの残りのチャレンジも不要になる
+    email_verification::Entity::delete_many()
+        .filter(email_verification::Column::UserId.eq(&verification.user_id))
+        .exec(&txn)
+        .await?;
+
+    let found = user::Entity::find_active_by_id(&verification.user_id)
+        .one(&txn)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+    let mut am: user::ActiveModel = found.clone().into();
+    am.email_verified = Set(true);
+    am.updated_at = Set(Some(Utc::now().naive_utc()));
+    let updated = am.update(&txn).await?;
+    AuditEntry::updated(AuditAction::EmailVerify, &found.id, &found, &updated)
+        .record_as(&txn, &found.id, &client)
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -1,4 +1,5 @@
 pub mod apps;
+pub mod audit;
 pub mod common_dtos;
 pub mod email_verify;
 pub mod roles;
@@ -10,12 +10,19 @@ use sea_orm::*;
 use serde::Serialize;
 use ulid::Ulid;
 use utoipa::ToSchema;
+use validator::Validate;
 
 use crate::{
+    audit::{AuditAction, AuditEntry},
     constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    extract::{ValidatedJson, ValidatedQuery},
     middleware::{auth::AuthUser, permission_check},
     models::role::{self, Entity as Role},
-    routes::{common_dtos::array_dto::ApiResponse, roles_sub},
+    routes::{
+        common_dtos::{array_dto::ApiResponse, pagination::CursorParams},
+        roles_sub,
+    },
 };
 
 /// =======================
@@ -68,9 +75,11 @@ pub fn routes() -> Router<DbConn> {
     get,
     path = "/roles",
     tag = "roles",
+    params(CursorParams),
     responses(
         (status = 200, description = "ロール一覧の取得に成功", body = ApiResponse<Vec<RoleResponse>>),
-        (status = 403, description = "権限なし"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "クエリに誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -79,12 +88,17 @@ pub fn routes() -> Router<DbConn> {
 pub async fn get_all_roles(
     State(db): State<DbConn>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedQuery(page): ValidatedQuery<CursorParams>,
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::ROLE_MANAGE, &db).await?;
 
-    let roles = Role::find().all(&db).await.unwrap();
+    let roles = page.apply(Role::find(), role::Column::Id).all(&db).await?;
+    let (roles, next_cursor) = page.finish(roles, |r| &r.id);
     let responses: Vec<RoleResponse> = roles.into_iter().map(RoleResponse::from).collect();
-    Ok((StatusCode::OK, Json(ApiResponse { data: responses })))
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse::paginated(responses, next_cursor)),
+    ))
 }
 
 /// 特定のロールを取得するための関数
@@ -97,8 +111,8 @@ pub async fn get_all_roles(
     ),
     responses(
         (status = 200, description = "ロール情報の取得に成功", body = RoleResponse),
-        (status = 404, description = "ロールが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "ロールが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -108,22 +122,25 @@ pub async fn get_role(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::ROLE_MANAGE, &db).await?;
 
-    let role = Role::find_by_id(id).one(&db).await.unwrap();
+    let role = Role::find_by_id(id).one(&db).await?;
 
     if let Some(role) = role {
         Ok((StatusCode::OK, Json(RoleResponse::from(role))))
     } else {
-        Err(StatusCode::NOT_FOUND)
+        Err(ApiError::NotFound("ロール"))
     }
 }
 
-#[derive(serde::Deserialize, ToSchema)]
+#[derive(serde::Deserialize, ToSchema, Validate)]
 pub struct CreateRole {
+    #[validate(custom(function = "crate::utils::validation::custom_id"))]
     pub custom_id: String,
+    #[validate(length(min = 1, max = 100))]
     pub name: String,
+    #[validate(range(min = 0))]
     pub permission: i32,
     pub is_system: Option<bool>,
     pub is_enable: Option<bool>,
@@ -138,7 +155,9 @@ pub struct CreateRole {
     request_body = CreateRole,
     responses(
         (status = 201, description = "ロールの作成に成功", body = RoleResponse),
-        (status = 403, description = "権限なし"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "custom_id が既に使用されている", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -147,8 +166,8 @@ pub struct CreateRole {
 pub async fn create_role(
     State(db): State<DbConn>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<CreateRole>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedJson(payload): ValidatedJson<CreateRole>,
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::ROLE_MANAGE, &db).await?;
 
     let am = role::ActiveModel {
@@ -161,7 +180,12 @@ pub async fn create_role(
         is_enable: Set(Some(payload.is_enable.unwrap_or(true))),
         is_system: Set(Some(payload.is_system.unwrap_or(false))),
     };
-    let res = am.insert(&db).await.unwrap();
+    let txn = db.begin().await?;
+    let res = am.insert(&txn).await?;
+    AuditEntry::created(AuditAction::RoleCreate, &res.id, &res)
+        .record(&txn, &auth_user)
+        .await?;
+    txn.commit().await?;
     Ok((StatusCode::CREATED, Json(RoleResponse::from(res))))
 }
 
@@ -175,8 +199,10 @@ pub async fn create_role(
     request_body = CreateRole,
     responses(
         (status = 200, description = "ロールの更新に成功", body = RoleResponse),
-        (status = 404, description = "ロールが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "ロールが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "custom_id が既に使用されている", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -186,29 +212,37 @@ pub async fn put_role(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<CreateRole>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedJson(payload): ValidatedJson<CreateRole>,
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::ROLE_MANAGE, &db).await?;
 
-    let found = role::Entity::find_by_id(id).one(&db).await.unwrap();
-    if let Some(user) = found {
-        let mut am: role::ActiveModel = user.into();
+    let found = role::Entity::find_by_id(id).one(&db).await?;
+    if let Some(before) = found {
+        let mut am: role::ActiveModel = before.clone().into();
         am.custom_id = Set(payload.custom_id);
         am.name = Set(Some(payload.name));
         am.permission = Set(payload.permission);
         am.is_system = Set(Some(payload.is_system.unwrap_or(false)));
         am.is_enable = Set(Some(payload.is_enable.unwrap_or(false)));
         am.updated_at = Set(Utc::now());
-        let res = am.update(&db).await.unwrap();
+        let txn = db.begin().await?;
+        let res = am.update(&txn).await?;
+        AuditEntry::updated(AuditAction::RoleUpdate, &res.id, &before, &res)
+            .record(&txn, &auth_user)
+            .await?;
+        txn.commit().await?;
         return Ok((StatusCode::OK, Json(RoleResponse::from(res))));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ロール"))
 }
 
-#[derive(serde::Deserialize, ToSchema)]
+#[derive(serde::Deserialize, ToSchema, Validate)]
 pub struct UpdateRole {
+    #[validate(length(min = 1, max = 100))]
     pub name: Option<String>,
+    #[validate(custom(function = "crate::utils::validation::custom_id"))]
     pub custom_id: Option<String>,
+    #[validate(range(min = 0))]
     pub permission: Option<i32>,
     pub is_system: Option<bool>,
     pub is_enable: Option<bool>,
@@ -225,8 +259,10 @@ pub struct UpdateRole {
     request_body = UpdateRole,
     responses(
         (status = 200, description = "ロールの部分更新に成功", body = RoleResponse),
-        (status = 404, description = "ロールが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "ロールが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "custom_id が既に使用されている", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -236,13 +272,13 @@ pub async fn patch_update_role(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<UpdateRole>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedJson(payload): ValidatedJson<UpdateRole>,
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::ROLE_MANAGE, &db).await?;
 
-    let found = role::Entity::find_by_id(id).one(&db).await.unwrap();
-    if let Some(user) = found {
-        let mut am: role::ActiveModel = user.into();
+    let found = role::Entity::find_by_id(id).one(&db).await?;
+    if let Some(before) = found {
+        let mut am: role::ActiveModel = before.clone().into();
         if let Some(name) = payload.name {
             am.name = Set(Some(name));
         }
@@ -259,10 +295,15 @@ pub async fn patch_update_role(
             am.is_enable = Set(Some(is_enable));
         }
         am.updated_at = Set(Utc::now());
-        let res = am.update(&db).await.unwrap();
+        let txn = db.begin().await?;
+        let res = am.update(&txn).await?;
+        AuditEntry::updated(AuditAction::RoleUpdate, &res.id, &before, &res)
+            .record(&txn, &auth_user)
+            .await?;
+        txn.commit().await?;
         return Ok((StatusCode::OK, Json(RoleResponse::from(res))));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ロール"))
 }
 
 /// ロールを削除するための関数
@@ -275,8 +316,9 @@ pub async fn patch_update_role(
     ),
     responses(
         (status = 204, description = "ロールの削除に成功"),
-        (status = 404, description = "ロールが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "ロールが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "他のデータから参照されているため削除できない", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -286,14 +328,19 @@ pub async fn delete_role(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::ROLE_MANAGE, &db).await?;
 
-    let found = Role::find_by_id(id).one(&db).await.unwrap();
+    let found = Role::find_by_id(id).one(&db).await?;
     if let Some(role) = found {
-        let am: role::ActiveModel = role.into();
-        am.delete(&db).await.unwrap();
+        let am: role::ActiveModel = role.clone().into();
+        let txn = db.begin().await?;
+        am.delete(&txn).await?;
+        AuditEntry::deleted(AuditAction::RoleDelete, &role.id, &role)
+            .record(&txn, &auth_user)
+            .await?;
+        txn.commit().await?;
         return Ok((StatusCode::NO_CONTENT, Json::<Option<role::Model>>(None)));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ロール"))
 }
@@ -1,16 +1,13 @@
-use axum::{
-    Json, Router,
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::get,
-};
+use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
 use sea_orm::*;
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 use crate::{
     constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    extract::ValidatedQuery,
     middleware::{auth::AuthUser, permission_check},
     models::role::{self, Entity as Role},
     routes::roles::RoleResponse,
@@ -38,10 +35,12 @@ pub fn routes() -> Router<DbConn> {
     Router::new().route("/roles/search", get(search_roles))
 }
 
-#[derive(Debug, Deserialize, IntoParams)]
+#[derive(Debug, Default, Deserialize, IntoParams, Validate)]
 #[serde(default)]
 pub struct SearchParams {
+    #[validate(range(min = 1))]
     pub page: Option<usize>,
+    #[validate(range(min = 1, max = 100))]
     pub per_page: Option<usize>,
     pub q: Option<String>,
     pub name: Option<String>,
@@ -61,18 +60,6 @@ impl SearchParams {
     }
 }
 
-impl Default for SearchParams {
-    fn default() -> Self {
-        Self {
-            page: None,
-            per_page: None,
-            q: None,
-            name: None,
-            custom_id: None,
-        }
-    }
-}
-
 #[utoipa::path(
     get,
     path = "/roles/search",
@@ -80,7 +67,9 @@ impl Default for SearchParams {
     params(SearchParams),
     responses(
         (status = 200, description = "ロール検索成功", body = SearchRolesResponse),
-        (status = 403, description = "アクセス権限なし")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "リクエストが多すぎる", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -88,9 +77,9 @@ impl Default for SearchParams {
 )]
 pub async fn search_roles(
     State(db): State<crate::db::DbConn>,
-    Query(params): Query<SearchParams>,
+    ValidatedQuery(params): ValidatedQuery<SearchParams>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::ROLE_MANAGE, &db).await?;
 
     let page = params.page();
@@ -127,13 +116,10 @@ pub async fn search_roles(
 
     // ページング
     let paginator = select.paginate(&db, per_page as u64);
-    let roles = paginator
-        .fetch_page(page_index as u64)
-        .await
-        .unwrap_or_default();
-    let total = paginator.num_items().await.unwrap_or(0);
+    let roles = paginator.fetch_page(page_index as u64).await?;
+    let total = paginator.num_items().await?;
 
-    let total_pages = (total as usize + per_page - 1) / per_page;
+    let total_pages = (total as usize).div_ceil(per_page);
     let data: Vec<RoleResponse> = roles.into_iter().map(RoleResponse::from).collect();
 
     Ok((
@@ -5,15 +5,25 @@ use axum::{
     response::IntoResponse,
     routing::*,
 };
-use sea_orm::{prelude::DateTimeUtc, *};
+use sea_orm::{
+    prelude::DateTimeUtc,
+    sea_query::{Expr, Func, SimpleExpr},
+    *,
+};
 use serde::Serialize;
 use utoipa::ToSchema;
 
 use crate::{
+    audit::AuditEntry,
     constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    extract::ValidatedQuery,
     middleware::{auth::AuthUser, permission_check},
     models::session::{self, Entity as Session},
-    routes::{common_dtos::array_dto::ApiResponse, users::PublicUserResponse},
+    routes::{
+        common_dtos::{array_dto::ApiResponse, pagination::CursorParams},
+        users::PublicUserResponse,
+    },
 };
 
 /// =======================
@@ -40,13 +50,18 @@ pub fn routes() -> Router<DbConn> {
 }
 
 /// すべてのセッションを取得するための関数
+///
+/// 作成日時の順に並べる（カーソルは前回のレスポンスの `next_cursor` をそのまま渡す）。
 #[utoipa::path(
     get,
     path = "/sessions",
     tag = "sessions",
+    params(CursorParams),
     responses(
         (status = 200, description = "セッション一覧の取得に成功", body = ApiResponse<Vec<SessionResponse>>),
-        (status = 403, description = "権限なし"),
+        (status = 400, description = "カーソルが不正", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "クエリに誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -55,39 +70,78 @@ pub fn routes() -> Router<DbConn> {
 pub async fn get_all_sessions(
     State(db): State<DbConn>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedQuery(page): ValidatedQuery<CursorParams>,
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::SESSION_MANAGE, &db).await?;
 
     // relatedでuserも取得する
-    let joined = Session::find()
-        .find_with_related(crate::models::user::Entity)
+    let mut query = Session::find().find_also_related(crate::models::user::Entity);
+    if let Some(ref cursor) = page.cursor {
+        let (created_at, id) = parse_cursor(cursor)?;
+        query = query.filter(
+            Condition::any()
+                .add(Expr::expr(created_at_key()).gt(created_at))
+                .add(
+                    Condition::all()
+                        .add(Expr::expr(created_at_key()).eq(created_at))
+                        .add(session::Column::Id.gt(id)),
+                ),
+        );
+    }
+    let joined = query
+        .order_by_asc(created_at_key())
+        .order_by_asc(session::Column::Id)
+        .limit(page.limit() + 1)
         .all(&db)
-        .await
-        .unwrap();
+        .await?;
+    let (joined, next_cursor) = page.finish_with(joined, |(s, _)| {
+        let created_at = s.created_at.unwrap_or(DateTimeUtc::UNIX_EPOCH);
+        format!("{}_{}", created_at.timestamp_micros(), s.id)
+    });
 
     let session_responses: Vec<SessionResponse> = joined
         .into_iter()
-        .filter_map(|(session, users)| {
-            users.first().map(|user| SessionResponse {
+        .filter_map(|(session, user)| {
+            user.map(|user| SessionResponse {
                 id: session.id,
                 created_at: session.created_at,
                 expires_at: session.expires_at,
                 ip_address: session.ip_address,
                 user_agent: session.user_agent,
                 is_enable: session.is_enable,
-                user: PublicUserResponse::from(user.clone()),
+                user: PublicUserResponse::from(user),
             })
         })
         .collect();
 
     Ok((
         StatusCode::OK,
-        Json(ApiResponse {
-            data: session_responses,
-        }),
+        Json(ApiResponse::paginated(session_responses, next_cursor)),
     ))
 }
 
+/// 一覧の並び順に使う作成日時（作成日時の無いセッションは先頭に並べる）
+///
+/// セッション ID はランダムな値で作成順に並ばないため、作成日時の順に並べ、同じ日時の中で ID の順に並べる.
+fn created_at_key() -> SimpleExpr {
+    Func::coalesce([
+        Expr::col(session::Column::CreatedAt).into(),
+        Expr::val(DateTimeUtc::UNIX_EPOCH).into(),
+    ])
+    .into()
+}
+
+/// `<作成日時のマイクロ秒>_<セッション ID>` 形式のカーソルを読む
+fn parse_cursor(cursor: &str) -> Result<(DateTimeUtc, &str), ApiError> {
+    cursor
+        .split_once('_')
+        .and_then(|(micros, id)| {
+            let created_at = DateTimeUtc::from_timestamp_micros(micros.parse().ok()?)?;
+            Some((created_at, id))
+        })
+        .ok_or_else(|| ApiError::BadRequest("cursor が不正です".to_string()))
+}
+
 /// 特定のセッションを取得するための関数
 #[utoipa::path(
     get,
@@ -98,8 +152,8 @@ pub async fn get_all_sessions(
     ),
     responses(
         (status = 200, description = "セッション情報の取得に成功", body = SessionResponse),
-        (status = 404, description = "セッションが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "セッションが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -109,14 +163,13 @@ pub async fn get_session(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     // セッションと関連のデータを結合して取得する（例: user を関連として取得する場合）
     let joined = Session::find()
         .filter(session::Column::Id.eq(id.clone()))
         .find_with_related(crate::models::user::Entity)
         .all(&db)
-        .await
-        .unwrap();
+        .await?;
     // find_with_related は Vec<(session::Model, Vec<related::Model>)> を返す
     if let Some((session, related)) = joined.into_iter().next() {
         // 自分のセッションでない場合は SESSION_MANAGE 権限が必要
@@ -138,7 +191,7 @@ pub async fn get_session(
             return Ok((StatusCode::OK, Json(response)));
         }
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("セッション"))
 }
 
 /// セッションを削除するための関数
@@ -151,8 +204,8 @@ pub async fn get_session(
     ),
     responses(
         (status = 204, description = "セッションの削除に成功"),
-        (status = 404, description = "セッションが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "セッションが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -162,8 +215,8 @@ pub async fn delete_session(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let found = Session::find_by_id(id).one(&db).await.unwrap();
+) -> Result<impl IntoResponse, ApiError> {
+    let found = Session::find_by_id(id).one(&db).await?;
     if let Some(session) = found {
         // 自分のセッションでない場合は SESSION_MANAGE 権限が必要
         if session.user_id != auth_user.user_id {
@@ -171,9 +224,14 @@ pub async fn delete_session(
                 .await?;
         }
 
-        let am: session::ActiveModel = session.into();
-        am.delete(&db).await.unwrap();
+        let am: session::ActiveModel = session.clone().into();
+        let txn = db.begin().await?;
+        am.delete(&txn).await?;
+        AuditEntry::session_deleted(&session)
+            .record(&txn, &auth_user)
+            .await?;
+        txn.commit().await?;
         return Ok((StatusCode::NO_CONTENT, Json::<Option<session::Model>>(None)));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("セッション"))
 }
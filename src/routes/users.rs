@@ -10,22 +10,37 @@ use sea_orm::*;
 use serde::Serialize;
 use ulid::Ulid;
 use utoipa::ToSchema;
+use validator::Validate;
 
-use crate::routes::users_sub::discord::DiscordResponse;
+use crate::routes::{
+    roles::RoleResponse,
+    users_sub::{
+        discord::DiscordResponse, email_change::EmailField, update_policy::UpdatePrincipal,
+    },
+};
 use crate::{
+    audit::{AuditAction, AuditEntry},
     constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    extract::{ValidatedJson, ValidatedQuery},
+    mailer::{self, Locale, Template},
     middleware::{auth::AuthUser, permission_check},
     models::{
-        discord,
+        access_tokens, discord, refresh_tokens, session,
         user::{self, Entity as User},
     },
-    routes::{common_dtos::array_dto::ApiResponse, users_sub},
+    password_policy::{self, UserContext},
+    routes::{
+        common_dtos::{array_dto::ApiResponse, pagination::CursorParams},
+        users_sub,
+    },
+    search,
     utils::password,
 };
 
-/// =======================
-/// DTO（レスポンス専用）
-/// =======================
+// =======================
+// DTO（レスポンス専用）
+// =======================
 
 /// 公開用ユーザー情報(権限なしでも見られる情報)
 #[derive(Serialize, ToSchema)]
@@ -44,6 +59,7 @@ pub struct DetailedUserResponse {
     pub id: String,
     pub custom_id: String,
     pub name: String,
+    pub name_kana: Option<String>,
     pub email: String,
     pub external_email: String,
     pub birthdate: Option<chrono::NaiveDate>,
@@ -58,6 +74,9 @@ pub struct DetailedUserResponse {
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
     pub discords: Vec<DiscordResponse>,
+    /// 一覧・検索で読み込んだ場合のみ含まれる
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<RoleResponse>>,
 }
 
 /// ユーザーリストのレスポンス型（権限に応じて異なる型を返す）
@@ -95,6 +114,7 @@ impl From<user::Model> for DetailedUserResponse {
             id: user.id,
             custom_id: user.custom_id,
             name: user.name,
+            name_kana: user.name_kana,
             email: user.email,
             external_email: user.external_email,
             birthdate: user.birthdate,
@@ -109,6 +129,7 @@ impl From<user::Model> for DetailedUserResponse {
             created_at: user.created_at,
             updated_at: user.updated_at,
             discords: Vec::new(),
+            roles: None,
         }
     }
 }
@@ -124,12 +145,19 @@ pub fn routes() -> Router<DbConn> {
                 .put(put_user),
         )
         .merge(users_sub::discord::routes())
+        .merge(users_sub::email_change::routes())
         .merge(users_sub::roles::routes())
         .merge(users_sub::password::routes())
         .merge(users_sub::search::routes())
+        .merge(users_sub::bulk::routes())
         .merge(users_sub::sessions::routes())
+        .merge(users_sub::transfer::routes())
         .merge(users_sub::email_verify::routes())
         .merge(users_sub::permissions::routes())
+        .merge(users_sub::lockout::routes())
+        .merge(users_sub::restore::routes())
+        .merge(users_sub::personal_data::routes())
+        .merge(users_sub::deletion::routes())
 }
 
 /// すべてのユーザーを取得するための関数
@@ -137,9 +165,11 @@ pub fn routes() -> Router<DbConn> {
     get,
     path = "/users",
     tag = "users",
+    params(CursorParams),
     responses(
         (status = 200, description = "ユーザー一覧の取得に成功", body = UserListResponse),
-        (status = 403, description = "権限なし"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "クエリに誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -148,14 +178,20 @@ pub fn routes() -> Router<DbConn> {
 pub async fn get_all_users(
     State(db): State<DbConn>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedQuery(page): ValidatedQuery<CursorParams>,
+) -> Result<impl IntoResponse, ApiError> {
     // USER_READ があるかどうかを判定（失敗しても許可し、後続でマスキング＆フィルタ）
     let has_user_read =
         permission_check::require_permission(&auth_user, Permission::USER_READ, &db)
             .await
             .is_ok();
 
-    let users = User::find().all(&db).await.unwrap();
+    let mut query = User::find_active();
+    if !has_user_read {
+        query = query.filter(public_user_condition());
+    }
+    let users = page.apply(query, user::Column::Id).all(&db).await?;
+    let (users, next_cursor) = page.finish(users, |u| &u.id);
 
     if has_user_read {
         // 権限ありの場合は詳細情報を返す
@@ -163,30 +199,43 @@ pub async fn get_all_users(
             users.into_iter().map(DetailedUserResponse::from).collect();
         Ok((
             StatusCode::OK,
-            Json(UserListResponse::Detailed(ApiResponse {
-                data: detailed_users,
-            })),
+            Json(UserListResponse::Detailed(ApiResponse::paginated(
+                detailed_users,
+                next_cursor,
+            ))),
         ))
     } else {
         // 権限なしの場合は公開情報のみを返す
-        // is_suspended=true または is_enable=false または tmp_ メールのユーザーを除外
-        let public_users: Vec<PublicUserResponse> = users
-            .into_iter()
-            .filter(|u| {
-                let suspended = u.is_suspended.unwrap_or(false);
-                let enabled = u.is_enable.unwrap_or(true);
-                let has_tmp_email = u.email.contains("tmp_");
-                !suspended && enabled && !has_tmp_email
-            })
-            .map(PublicUserResponse::from)
-            .collect();
+        let public_users: Vec<PublicUserResponse> =
+            users.into_iter().map(PublicUserResponse::from).collect();
         Ok((
             StatusCode::OK,
-            Json(UserListResponse::Public(ApiResponse { data: public_users })),
+            Json(UserListResponse::Public(ApiResponse::paginated(
+                public_users,
+                next_cursor,
+            ))),
         ))
     }
 }
 
+/// 権限のないユーザーにも公開してよいユーザーの条件
+/// is_suspended=true または is_enable=false または tmp_ メールのユーザーを除外する.
+pub fn public_user_condition() -> Condition {
+    Condition::all()
+        .add(
+            Condition::any()
+                .add(user::Column::IsSuspended.is_null())
+                .add(user::Column::IsSuspended.eq(false)),
+        )
+        .add(
+            Condition::any()
+                .add(user::Column::IsEnable.is_null())
+                .add(user::Column::IsEnable.eq(true)),
+        )
+        // `_` は LIKE のワイルドカードなのでエスケープする
+        .add(user::Column::Email.not_like(sea_query::LikeExpr::new("%tmp\\_%").escape('\\')))
+}
+
 /// 特定のユーザーを取得するための関数
 #[utoipa::path(
     get,
@@ -197,8 +246,8 @@ pub async fn get_all_users(
     ),
     responses(
         (status = 200, description = "ユーザー情報の取得に成功", body = UserResponse),
-        (status = 404, description = "ユーザーが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -208,7 +257,7 @@ pub async fn get_user(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     // 自分自身かどうか & USER_READ 権限の有無をチェック
     let is_self = auth_user.user_id == id;
     let has_user_read =
@@ -216,11 +265,10 @@ pub async fn get_user(
             .await
             .is_ok();
 
-    let user = User::find_by_id(id.clone())
+    let user = User::find_active_by_id(id.clone())
         .find_with_related(discord::Entity)
         .all(&db)
-        .await
-        .unwrap();
+        .await?;
 
     if let Some((user_model, discord_models)) = user.into_iter().next() {
         // 権限が無く本人でもない場合は、is_suspended=true または is_enable=false のユーザーは非表示
@@ -229,7 +277,7 @@ pub async fn get_user(
             let enabled = user_model.is_enable.unwrap_or(true);
             let has_tmp_email = user_model.email.contains("tmp_");
             if suspended || !enabled || has_tmp_email {
-                return Err(StatusCode::NOT_FOUND);
+                return Err(ApiError::NotFound("ユーザー"));
             }
         }
 
@@ -247,17 +295,33 @@ pub async fn get_user(
             Ok((StatusCode::OK, Json(UserResponse::Public(public))))
         }
     } else {
-        Err(StatusCode::NOT_FOUND)
+        Err(ApiError::NotFound("ユーザー"))
     }
 }
 
-#[derive(serde::Deserialize, ToSchema)]
+/// メールアドレスが指定されなかった場合に割り当てるアドレス
+pub fn default_email(period: Option<&str>, custom_id: &str) -> String {
+    match period {
+        Some(period) => format!("{}.{}@uniproject.jp", period, custom_id),
+        None => format!("temp_{}@uniproject.jp", custom_id),
+    }
+}
+
+#[derive(serde::Deserialize, ToSchema, Validate)]
 pub struct CreateUser {
+    #[validate(custom(function = "crate::utils::validation::custom_id"))]
     pub custom_id: String,
+    #[validate(length(min = 1, max = 100))]
     pub name: String,
+    /// 名前の読み（ひらがな・カタカナ）。検索に使う
+    #[validate(length(max = 100))]
+    pub name_kana: Option<String>,
     pub password: String,
+    #[validate(email)]
     pub email: Option<String>,
+    #[validate(email)]
     pub external_email: String,
+    #[validate(custom(function = "crate::utils::validation::birthdate"))]
     pub birthdate: Option<chrono::NaiveDate>,
     pub email_verified: Option<bool>,
     pub period: Option<String>,
@@ -266,6 +330,7 @@ pub struct CreateUser {
     pub is_enable: Option<bool>,
     pub is_suspended: Option<bool>,
     pub suspended_until: Option<chrono::NaiveDateTime>,
+    #[validate(length(max = 1000))]
     pub suspended_reason: Option<String>,
 }
 
@@ -277,7 +342,9 @@ pub struct CreateUser {
     request_body = CreateUser,
     responses(
         (status = 201, description = "ユーザーの作成に成功"),
-        (status = 403, description = "権限なし"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "custom_id または email が既に使用されている", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -286,30 +353,34 @@ pub struct CreateUser {
 pub async fn create_user(
     State(db): State<DbConn>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<CreateUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedJson(payload): ValidatedJson<CreateUser>,
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::USER_CREATE, &db).await?;
 
+    password_policy::check(
+        &db,
+        "password",
+        &payload.password,
+        &UserContext {
+            user_id: None,
+            current_hash: None,
+            custom_id: &payload.custom_id,
+            name: &payload.name,
+        },
+    )
+    .await?;
     let password_hash = password::hash_password(&payload.password);
-    let mut email = payload.email;
-    if email.is_none() {
-        if payload.period.is_some() {
-            email = Some(format!(
-                "{}.{}@uniproject.jp",
-                payload.period.as_ref().unwrap(),
-                payload.custom_id
-            ));
-        } else {
-            email = Some(format!("temp_{}@uniproject.jp", payload.custom_id));
-        }
-    }
+    let email = payload
+        .email
+        .unwrap_or_else(|| default_email(payload.period.as_deref(), &payload.custom_id));
 
     let am = user::ActiveModel {
         id: Set(Ulid::new().to_string()),
         custom_id: Set(payload.custom_id),
         name: Set(payload.name),
-        password_hash: Set(Some(password_hash)),
-        email: Set(email.unwrap()),
+        name_kana: Set(payload.name_kana),
+        password_hash: Set(Some(password_hash.clone())),
+        email: Set(email),
         external_email: Set(payload.external_email),
         birthdate: Set(payload.birthdate),
         email_verified: Set(payload.email_verified.unwrap_or(false)),
@@ -322,18 +393,34 @@ pub async fn create_user(
         is_suspended: Set(Some(payload.is_suspended.unwrap_or(false))),
         suspended_until: Set(payload.suspended_until),
         suspended_reason: Set(payload.suspended_reason),
+        deleted_at: Set(None),
     };
-    let res = am.insert(&db).await.unwrap();
+    let txn = db.begin().await?;
+    let res = am.insert(&txn).await?;
+    password_policy::record_history(&txn, &res.id, &password_hash).await?;
+    AuditEntry::created(AuditAction::UserCreate, &res.id, &res)
+        .record(&txn, &auth_user)
+        .await?;
+    txn.commit().await?;
+    search::index().upsert(&res);
     Ok((StatusCode::CREATED, Json(res)))
 }
 
-#[derive(serde::Deserialize, ToSchema)]
+#[derive(serde::Deserialize, ToSchema, Validate)]
 pub struct PutUser {
+    #[validate(custom(function = "crate::utils::validation::custom_id"))]
     pub custom_id: String,
+    #[validate(length(min = 1, max = 100))]
     pub name: String,
+    /// 名前の読み（ひらがな・カタカナ）。検索に使う
+    #[validate(length(max = 100))]
+    pub name_kana: Option<String>,
     pub password: Option<String>,
+    #[validate(email)]
     pub email: Option<String>,
+    #[validate(email)]
     pub external_email: String,
+    #[validate(custom(function = "crate::utils::validation::birthdate"))]
     pub birthdate: Option<chrono::NaiveDate>,
     pub email_verified: Option<bool>,
     pub period: Option<String>,
@@ -342,9 +429,80 @@ pub struct PutUser {
     pub is_enable: Option<bool>,
     pub is_suspended: Option<bool>,
     pub suspended_until: Option<chrono::NaiveDateTime>,
+    #[validate(length(max = 1000))]
     pub suspended_reason: Option<String>,
 }
 
+impl PutUser {
+    /// 現在の値から変更されるフィールド名の一覧（省略したフィールドは変更しない）
+    fn changed_fields(&self, current: &user::Model, email: &str) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.custom_id != current.custom_id {
+            changed.push("custom_id");
+        }
+        if self.name != current.name {
+            changed.push("name");
+        }
+        if self.name_kana.is_some() && self.name_kana != current.name_kana {
+            changed.push("name_kana");
+        }
+        if self.password.is_some() {
+            changed.push("password");
+        }
+        if email != current.email {
+            changed.push("email");
+        }
+        if self.external_email != current.external_email {
+            changed.push("external_email");
+        }
+        if self.birthdate.is_some() && self.birthdate != current.birthdate {
+            changed.push("birthdate");
+        }
+        if self
+            .email_verified
+            .is_some_and(|v| v != current.email_verified)
+        {
+            changed.push("email_verified");
+        }
+        if self.period.is_some() && self.period != current.period {
+            changed.push("period");
+        }
+        if self.joined_at.is_some() && self.joined_at != current.joined_at {
+            changed.push("joined_at");
+        }
+        if self
+            .is_system
+            .is_some_and(|v| v != current.is_system.unwrap_or(false))
+        {
+            changed.push("is_system");
+        }
+        if self
+            .is_enable
+            .is_some_and(|v| v != current.is_enable.unwrap_or(false))
+        {
+            changed.push("is_enable");
+        }
+        if self
+            .is_suspended
+            .is_some_and(|v| v != current.is_suspended.unwrap_or(false))
+        {
+            changed.push("is_suspended");
+        }
+        if self.suspended_until.is_some() && self.suspended_until != current.suspended_until {
+            changed.push("suspended_until");
+        }
+        if self.suspended_reason.is_some() && self.suspended_reason != current.suspended_reason {
+            changed.push("suspended_reason");
+        }
+        changed
+    }
+}
+
+/// ユーザーを全体更新するための関数
+///
+/// > 書き換え可能なフィールドの制限は PATCH と同じです。
+/// > 現在の値と異なるフィールドのみが変更とみなされます。
+/// > 省略可能なフィールド（`email` など）を省略した場合は現在の値のままです。
 #[utoipa::path(
     put,
     path = "/users/{id}",
@@ -355,8 +513,10 @@ pub struct PutUser {
     request_body = PutUser,
     responses(
         (status = 200, description = "ユーザーの更新に成功"),
-        (status = 404, description = "ユーザーが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし、または変更が許可されていないフィールドを含む", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "custom_id または email が既に使用されている", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -366,64 +526,114 @@ pub async fn put_user(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<PutUser>,
-) -> Result<impl IntoResponse, StatusCode> {
-    permission_check::require_permission_or_self(&auth_user, Permission::USER_UPDATE, &id, &db)
-        .await?;
-    let password_hash = if let Some(password) = payload.password {
-        password::hash_password(&password)
-    } else {
-        // 既存のパスワードハッシュを保持する
-        let found = user::Entity::find_by_id(id.clone()).one(&db).await.unwrap();
-        if let Some(user) = found {
-            user.password_hash.unwrap_or_default()
-        } else {
-            "".to_string()
-        }
+    ValidatedJson(payload): ValidatedJson<PutUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    let principal = UpdatePrincipal::resolve(&auth_user, &id, &db).await?;
+
+    let found = user::Entity::find_active_by_id(id).one(&db).await?;
+    let Some(user) = found else {
+        return Err(ApiError::NotFound("ユーザー"));
     };
-    let mut email = payload.email;
-    if email.is_none() {
-        if payload.period.is_some() {
-            email = Some(format!(
-                "{}.{}@uniproject.jp",
-                payload.period.as_ref().unwrap(),
-                payload.custom_id
-            ));
-        } else {
-            email = Some(format!("temp_{}@uniproject.jp", payload.custom_id));
-        }
+
+    // 省略した場合は現在のアドレスのまま
+    let email = payload.email.clone().unwrap_or_else(|| user.email.clone());
+    principal.check(&payload.changed_fields(&user, &email))?;
+    if let Some(ref password) = payload.password {
+        password_policy::check(
+            &db,
+            "password",
+            password,
+            &UserContext {
+                user_id: Some(&user.id),
+                current_hash: user.password_hash.as_deref(),
+                custom_id: &payload.custom_id,
+                name: &payload.name,
+            },
+        )
+        .await?;
     }
 
-    let found = user::Entity::find_by_id(id).one(&db).await.unwrap();
-    if let Some(user) = found {
-        let mut am: user::ActiveModel = user.into();
-        am.name = Set(payload.name);
-        am.email = Set(email.unwrap());
+    let was_suspended = user.is_suspended.unwrap_or(false);
+    let mut pending_changes = Vec::new();
+    let mut am: user::ActiveModel = user.clone().into();
+    am.custom_id = Set(payload.custom_id);
+    am.name = Set(payload.name);
+    if payload.name_kana.is_some() {
+        am.name_kana = Set(payload.name_kana);
+    }
+    am.email = Set(email.clone());
+    // 本人による外部メールアドレスの変更は確認を経てから切り替える
+    if payload.external_email != user.external_email && principal == UpdatePrincipal::SelfService {
+        pending_changes.push((EmailField::ExternalEmail, payload.external_email));
+    } else {
         am.external_email = Set(payload.external_email);
+    }
+    if payload.birthdate.is_some() {
         am.birthdate = Set(payload.birthdate);
-        am.email_verified = Set(payload.email_verified.unwrap_or(false));
+    }
+    if let Some(email_verified) = payload.email_verified {
+        am.email_verified = Set(email_verified);
+    } else if email != user.email {
+        // アドレスが変わった場合、明示的な指定がなければ未検証に戻す
+        am.email_verified = Set(false);
+    }
+    if payload.period.is_some() {
         am.period = Set(payload.period);
-        am.password_hash = Set(Some(password_hash));
+    }
+    let new_password_hash = payload.password.map(|p| password::hash_password(&p));
+    if let Some(ref hash) = new_password_hash {
+        am.password_hash = Set(Some(hash.clone()));
+    }
+    if payload.joined_at.is_some() {
         am.joined_at = Set(payload.joined_at);
-        am.is_system = Set(Some(payload.is_system.unwrap_or(false)));
-        am.is_enable = Set(Some(payload.is_enable.unwrap_or(false)));
-        am.updated_at = Set(Some(Utc::now().naive_utc()));
+    }
+    if payload.is_system.is_some() {
+        am.is_system = Set(payload.is_system);
+    }
+    if payload.is_enable.is_some() {
+        am.is_enable = Set(payload.is_enable);
+    }
+    am.updated_at = Set(Some(Utc::now().naive_utc()));
+    if payload.suspended_until.is_some() {
         am.suspended_until = Set(payload.suspended_until);
+    }
+    if payload.suspended_reason.is_some() {
         am.suspended_reason = Set(payload.suspended_reason);
-        am.is_suspended = Set(Some(payload.is_suspended.unwrap_or(false)));
-        am.joined_at = Set(payload.joined_at);
-        let res = am.update(&db).await.unwrap();
-        return Ok((StatusCode::OK, Json(Some(res))));
     }
-    Err(StatusCode::NOT_FOUND)
+    if payload.is_suspended.is_some() {
+        am.is_suspended = Set(payload.is_suspended);
+    }
+    let txn = db.begin().await?;
+    let res = am.update(&txn).await?;
+    if let Some(ref hash) = new_password_hash {
+        password_policy::record_history(&txn, &res.id, hash).await?;
+    }
+    AuditEntry::updated(AuditAction::UserUpdate, &res.id, &user, &res)
+        .record(&txn, &auth_user)
+        .await?;
+    txn.commit().await?;
+    search::index().upsert(&res);
+    if !was_suspended && res.is_suspended.unwrap_or(false) {
+        notify_suspension(&db, &res).await?;
+    }
+    for (field, new_address) in pending_changes {
+        users_sub::email_change::request_email_change(&db, &res, field, &new_address).await?;
+    }
+    Ok((StatusCode::OK, Json(Some(res))))
 }
 
-#[derive(serde::Deserialize, ToSchema)]
+#[derive(serde::Deserialize, ToSchema, Validate)]
 pub struct UpdateUser {
+    #[validate(custom(function = "crate::utils::validation::custom_id"))]
     pub custom_id: Option<String>,
+    #[validate(length(min = 1, max = 100))]
     pub name: Option<String>,
+    #[validate(length(max = 100))]
+    pub name_kana: Option<String>,
     pub password_hash: Option<String>,
+    #[validate(email)]
     pub external_email: Option<String>,
+    #[validate(custom(function = "crate::utils::validation::birthdate"))]
     pub birthdate: Option<chrono::NaiveDate>,
     pub email_verified: Option<bool>,
     pub period: Option<String>,
@@ -432,16 +642,91 @@ pub struct UpdateUser {
     pub is_enable: Option<bool>,
     pub is_suspended: Option<bool>,
     pub suspended_until: Option<chrono::NaiveDateTime>,
+    #[validate(length(max = 1000))]
     pub suspended_reason: Option<String>,
+    #[validate(email)]
     pub email: Option<String>,
 }
 
+impl UpdateUser {
+    /// 現在の値から変更されるフィールド名の一覧（指定されていても値が同じものは含めない）
+    fn changed_fields(&self, current: &user::Model) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self
+            .custom_id
+            .as_ref()
+            .is_some_and(|v| *v != current.custom_id)
+        {
+            changed.push("custom_id");
+        }
+        if self.name.as_ref().is_some_and(|v| *v != current.name) {
+            changed.push("name");
+        }
+        if self.name_kana.is_some() && self.name_kana != current.name_kana {
+            changed.push("name_kana");
+        }
+        if self.password_hash.is_some() {
+            changed.push("password_hash");
+        }
+        if self
+            .external_email
+            .as_ref()
+            .is_some_and(|v| *v != current.external_email)
+        {
+            changed.push("external_email");
+        }
+        if self.birthdate.is_some() && self.birthdate != current.birthdate {
+            changed.push("birthdate");
+        }
+        if self
+            .email_verified
+            .is_some_and(|v| v != current.email_verified)
+        {
+            changed.push("email_verified");
+        }
+        if self.period.is_some() && self.period != current.period {
+            changed.push("period");
+        }
+        if self.joined_at.is_some() && self.joined_at != current.joined_at {
+            changed.push("joined_at");
+        }
+        if self.is_system.is_some() && self.is_system != current.is_system {
+            changed.push("is_system");
+        }
+        if self.is_enable.is_some() && self.is_enable != current.is_enable {
+            changed.push("is_enable");
+        }
+        if self.is_suspended.is_some() && self.is_suspended != current.is_suspended {
+            changed.push("is_suspended");
+        }
+        if self.suspended_until.is_some() && self.suspended_until != current.suspended_until {
+            changed.push("suspended_until");
+        }
+        if self.suspended_reason.is_some() && self.suspended_reason != current.suspended_reason {
+            changed.push("suspended_reason");
+        }
+        if self.email.as_ref().is_some_and(|v| *v != current.email) {
+            changed.push("email");
+        }
+        changed
+    }
+}
+
 /// ユーザーを差分アップデートするための関数
 ///
 /// > このエンドポイントはOAuthの**アクセストークンでアクセス可能**です。
 /// > ただし、システムのユーザーではない場合は、以下のフィールドのみ書き換え可能です。
 /// > - name
+/// > - name_kana
 /// > - external_email
+///
+/// > 許可されていないフィールドを変更しようとした場合は 403 とともに
+/// > `forbidden_fields` にフィールド名の一覧を返します。
+/// > また `is_system` はシステム（API キー）からのみ変更できます。
+///
+/// > [!NOTE]
+/// > 本人が `external_email` を変更した場合、新しいアドレスへ確認メールが送られ、
+/// > 確認が済むまでアドレスは切り替わりません。
 #[utoipa::path(
     patch,
     path = "/users/{id}",
@@ -452,8 +737,10 @@ pub struct UpdateUser {
     request_body = UpdateUser,
     responses(
         (status = 200, description = "ユーザーの部分更新に成功"),
-        (status = 404, description = "ユーザーが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし、または変更が許可されていないフィールドを含む", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "custom_id または email が既に使用されている", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -463,63 +750,110 @@ pub async fn patch_update_user(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<UpdateUser>,
-) -> Result<impl IntoResponse, StatusCode> {
-    permission_check::require_permission_or_self(&auth_user, Permission::USER_UPDATE, &id, &db)
-        .await?;
-    let found = user::Entity::find_by_id(id).one(&db).await.unwrap();
-    if let Some(user) = found {
-        let mut am: user::ActiveModel = user.into();
-        if let Some(custom_id) = payload.custom_id {
-            am.custom_id = Set(custom_id);
-        }
-        if let Some(name) = payload.name {
-            am.name = Set(name);
-        }
-        if let Some(external_email) = payload.external_email {
+    ValidatedJson(payload): ValidatedJson<UpdateUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    let principal = UpdatePrincipal::resolve(&auth_user, &id, &db).await?;
+
+    let found = user::Entity::find_active_by_id(id).one(&db).await?;
+    let Some(user) = found else {
+        return Err(ApiError::NotFound("ユーザー"));
+    };
+
+    principal.check(&payload.changed_fields(&user))?;
+
+    let was_suspended = user.is_suspended.unwrap_or(false);
+    let mut pending_changes = Vec::new();
+    let mut am: user::ActiveModel = user.clone().into();
+    if let Some(custom_id) = payload.custom_id {
+        am.custom_id = Set(custom_id);
+    }
+    if let Some(name) = payload.name {
+        am.name = Set(name);
+    }
+    if let Some(name_kana) = payload.name_kana {
+        am.name_kana = Set(Some(name_kana));
+    }
+    if let Some(external_email) = payload.external_email {
+        // 本人による外部メールアドレスの変更は確認を経てから切り替える
+        if external_email != user.external_email && principal == UpdatePrincipal::SelfService {
+            pending_changes.push((EmailField::ExternalEmail, external_email));
+        } else {
             am.external_email = Set(external_email);
         }
-        if let Some(birthdate) = payload.birthdate {
-            am.birthdate = Set(Some(birthdate));
-        }
-        if let Some(email_verified) = payload.email_verified {
-            am.email_verified = Set(email_verified);
-        }
-        if let Some(password_hash) = payload.password_hash {
-            am.password_hash = Set(Some(password_hash));
-        }
-        if let Some(period) = payload.period {
-            am.period = Set(Some(period));
-        }
-        if let Some(joined_at) = payload.joined_at {
-            am.joined_at = Set(Some(joined_at));
-        }
-        if let Some(is_system) = payload.is_system {
-            am.is_system = Set(Some(is_system));
-        }
-        if let Some(is_enable) = payload.is_enable {
-            am.is_enable = Set(Some(is_enable));
-        }
-        if let Some(is_suspended) = payload.is_suspended {
-            am.is_suspended = Set(Some(is_suspended));
-        }
-        if let Some(suspended_until) = payload.suspended_until {
-            am.suspended_until = Set(Some(suspended_until));
-        }
-        if let Some(suspended_reason) = payload.suspended_reason {
-            am.suspended_reason = Set(Some(suspended_reason));
-        }
-        if let Some(email) = payload.email {
-            am.email = Set(email);
+    }
+    if let Some(birthdate) = payload.birthdate {
+        am.birthdate = Set(Some(birthdate));
+    }
+    if let Some(email_verified) = payload.email_verified {
+        am.email_verified = Set(email_verified);
+    }
+    if let Some(password_hash) = payload.password_hash {
+        am.password_hash = Set(Some(password_hash));
+    }
+    if let Some(period) = payload.period {
+        am.period = Set(Some(period));
+    }
+    if let Some(joined_at) = payload.joined_at {
+        am.joined_at = Set(Some(joined_at));
+    }
+    if let Some(is_system) = payload.is_system {
+        am.is_system = Set(Some(is_system));
+    }
+    if let Some(is_enable) = payload.is_enable {
+        am.is_enable = Set(Some(is_enable));
+    }
+    if let Some(is_suspended) = payload.is_suspended {
+        am.is_suspended = Set(Some(is_suspended));
+    }
+    if let Some(suspended_until) = payload.suspended_until {
+        am.suspended_until = Set(Some(suspended_until));
+    }
+    if let Some(suspended_reason) = payload.suspended_reason {
+        am.suspended_reason = Set(Some(suspended_reason));
+    }
+    if let Some(email) = payload.email {
+        // アドレスが変わった場合、明示的な指定がなければ未検証に戻す
+        if email != user.email && payload.email_verified.is_none() {
+            am.email_verified = Set(false);
         }
-        am.updated_at = Set(Some(Utc::now().naive_utc()));
-        let res = am.update(&db).await.unwrap();
-        return Ok((StatusCode::OK, Json(Some(res))));
+        am.email = Set(email);
     }
-    Err(StatusCode::NOT_FOUND)
+    am.updated_at = Set(Some(Utc::now().naive_utc()));
+    let txn = db.begin().await?;
+    let res = am.update(&txn).await?;
+    AuditEntry::updated(AuditAction::UserUpdate, &res.id, &user, &res)
+        .record(&txn, &auth_user)
+        .await?;
+    txn.commit().await?;
+    search::index().upsert(&res);
+    if !was_suspended && res.is_suspended.unwrap_or(false) {
+        notify_suspension(&db, &res).await?;
+    }
+    for (field, new_address) in pending_changes {
+        users_sub::email_change::request_email_change(&db, &res, field, &new_address).await?;
+    }
+    Ok((StatusCode::OK, Json(Some(res))))
+}
+
+/// アカウント停止を本人の外部メールアドレスへ通知する
+pub async fn notify_suspension(db: &DbConn, user: &user::Model) -> Result<(), ApiError> {
+    let mail = Template::Suspension {
+        name: &user.name,
+        until: user.suspended_until,
+        reason: user.suspended_reason.as_deref(),
+    }
+    .to_mail(&user.external_email, Locale::from_env());
+    mailer::queue::enqueue(db, mail).await?;
+    Ok(())
 }
 
 /// ユーザーを削除するための関数
+/// USER_DELETE 権限が必要です
+///
+/// ユーザーは論理削除され、すべてのエンドポイントから見えなくなります。
+/// セッションは削除し、発行済みのアクセストークン・リフレッシュトークンは失効させます。
+/// 保持期間（`USER_DELETE_RETENTION_DAYS`）内であれば `POST /users/{id}/restore` で復元でき、
+/// 保持期間を過ぎると定期ジョブが関連するデータごと完全に削除します。
 /// > [!IMPORTANT]
 /// > このエンドポイントはOAuthの**アクセストークンでアクセス不可**です
 #[utoipa::path(
@@ -531,8 +865,8 @@ pub async fn patch_update_user(
     ),
     responses(
         (status = 204, description = "ユーザーの削除に成功"),
-        (status = 404, description = "ユーザーが見つからない"),
-        (status = 403, description = "権限なし"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -542,13 +876,44 @@ pub async fn delete_user(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::USER_DELETE, &db).await?;
-    let found = User::find_by_id(id).one(&db).await.unwrap();
-    if let Some(user) = found {
-        let am: user::ActiveModel = user.into();
-        am.delete(&db).await.unwrap();
-        return Ok((StatusCode::NO_CONTENT, Json::<Option<user::Model>>(None)));
-    }
-    Err(StatusCode::NOT_FOUND)
+    let user = User::find_active_by_id(id)
+        .one(&db)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+    let mut am: user::ActiveModel = user.clone().into();
+    am.deleted_at = Set(Some(now));
+    am.updated_at = Set(Some(now));
+    let deleted = am.update(&txn).await?;
+    revoke_access(&txn, &user.id).await?;
+    AuditEntry::updated(AuditAction::UserDelete, &user.id, &user, &deleted)
+        .record(&txn, &auth_user)
+        .await?;
+    txn.commit().await?;
+
+    search::index().remove(&user.id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// ユーザーのセッションを削除し、発行済みのアクセストークン・リフレッシュトークンを失効させる
+pub async fn revoke_access<C: ConnectionTrait>(conn: &C, user_id: &str) -> Result<(), DbErr> {
+    session::Entity::delete_many()
+        .filter(session::Column::UserId.eq(user_id))
+        .exec(conn)
+        .await?;
+    access_tokens::Entity::update_many()
+        .col_expr(access_tokens::Column::Revoked, sea_query::Expr::value(1))
+        .filter(access_tokens::Column::UserId.eq(user_id))
+        .exec(conn)
+        .await?;
+    refresh_tokens::Entity::update_many()
+        .col_expr(refresh_tokens::Column::Revoked, sea_query::Expr::value(1))
+        .filter(refresh_tokens::Column::UserId.eq(user_id))
+        .exec(conn)
+        .await?;
+    Ok(())
 }
@@ -0,0 +1,352 @@
+use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::*};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    constants::permissions::Permission,
+    error::{ApiError, FieldError, ProblemDetails},
+    extract::ValidatedJson,
+    middleware::{auth::AuthUser, permission_check},
+    models::{role, session, user, user_role},
+    routes::{
+        users::notify_suspension,
+        users_sub::search::{self, SearchParams},
+    },
+};
+
+/// 1回の一括操作で対象にできる最大人数
+const MAX_BULK_USERS: usize = 1000;
+
+pub fn routes() -> Router<DbConn> {
+    Router::new().route("/users/bulk", post(bulk_users))
+}
+
+// =======================
+// DTO
+// =======================
+
+/// 一括操作の内容
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BulkAction {
+    /// 無効化する
+    Disable,
+    /// 有効化する
+    Enable,
+    /// 停止する（停止を知らせるメールを送る）
+    Suspend {
+        until: Option<NaiveDateTime>,
+        reason: Option<String>,
+    },
+    /// ロールを付与する
+    AssignRole { role_id: String },
+    /// ロールを剥奪する
+    RemoveRole { role_id: String },
+    /// すべてのセッションを削除する（強制ログアウト）
+    RevokeSessions,
+}
+
+impl BulkAction {
+    fn required_permission(&self) -> Permission {
+        match self {
+            BulkAction::Disable | BulkAction::Enable | BulkAction::Suspend { .. } => {
+                Permission::USER_DISABLE
+            }
+            BulkAction::AssignRole { .. } | BulkAction::RemoveRole { .. } => {
+                Permission::PERMISSION_MANAGE
+            }
+            BulkAction::RevokeSessions => Permission::SESSION_MANAGE,
+        }
+    }
+
+    /// 実行者自身を対象にできない操作か
+    fn excludes_self(&self) -> bool {
+        matches!(
+            self,
+            BulkAction::Disable | BulkAction::Suspend { .. } | BulkAction::RevokeSessions
+        )
+    }
+}
+
+/// 一括操作のリクエスト
+/// 対象は `user_ids` か `search`（`/users/search` と同じ条件）のどちらか一方で指定する.
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct BulkRequest {
+    #[validate(length(min = 1, max = 1000))]
+    pub user_ids: Option<Vec<String>>,
+    /// 例: `{ "filter": "period:2021,is_enable:true" }`
+    pub search: Option<SearchParams>,
+    pub action: BulkAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkStatus {
+    /// 変更した
+    Updated,
+    /// 既にその状態だった
+    Unchanged,
+    /// ユーザーが見つからない
+    NotFound,
+    /// 対象にできない（実行者自身など）
+    Skipped,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkResult {
+    pub user_id: String,
+    pub status: BulkStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkResponse {
+    /// 変更したユーザーの数
+    pub updated: usize,
+    pub results: Vec<BulkResult>,
+}
+
+impl BulkResult {
+    fn new(user_id: impl Into<String>, status: BulkStatus) -> Self {
+        Self {
+            user_id: user_id.into(),
+            status,
+            message: None,
+        }
+    }
+}
+
+/// 対象のユーザーを読み込む（`user_ids` の場合は見つからなかった ID も返す）
+/// `search` は検索と同じく条件でユーザーを探れるため、USER_READ 権限も必要とする.
+async fn resolve_targets(
+    db: &DbConn,
+    auth_user: &AuthUser,
+    request: &mut BulkRequest,
+) -> Result<(Vec<user::Model>, Vec<String>), ApiError> {
+    match (request.user_ids.take(), request.search.as_mut()) {
+        (Some(mut ids), None) => {
+            ids.sort();
+            ids.dedup();
+            let users = user::Entity::find_active()
+                .filter(user::Column::Id.is_in(ids.clone()))
+                .order_by_asc(user::Column::Id)
+                .all(db)
+                .await?;
+            let missing = ids
+                .into_iter()
+                .filter(|id| !users.iter().any(|u| u.id == *id))
+                .collect();
+            Ok((users, missing))
+        }
+        (None, Some(params)) => {
+            permission_check::require_permission(auth_user, Permission::USER_READ, db).await?;
+            // 上限を1件超えて読み、絞り込みが足りないことを検出する
+            let users = search::build_select(params)?
+                .limit(MAX_BULK_USERS as u64 + 1)
+                .all(db)
+                .await?;
+            if users.len() > MAX_BULK_USERS {
+                return Err(ApiError::BadRequest(format!(
+                    "条件に一致するユーザーが {} 人を超えています。条件を絞り込んでください",
+                    MAX_BULK_USERS
+                )));
+            }
+            Ok((users, Vec::new()))
+        }
+        _ => Err(ApiError::Validation(vec![FieldError::new(
+            "user_ids",
+            "required",
+            "user_ids と search のどちらか一方を指定してください",
+        )])),
+    }
+}
+
+/// ユーザーに対して一括で操作を行うための関数
+///
+/// 操作に応じた権限が必要です。
+/// - `disable` / `enable` / `suspend`: USER_DISABLE
+/// - `assign_role` / `remove_role`: PERMISSION_MANAGE
+/// - `revoke_sessions`: SESSION_MANAGE
+/// - `search` で対象を指定する場合は、さらに USER_READ
+///
+/// > [!NOTE]
+/// > すべての変更は1つのトランザクションで行い、途中で失敗した場合は何も変更しません。
+/// > `disable` / `suspend` / `revoke_sessions` は実行者自身には行いません（`skipped`）。
+#[utoipa::path(
+    post,
+    path = "/users/bulk",
+    tag = "users",
+    request_body = BulkRequest,
+    responses(
+        (status = 200, description = "一括操作に成功（ユーザーごとの結果を含む）", body = BulkResponse),
+        (status = 400, description = "search の指定が不正、または対象が多すぎる", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ロールが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "リクエストが多すぎる", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn bulk_users(
+    State(db): State<DbConn>,
+    auth_user: axum::Extension<AuthUser>,
+    ValidatedJson(mut request): ValidatedJson<BulkRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let action = request.action.clone();
+    permission_check::require_permission(&auth_user, action.required_permission(), &db).await?;
+
+    let role = match action {
+        BulkAction::AssignRole { ref role_id } | BulkAction::RemoveRole { ref role_id } => Some(
+            role::Entity::find_by_id(role_id.clone())
+                .one(&db)
+                .await?
+                .ok_or(ApiError::NotFound("ロール"))?,
+        ),
+        _ => None,
+    };
+
+    let (users, missing) = resolve_targets(&db, &auth_user, &mut request).await?;
+
+    let now = Utc::now().naive_utc();
+    let mut results: Vec<BulkResult> = Vec::with_capacity(users.len() + missing.len());
+    let mut suspended: Vec<user::Model> = Vec::new();
+
+    let txn = db.begin().await?;
+    for user in users {
+        if action.excludes_self() && user.id == auth_user.user_id {
+            results.push(BulkResult {
+                message: Some("自分自身は対象にできません".to_string()),
+                ..BulkResult::new(&user.id, BulkStatus::Skipped)
+            });
+            continue;
+        }
+
+        let status = match action {
+            BulkAction::Disable | BulkAction::Enable => {
+                let enable = matches!(action, BulkAction::Enable);
+                if user.is_enable == Some(enable) {
+                    BulkStatus::Unchanged
+                } else {
+                    let mut am: user::ActiveModel = user.clone().into();
+                    am.is_enable = Set(Some(enable));
+                    am.updated_at = Set(Some(now));
+                    let updated = am.update(&txn).await?;
+                    AuditEntry::updated(AuditAction::UserUpdate, &user.id, &user, &updated)
+                        .record(&txn, &auth_user)
+                        .await?;
+                    BulkStatus::Updated
+                }
+            }
+            BulkAction::Suspend { until, ref reason } => {
+                let was_suspended = user.is_suspended.unwrap_or(false);
+                if was_suspended
+                    && user.suspended_until == until
+                    && user.suspended_reason == *reason
+                {
+                    BulkStatus::Unchanged
+                } else {
+                    let mut am: user::ActiveModel = user.clone().into();
+                    am.is_suspended = Set(Some(true));
+                    am.suspended_until = Set(until);
+                    am.suspended_reason = Set(reason.clone());
+                    am.updated_at = Set(Some(now));
+                    let updated = am.update(&txn).await?;
+                    AuditEntry::updated(AuditAction::UserUpdate, &user.id, &user, &updated)
+                        .record(&txn, &auth_user)
+                        .await?;
+                    if !was_suspended {
+                        suspended.push(updated);
+                    }
+                    BulkStatus::Updated
+                }
+            }
+            BulkAction::AssignRole { .. } => {
+                let role = role.as_ref().expect("role is loaded for assign_role");
+                let exists = user_role::Entity::find()
+                    .filter(user_role::Column::UserId.eq(&user.id))
+                    .filter(user_role::Column::RoleId.eq(&role.id))
+                    .count(&txn)
+                    .await?
+                    > 0;
+                if exists {
+                    BulkStatus::Unchanged
+                } else {
+                    user_role::ActiveModel {
+                        user_id: Set(user.id.clone()),
+                        role_id: Set(role.id.clone()),
+                        ..Default::default()
+                    }
+                    .insert(&txn)
+                    .await?;
+                    AuditEntry::new(AuditAction::RoleGrant, &user.id)
+                        .with_after(json!({ "role_id": role.id }))
+                        .record(&txn, &auth_user)
+                        .await?;
+                    BulkStatus::Updated
+                }
+            }
+            BulkAction::RemoveRole { .. } => {
+                let role = role.as_ref().expect("role is loaded for remove_role");
+                let res = user_role::Entity::delete_many()
+                    .filter(user_role::Column::UserId.eq(&user.id))
+                    .filter(user_role::Column::RoleId.eq(&role.id))
+                    .exec(&txn)
+                    .await?;
+                if res.rows_affected == 0 {
+                    BulkStatus::Unchanged
+                } else {
+                    AuditEntry::new(AuditAction::RoleRevoke, &user.id)
+                        .with_before(json!({ "role_id": role.id }))
+                        .record(&txn, &auth_user)
+                        .await?;
+                    BulkStatus::Updated
+                }
+            }
+            BulkAction::RevokeSessions => {
+                let sessions = session::Entity::find()
+                    .filter(session::Column::UserId.eq(&user.id))
+                    .all(&txn)
+                    .await?;
+                if sessions.is_empty() {
+                    BulkStatus::Unchanged
+                } else {
+                    session::Entity::delete_many()
+                        .filter(session::Column::UserId.eq(&user.id))
+                        .exec(&txn)
+                        .await?;
+                    for s in sessions {
+                        AuditEntry::session_deleted(&s)
+                            .record(&txn, &auth_user)
+                            .await?;
+                    }
+                    BulkStatus::Updated
+                }
+            }
+        };
+        results.push(BulkResult::new(user.id, status));
+    }
+    txn.commit().await?;
+
+    for user in suspended {
+        notify_suspension(&db, &user).await?;
+    }
+
+    results.extend(
+        missing
+            .into_iter()
+            .map(|id| BulkResult::new(id, BulkStatus::NotFound)),
+    );
+    let updated = results
+        .iter()
+        .filter(|r| r.status == BulkStatus::Updated)
+        .count();
+    Ok((StatusCode::OK, Json(BulkResponse { updated, results })))
+}
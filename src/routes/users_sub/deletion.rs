@@ -0,0 +1,362 @@
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::*,
+};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    extract::ValidatedJson,
+    lockout,
+    mailer::{self, Locale, Template},
+    middleware::{
+        auth::{AuthUser, ClientInfo},
+        permission_check,
+    },
+    models::{account_deletion_request, user},
+    utils::{password, token},
+};
+
+/// 確認コードの有効期間（時間）
+const CONFIRMATION_TTL_HOURS: i64 = 24;
+/// 申請を確定してから削除するまでの猶予期間（日）
+const COOLING_OFF_DAYS: i64 = 14;
+
+// =======================
+// DTO
+// =======================
+
+#[derive(Serialize, ToSchema)]
+pub struct DeletionRequestResponse {
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: NaiveDateTime,
+    /// 確認コードの有効期限
+    #[schema(value_type = String, format = "date-time")]
+    pub expires_at: NaiveDateTime,
+    /// 確認した日時（未確認の場合は `null`）
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub confirmed_at: Option<NaiveDateTime>,
+    /// 削除する予定の日時（未確認の場合は `null`）
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub scheduled_at: Option<NaiveDateTime>,
+}
+
+impl From<account_deletion_request::Model> for DeletionRequestResponse {
+    fn from(model: account_deletion_request::Model) -> Self {
+        Self {
+            created_at: model.created_at,
+            expires_at: model.expires_at,
+            confirmed_at: model.confirmed_at,
+            scheduled_at: model.scheduled_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct CreateDeletionRequest {
+    /// 現在のパスワード（本人確認のため）
+    #[validate(length(min = 1))]
+    pub password: String,
+}
+
+pub fn routes() -> Router<DbConn> {
+    Router::new()
+        .route(
+            "/users/{id}/deletion",
+            get(get_deletion_request)
+                .post(post_deletion_request)
+                .delete(delete_deletion_request),
+        )
+        .route("/users/deletion/{code}/confirm", post(confirm_deletion))
+        .route("/users/deletion/{code}/cancel", post(cancel_deletion))
+}
+
+fn not_found() -> ApiError {
+    ApiError::NotFound("アカウント削除の申請")
+}
+
+/// アカウント削除の申請を取得するための関数
+#[utoipa::path(
+    get,
+    path = "/users/{id}/deletion",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    responses(
+        (status = 200, description = "申請の取得に成功", body = DeletionRequestResponse),
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "申請がない", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn get_deletion_request(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission_or_self(&auth_user, Permission::USER_READ, &id, &db)
+        .await?;
+
+    let request = account_deletion_request::Entity::find()
+        .filter(account_deletion_request::Column::UserId.eq(id))
+        .one(&db)
+        .await?
+        .ok_or_else(not_found)?;
+    Ok((StatusCode::OK, Json(DeletionRequestResponse::from(request))))
+}
+
+/// 自分のアカウントの削除を申請するための関数
+///
+/// パスワードを再入力して本人確認を行い、外部メールアドレスへ確認リンクを送ります。
+/// 確認リンクを開くと申請が確定し、猶予期間（14日）の後にアカウントと関連するデータを完全に削除します。
+/// 猶予期間中は申請を取り消せます。
+///
+/// > [!NOTE]
+/// > 申請し直すと以前の申請は取り消されます。パスワードを設定していないユーザーとシステムユーザーは申請できません。
+#[utoipa::path(
+    post,
+    path = "/users/{id}/deletion",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    request_body = CreateDeletionRequest,
+    responses(
+        (status = 202, description = "申請を受け付け、確認メールを送信した", body = DeletionRequestResponse),
+        (status = 401, description = "パスワードが不正確", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "本人以外、またはシステムユーザー", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "失敗が続いたため一時的にロックされている", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn post_deletion_request(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+    ValidatedJson(payload): ValidatedJson<CreateDeletionRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    // 本人のみ
+    if auth_user.user_id != id {
+        return Err(ApiError::Forbidden);
+    }
+
+    let subjects = lockout::subjects(&id, &auth_user.client);
+    lockout::lockout().check(&subjects).await?;
+
+    let user = user::Entity::find_active_by_id(&id)
+        .one(&db)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+    if user.is_system.unwrap_or(false) {
+        return Err(ApiError::Forbidden);
+    }
+
+    let password_matches = user
+        .password_hash
+        .as_deref()
+        .map(|h| password::verify_password(&payload.password, h))
+        .unwrap_or(false);
+    if !password_matches {
+        lockout::lockout()
+            .record_failure(&db, &subjects, &auth_user.user_id, &auth_user.client)
+            .await?;
+        return Err(ApiError::Unauthorized);
+    }
+    lockout::lockout().record_success(&id).await?;
+
+    let confirmation_code = token::generate_token();
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+    account_deletion_request::Entity::delete_many()
+        .filter(account_deletion_request::Column::UserId.eq(&user.id))
+        .exec(&txn)
+        .await?;
+    let request = account_deletion_request::ActiveModel {
+        user_id: Set(user.id.clone()),
+        confirmation_code_hash: Set(token::hash_token(&confirmation_code)),
+        cancel_code_hash: Set(None),
+        created_at: Set(now),
+        expires_at: Set(now + chrono::Duration::hours(CONFIRMATION_TTL_HOURS)),
+        confirmed_at: Set(None),
+        scheduled_at: Set(None),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    AuditEntry::new(AuditAction::DeletionRequest, &user.id)
+        .record(&txn, &auth_user)
+        .await?;
+    txn.commit().await?;
+
+    let url = mailer::public_url(&format!("/account/deletion?code={}", confirmation_code));
+    let mail = Template::AccountDeletionConfirmation {
+        name: &user.name,
+        url: &url,
+        expires_at: request.expires_at,
+        cooling_off_days: COOLING_OFF_DAYS,
+    }
+    .to_mail(&user.external_email, Locale::from_env());
+    mailer::queue::enqueue(&db, mail).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(DeletionRequestResponse::from(request)),
+    ))
+}
+
+/// アカウント削除の申請を取り消すための関数
+/// 本人、または USER_DELETE 権限が必要です
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/deletion",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    responses(
+        (status = 204, description = "申請の取り消しに成功"),
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn delete_deletion_request(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission_or_self(&auth_user, Permission::USER_DELETE, &id, &db)
+        .await?;
+
+    let txn = db.begin().await?;
+    let res = account_deletion_request::Entity::delete_many()
+        .filter(account_deletion_request::Column::UserId.eq(&id))
+        .exec(&txn)
+        .await?;
+    if res.rows_affected > 0 {
+        AuditEntry::new(AuditAction::DeletionCancel, &id)
+            .record(&txn, &auth_user)
+            .await?;
+    }
+    txn.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 確認コードでアカウント削除の申請を確定するための関数
+///
+/// 確認コード自体が資格情報となるため、ログインしていなくても呼び出せます。
+/// 確定すると削除の予定日時と取り消しリンクをメールで送ります。
+#[utoipa::path(
+    post,
+    path = "/users/deletion/{code}/confirm",
+    tag = "users",
+    params(
+        ("code" = String, Path, description = "確認コード")
+    ),
+    responses(
+        (status = 200, description = "申請の確定に成功", body = DeletionRequestResponse),
+        (status = 404, description = "申請が見つからない、確定済み、または期限切れ", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn confirm_deletion(
+    State(db): State<DbConn>,
+    Extension(client): Extension<ClientInfo>,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let now = Utc::now().naive_utc();
+    let request = account_deletion_request::Entity::find()
+        .filter(account_deletion_request::Column::ConfirmationCodeHash.eq(token::hash_token(&code)))
+        .filter(account_deletion_request::Column::ConfirmedAt.is_null())
+        .filter(account_deletion_request::Column::ExpiresAt.gt(now))
+        .one(&db)
+        .await?
+        .ok_or_else(not_found)?;
+    let user = user::Entity::find_active_by_id(&request.user_id)
+        .one(&db)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+
+    let cancel_code = token::generate_token();
+    let scheduled_at = now + chrono::Duration::days(COOLING_OFF_DAYS);
+    let mut am: account_deletion_request::ActiveModel = request.into();
+    am.cancel_code_hash = Set(Some(token::hash_token(&cancel_code)));
+    am.confirmed_at = Set(Some(now));
+    am.scheduled_at = Set(Some(scheduled_at));
+    let txn = db.begin().await?;
+    let confirmed = am.update(&txn).await?;
+    AuditEntry::new(AuditAction::DeletionConfirm, &user.id)
+        .with_after(json!({ "scheduled_at": scheduled_at }))
+        .record_as(&txn, &user.id, &client)
+        .await?;
+    txn.commit().await?;
+
+    let cancel_url = mailer::public_url(&format!("/account/deletion/cancel?code={}", cancel_code));
+    let mail = Template::AccountDeletionScheduled {
+        name: &user.name,
+        scheduled_at,
+        cancel_url: &cancel_url,
+    }
+    .to_mail(&user.external_email, Locale::from_env());
+    mailer::queue::enqueue(&db, mail).await?;
+    Ok((
+        StatusCode::OK,
+        Json(DeletionRequestResponse::from(confirmed)),
+    ))
+}
+
+/// 取り消しコードでアカウント削除の申請を取り消すための関数
+///
+/// 取り消しコード自体が資格情報となるため、ログインしていなくても呼び出せます。
+#[utoipa::path(
+    post,
+    path = "/users/deletion/{code}/cancel",
+    tag = "users",
+    params(
+        ("code" = String, Path, description = "取り消しコード")
+    ),
+    responses(
+        (status = 204, description = "申請の取り消しに成功"),
+        (status = 404, description = "申請が見つからない", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn cancel_deletion(
+    State(db): State<DbConn>,
+    Extension(client): Extension<ClientInfo>,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let request = account_deletion_request::Entity::find()
+        .filter(account_deletion_request::Column::CancelCodeHash.eq(token::hash_token(&code)))
+        .one(&db)
+        .await?
+        .ok_or_else(not_found)?;
+
+    let txn = db.begin().await?;
+    let res = account_deletion_request::Entity::delete_by_id(request.id)
+        .exec(&txn)
+        .await?;
+    if res.rows_affected == 0 {
+        return Err(not_found());
+    }
+    AuditEntry::new(AuditAction::DeletionCancel, &request.user_id)
+        .record_as(&txn, &request.user_id, &client)
+        .await?;
+    txn.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -8,9 +8,13 @@ use axum::{
 use sea_orm::*;
 use serde::Serialize;
 use utoipa::ToSchema;
+use validator::Validate;
 
 use crate::{
+    audit::{AuditAction, AuditEntry},
     constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    extract::ValidatedJson,
     middleware::{auth::AuthUser, permission_check},
     models::discord::{self, Entity as Discord},
     models::user::{self, Entity as User},
@@ -55,8 +59,8 @@ pub fn routes() -> Router<DbConn> {
     ),
     responses(
         (status = 200, description = "Discordアカウント一覧取得成功"),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "ユーザーが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -66,28 +70,29 @@ pub async fn get_all_discord(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission_or_self(&auth_user, Permission::USER_READ, &id, &db)
         .await?;
-    let user = User::find_by_id(id).one(&db).await.unwrap();
+    let user = User::find_active_by_id(id).one(&db).await?;
     if let Some(user) = user {
         let discord_accounts = user
             .find_related(crate::models::discord::Entity)
             .all(&db)
-            .await
-            .unwrap();
+            .await?;
         let responses: Vec<DiscordResponse> = discord_accounts
             .into_iter()
             .map(DiscordResponse::from)
             .collect();
-        return Ok((StatusCode::OK, Json(ApiResponse { data: responses })));
+        return Ok((StatusCode::OK, Json(ApiResponse::new(responses))));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ユーザー"))
 }
 
-#[derive(serde::Deserialize, ToSchema)]
+#[derive(serde::Deserialize, ToSchema, Validate)]
 pub struct CreateDiscord {
+    #[validate(custom(function = "crate::utils::validation::discord_snowflake"))]
     pub discord_id: String,
+    #[validate(length(min = 2, max = 32))]
     pub custom_id: String,
 }
 
@@ -102,8 +107,10 @@ pub struct CreateDiscord {
     request_body = CreateDiscord,
     responses(
         (status = 201, description = "Discordアカウント紐づけ成功", body = DiscordResponse),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "ユーザーが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Discordアカウントが既に紐付けられている", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -113,11 +120,11 @@ pub async fn put_discord(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<CreateDiscord>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedJson(payload): ValidatedJson<CreateDiscord>,
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission_or_self(&auth_user, Permission::USER_UPDATE, &id, &db)
         .await?;
-    let found = user::Entity::find_by_id(id).one(&db).await.unwrap();
+    let found = user::Entity::find_active_by_id(id).one(&db).await?;
     if let Some(user) = found {
         let am = discord::ActiveModel {
             discord_id: Set(payload.discord_id),
@@ -125,10 +132,15 @@ pub async fn put_discord(
             user_id: Set(user.id),
             ..Default::default()
         };
-        let res = am.insert(&db).await.unwrap();
+        let txn = db.begin().await?;
+        let res = am.insert(&txn).await?;
+        AuditEntry::created(AuditAction::DiscordLink, &res.user_id, &res)
+            .record(&txn, &auth_user)
+            .await?;
+        txn.commit().await?;
         return Ok((StatusCode::CREATED, Json(DiscordResponse::from(res))));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ユーザー"))
 }
 
 /// ユーザーのDiscordアカウントの紐付けを解除するための関数
@@ -142,8 +154,8 @@ pub async fn put_discord(
     ),
     responses(
         (status = 204, description = "Discordアカウント紐づけ解除成功"),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "ユーザーまたはDiscordアカウントが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーまたはDiscordアカウントが見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -153,23 +165,31 @@ pub async fn delete_discord(
     State(db): State<DbConn>,
     Path((id, discord_id)): Path<(String, String)>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission_or_self(&auth_user, Permission::USER_UPDATE, &id, &db)
         .await?;
-    let found = User::find_by_id(id).one(&db).await.unwrap();
+    let found = User::find_active_by_id(id).one(&db).await?;
     if let Some(user) = found {
         let discord_account = Discord::find()
             .filter(discord::Column::UserId.eq(user.id))
             .filter(discord::Column::DiscordId.eq(discord_id))
             .one(&db)
-            .await
-            .unwrap();
+            .await?;
         if let Some(discord_account) = discord_account {
-            let am: discord::ActiveModel = discord_account.into();
-            am.delete(&db).await.unwrap();
+            let txn = db.begin().await?;
+            let am: discord::ActiveModel = discord_account.clone().into();
+            am.delete(&txn).await?;
+            AuditEntry::deleted(
+                AuditAction::DiscordUnlink,
+                &discord_account.user_id,
+                &discord_account,
+            )
+            .record(&txn, &auth_user)
+            .await?;
+            txn.commit().await?;
             return Ok((StatusCode::NO_CONTENT, Json::<Option<discord::Model>>(None)));
         }
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::NotFound("Discord連携"));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ユーザー"))
 }
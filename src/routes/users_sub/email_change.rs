@@ -0,0 +1,476 @@
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::*,
+};
+use chrono::Utc;
+use sea_orm::{sea_query::Expr, *};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    constants::permissions::Permission,
+    error::{ApiError, FieldError, ProblemDetails},
+    extract::ValidatedJson,
+    mailer::{self, Locale, Template},
+    middleware::{
+        auth::{AuthUser, ClientInfo},
+        permission_check,
+    },
+    models::{email_change_request, email_verification, user},
+    routes::{
+        common_dtos::array_dto::ApiResponse, users, users_sub::update_policy::UpdatePrincipal,
+    },
+    search,
+    utils::token,
+};
+
+/// 変更リクエストの有効期間（時間）
+const EMAIL_CHANGE_TTL_HOURS: i64 = 24;
+/// 変更の確定後、旧アドレスからの取り消しを受け付ける期間（日）
+const EMAIL_CHANGE_REVERT_DAYS: i64 = 7;
+
+// =======================
+// DTO
+// =======================
+
+/// 変更対象のメールアドレス
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailField {
+    Email,
+    ExternalEmail,
+}
+
+impl EmailField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailField::Email => "email",
+            EmailField::ExternalEmail => "external_email",
+        }
+    }
+
+    fn current<'a>(&self, user: &'a user::Model) -> &'a str {
+        match self {
+            EmailField::Email => &user.email,
+            EmailField::ExternalEmail => &user.external_email,
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "email" => Some(EmailField::Email),
+            "external_email" => Some(EmailField::ExternalEmail),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EmailChangeResponse {
+    pub field: String,
+    pub new_address: String,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: Option<chrono::NaiveDateTime>,
+    #[schema(value_type = String, format = "date-time")]
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+impl From<email_change_request::Model> for EmailChangeResponse {
+    fn from(model: email_change_request::Model) -> Self {
+        Self {
+            field: model.field,
+            new_address: model.new_address,
+            created_at: model.created_at,
+            expires_at: model.expires_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct CreateEmailChange {
+    pub field: EmailField,
+    #[validate(email)]
+    pub new_address: String,
+}
+
+pub fn routes() -> Router<DbConn> {
+    Router::new()
+        .route(
+            "/users/{id}/email_change",
+            get(get_email_changes)
+                .post(post_email_change)
+                .delete(delete_email_changes),
+        )
+        .route(
+            "/users/email_change/{code}/confirm",
+            post(confirm_email_change),
+        )
+        .route(
+            "/users/email_change/{code}/cancel",
+            post(cancel_email_change),
+        )
+}
+
+/// メールアドレスの変更リクエストを作成し、確認メールと通知メールを送る
+///
+/// 新しいアドレスには確認リンクを、現在のアドレスには取り消しリンクを送信する.
+/// アドレスは確認リンクが開かれるまで切り替わらない.
+/// 取り消しリンクは確定後も `EMAIL_CHANGE_REVERT_DAYS` 日の間有効で、開くと旧アドレスに戻す.
+pub async fn request_email_change(
+    db: &DbConn,
+    user: &user::Model,
+    field: EmailField,
+    new_address: &str,
+) -> Result<email_change_request::Model, ApiError> {
+    // 同じ項目の未完了リクエストは置き換える（確定済みの取り消し用の記録は残す）
+    email_change_request::Entity::delete_many()
+        .filter(email_change_request::Column::UserId.eq(&user.id))
+        .filter(email_change_request::Column::Field.eq(field.as_str()))
+        .filter(email_change_request::Column::ConfirmedAt.is_null())
+        .exec(db)
+        .await?;
+
+    let verification_code = token::generate_token();
+    let cancel_code = token::generate_token();
+    let now = Utc::now();
+    let am = email_change_request::ActiveModel {
+        user_id: Set(user.id.clone()),
+        field: Set(field.as_str().to_string()),
+        old_address: Set(field.current(user).to_string()),
+        new_address: Set(new_address.to_string()),
+        verification_code_hash: Set(token::hash_token(&verification_code)),
+        cancel_code_hash: Set(token::hash_token(&cancel_code)),
+        created_at: Set(Some(now.naive_utc())),
+        expires_at: Set((now + chrono::Duration::hours(EMAIL_CHANGE_TTL_HOURS)).naive_utc()),
+        confirmed_at: Set(None),
+        ..Default::default()
+    };
+    let res = am.insert(db).await?;
+
+    let locale = Locale::from_env();
+    let url = mailer::public_url(&format!("/email/change?code={}", verification_code));
+    let verification = Template::EmailChangeVerification {
+        name: &user.name,
+        new_address,
+        url: &url,
+        expires_at: res.expires_at,
+    }
+    .to_mail(new_address, locale);
+    let cancel_url = mailer::public_url(&format!("/email/change/cancel?code={}", cancel_code));
+    let notice = Template::EmailChangeNotice {
+        name: &user.name,
+        new_address,
+        cancel_url: &cancel_url,
+    }
+    .to_mail(field.current(user), locale);
+    for mail in [verification, notice] {
+        mailer::queue::enqueue(db, mail).await?;
+    }
+
+    Ok(res)
+}
+
+/// 未完了のメールアドレス変更リクエストを取得するための関数
+#[utoipa::path(
+    get,
+    path = "/users/{id}/email_change",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    responses(
+        (status = 200, description = "変更リクエスト一覧取得成功", body = ApiResponse<Vec<EmailChangeResponse>>),
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn get_email_changes(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission_or_self(&auth_user, Permission::USER_READ, &id, &db)
+        .await?;
+
+    let requests = email_change_request::Entity::find()
+        .filter(email_change_request::Column::UserId.eq(id))
+        .filter(email_change_request::Column::ConfirmedAt.is_null())
+        .filter(email_change_request::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+        .all(&db)
+        .await?;
+    let data: Vec<EmailChangeResponse> = requests
+        .into_iter()
+        .map(EmailChangeResponse::from)
+        .collect();
+
+    Ok((StatusCode::OK, Json(ApiResponse::new(data))))
+}
+
+/// メールアドレスの変更をリクエストするための関数
+#[utoipa::path(
+    post,
+    path = "/users/{id}/email_change",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    request_body = CreateEmailChange,
+    responses(
+        (status = 202, description = "変更リクエスト受付", body = EmailChangeResponse),
+        (status = 403, description = "アクセス権限なし、または変更が許可されていないフィールド", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "メールアドレスの形式が不正、または現在と同じ", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn post_email_change(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+    ValidatedJson(payload): ValidatedJson<CreateEmailChange>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Self-only access
+    if auth_user.user_id != id {
+        return Err(ApiError::Forbidden);
+    }
+
+    // 本人が書き換えられないフィールドは確認を経ても変更できない
+    UpdatePrincipal::resolve(&auth_user, &id, &db)
+        .await?
+        .check(&[payload.field.as_str()])?;
+
+    let user = user::Entity::find_active_by_id(id)
+        .one(&db)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+    if payload.new_address == payload.field.current(&user) {
+        return Err(ApiError::Validation(vec![FieldError::new(
+            "new_address",
+            "unchanged",
+            "現在と同じメールアドレスです",
+        )]));
+    }
+
+    let res = request_email_change(&db, &user, payload.field, &payload.new_address).await?;
+    Ok((StatusCode::ACCEPTED, Json(EmailChangeResponse::from(res))))
+}
+
+/// 未完了のメールアドレス変更リクエストを取り消すための関数
+///
+/// 確定済みの変更を旧アドレスから取り消すための記録は削除しません。
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/email_change",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    responses(
+        (status = 204, description = "変更リクエスト取り消し成功"),
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn delete_email_changes(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission_or_self(&auth_user, Permission::USER_UPDATE, &id, &db)
+        .await?;
+
+    email_change_request::Entity::delete_many()
+        .filter(email_change_request::Column::UserId.eq(id))
+        .filter(email_change_request::Column::ConfirmedAt.is_null())
+        .exec(&db)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 新しいアドレスに届いた確認コードでメールアドレスを切り替えるための関数
+///
+/// 確認コード自体が資格情報となるため、ログインしていなくても呼び出せます。
+#[utoipa::path(
+    post,
+    path = "/users/email_change/{code}/confirm",
+    tag = "users",
+    params(
+        ("code" = String, Path, description = "確認コード")
+    ),
+    responses(
+        (status = 204, description = "メールアドレス変更成功"),
+        (status = 404, description = "変更リクエストが見つからない、または期限切れ", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "新しいアドレスが既に使用されている", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn confirm_email_change(
+    State(db): State<DbConn>,
+    Extension(client): Extension<ClientInfo>,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let txn = db.begin().await?;
+    let now = Utc::now().naive_utc();
+
+    let request = email_change_request::Entity::find()
+        .filter(email_change_request::Column::VerificationCodeHash.eq(token::hash_token(&code)))
+        .filter(email_change_request::Column::ConfirmedAt.is_null())
+        .filter(email_change_request::Column::ExpiresAt.gt(now))
+        .one(&txn)
+        .await?
+        .ok_or(ApiError::NotFound("メールアドレス変更リクエスト"))?;
+
+    // 使い捨て。旧アドレスから取り消せるよう、期限を延ばして記録は残す
+    let consumed = email_change_request::Entity::update_many()
+        .col_expr(email_change_request::Column::ConfirmedAt, Expr::value(now))
+        .col_expr(
+            email_change_request::Column::ExpiresAt,
+            Expr::value(now + chrono::Duration::days(EMAIL_CHANGE_REVERT_DAYS)),
+        )
+        .filter(email_change_request::Column::Id.eq(request.id))
+        .filter(email_change_request::Column::ConfirmedAt.is_null())
+        .exec(&txn)
+        .await?;
+    if consumed.rows_affected == 0 {
+        return Err(ApiError::NotFound("メールアドレス変更リクエスト"));
+    }
+
+    let found = user::Entity::find_active_by_id(&request.user_id)
+        .one(&txn)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+
+    let mut am: user::ActiveModel = found.clone().into();
+    if request.field == EmailField::Email.as_str() {
+        let taken = user::Entity::find()
+            .filter(user::Column::Email.eq(&request.new_address))
+            .filter(user::Column::Id.ne(&request.user_id))
+            .count(&txn)
+            .await?;
+        if taken > 0 {
+            return Err(ApiError::Duplicate(Some("email".to_string())));
+        }
+        am.email = Set(request.new_address);
+        // 新しいアドレスはこのリクエストで確認済み
+        am.email_verified = Set(true);
+        email_verification::Entity::delete_many()
+            .filter(email_verification::Column::UserId.eq(&request.user_id))
+            .exec(&txn)
+            .await?;
+    } else {
+        am.external_email = Set(request.new_address);
+    }
+    am.updated_at = Set(Some(now));
+    let updated = am.update(&txn).await?;
+    AuditEntry::updated(AuditAction::EmailChange, &found.id, &found, &updated)
+        .record_as(&txn, &found.id, &client)
+        .await?;
+
+    txn.commit().await?;
+    search::index().upsert(&updated);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 旧アドレスに届いた取り消しコードでメールアドレスの変更を取り消すための関数
+///
+/// 取り消しコード自体が資格情報となるため、ログインしていなくても呼び出せます。
+/// 変更が確定した後でも、7日以内であれば旧アドレスに戻します。
+/// その場合、乗っ取りの可能性があるためユーザーのセッションを削除し、トークンを失効させます。
+#[utoipa::path(
+    post,
+    path = "/users/email_change/{code}/cancel",
+    tag = "users",
+    params(
+        ("code" = String, Path, description = "取り消しコード")
+    ),
+    responses(
+        (status = 204, description = "メールアドレス変更の取り消し成功"),
+        (status = 404, description = "変更リクエストが見つからない、または取り消しの期限切れ", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "旧アドレスが既に他のユーザーに使用されている", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn cancel_email_change(
+    State(db): State<DbConn>,
+    Extension(client): Extension<ClientInfo>,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let txn = db.begin().await?;
+    let now = Utc::now().naive_utc();
+
+    let request = email_change_request::Entity::find()
+        .filter(email_change_request::Column::CancelCodeHash.eq(token::hash_token(&code)))
+        .filter(email_change_request::Column::ExpiresAt.gt(now))
+        .one(&txn)
+        .await?
+        .ok_or(ApiError::NotFound("メールアドレス変更リクエスト"))?;
+
+    let res = email_change_request::Entity::delete_by_id(request.id)
+        .exec(&txn)
+        .await?;
+    if res.rows_affected == 0 {
+        return Err(ApiError::NotFound("メールアドレス変更リクエスト"));
+    }
+
+    // 未確定なら削除するだけでよい
+    if request.confirmed_at.is_none() {
+        txn.commit().await?;
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    let field = EmailField::parse(&request.field)
+        .ok_or(ApiError::NotFound("メールアドレス変更リクエスト"))?;
+    let found = user::Entity::find_active_by_id(&request.user_id)
+        .one(&txn)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+
+    let mut am: user::ActiveModel = found.clone().into();
+    match field {
+        EmailField::Email => {
+            let taken = user::Entity::find()
+                .filter(user::Column::Email.eq(&request.old_address))
+                .filter(user::Column::Id.ne(&request.user_id))
+                .count(&txn)
+                .await?;
+            if taken > 0 {
+                return Err(ApiError::Duplicate(Some("email".to_string())));
+            }
+            am.email = Set(request.old_address);
+            // 取り消しリンクが届いたことで旧アドレスの受信を確認できている
+            am.email_verified = Set(true);
+        }
+        EmailField::ExternalEmail => {
+            am.external_email = Set(request.old_address);
+        }
+    }
+    am.updated_at = Set(Some(now));
+    let updated = am.update(&txn).await?;
+
+    // 同じ項目の他の変更も、戻したアドレスを上書きしないよう破棄する
+    email_change_request::Entity::delete_many()
+        .filter(email_change_request::Column::UserId.eq(&request.user_id))
+        .filter(email_change_request::Column::Field.eq(field.as_str()))
+        .exec(&txn)
+        .await?;
+    users::revoke_access(&txn, &request.user_id).await?;
+    AuditEntry::updated(AuditAction::EmailChangeRevert, &found.id, &found, &updated)
+        .record_as(&txn, &found.id, &client)
+        .await?;
+
+    txn.commit().await?;
+    search::index().upsert(&updated);
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -11,19 +11,23 @@ use ulid::Ulid;
 use utoipa::ToSchema;
 
 use crate::{
+    error::{ApiError, ProblemDetails},
+    mailer::{self, Locale, Template},
     middleware::auth::AuthUser,
-    models::{
-        email_verification,
-        user::{self, Entity as User},
-    },
+    models::{email_verification, user},
     routes::email_verify::EmailVerificationResponse,
 };
 
+/// 1時間あたりに作成できるEmail検証チャレンジの上限
+const MAX_CHALLENGES_PER_HOUR: u64 = 5;
+/// Email検証チャレンジの有効期間の上限（時間）
+const CHALLENGE_TTL_HOURS: i64 = 24;
+
 pub fn routes() -> Router<DbConn> {
     Router::new()
         .route("/users/{uid}/email_verify", post(post_challenge))
         .route(
-            "/users/{uid}/email_verify/{id}",
+            "/users/{uid}/email_verify/{code}",
             get(get_email_verifications).delete(delete_email_verification),
         )
     //.merge(users_sub::books::routes())
@@ -31,16 +35,16 @@ pub fn routes() -> Router<DbConn> {
 
 #[utoipa::path(
     get,
-    path = "/users/{uid}/email_verify/{id}",
+    path = "/users/{uid}/email_verify/{code}",
     tag = "users",
     params(
         ("uid" = String, Path, description = "ユーザーID"),
-        ("id" = String, Path, description = "Email検証ID or 検証コード")
+        ("code" = String, Path, description = "検証コード")
     ),
     responses(
         (status = 200, description = "Email検証情報取得成功", body = EmailVerificationResponse),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "Email検証が見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Email検証が見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -48,59 +52,31 @@ pub fn routes() -> Router<DbConn> {
 )]
 pub async fn get_email_verifications(
     State(db): State<DbConn>,
-    Path((uid, id)): Path<(String, String)>,
+    Path((uid, code)): Path<(String, String)>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     // Self-only access
     if auth_user.user_id != uid {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::Forbidden);
     }
 
-    let found = User::find_by_id(uid)
+    let verification = email_verification::Entity::find()
+        .filter(email_verification::Column::VerificationCode.eq(code))
+        .filter(email_verification::Column::UserId.eq(uid))
+        .filter(email_verification::Column::ExpiresAt.gt(Utc::now().naive_utc()))
         .one(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    if let Some(user) = found {
-        if id.chars().all(|c| c.is_numeric()) {
-            let verifications = email_verification::Entity::find_by_id(
-                id.parse::<i32>().map_err(|_| StatusCode::BAD_REQUEST)?,
-            )
-            .filter(email_verification::Column::UserId.eq(user.id))
-            .filter(email_verification::Column::ExpiresAt.gt(Utc::now().naive_utc()))
-            .one(&db)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-            if let Some(verification) = verifications {
-                return Ok((
-                    StatusCode::OK,
-                    Json(EmailVerificationResponse::from(verification)),
-                ));
-            }
-
-            return Err(StatusCode::NOT_FOUND);
-        }
-        let verification = email_verification::Entity::find()
-            .filter(email_verification::Column::VerificationCode.eq(id))
-            .filter(email_verification::Column::UserId.eq(user.id))
-            .filter(email_verification::Column::ExpiresAt.gt(Utc::now().naive_utc()))
-            .one(&db)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?
+        .ok_or(ApiError::NotFound("Email検証"))?;
 
-        if let Some(verification) = verification {
-            return Ok((
-                StatusCode::OK,
-                Json(EmailVerificationResponse::from(verification)),
-            ));
-        }
-    }
-    Err(StatusCode::NOT_FOUND)
+    Ok((
+        StatusCode::OK,
+        Json(EmailVerificationResponse::from(verification)),
+    ))
 }
 
 #[derive(serde::Deserialize, ToSchema)]
 pub struct CreateVerifyChallenge {
+    /// 有効期限（省略した場合、または24時間より先の場合は24時間後）
     #[schema(value_type = String, format = "date-time")]
     pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
 }
@@ -116,8 +92,9 @@ pub struct CreateVerifyChallenge {
     request_body = CreateVerifyChallenge,
     responses(
         (status = 201, description = "Email検証チャレンジ作成成功", body = EmailVerificationResponse),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "ユーザーが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "チャレンジの作成回数が上限を超えた", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -128,20 +105,34 @@ pub async fn post_challenge(
     Path(uid): Path<String>,
     auth_user: axum::Extension<AuthUser>,
     Json(payload): Json<CreateVerifyChallenge>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     // Self-only access
     if auth_user.user_id != uid {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::Forbidden);
     }
 
-    let found = user::Entity::find_by_id(uid)
-        .one(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let found = user::Entity::find_active_by_id(uid).one(&db).await?;
+
+    // 短時間に大量のチャレンジを作成できないようにする
+    if let Some(ref user) = found {
+        let recent = email_verification::Entity::find()
+            .filter(email_verification::Column::UserId.eq(&user.id))
+            .filter(
+                email_verification::Column::CreatedAt
+                    .gt((Utc::now() - chrono::Duration::hours(1)).naive_utc()),
+            )
+            .count(&db)
+            .await?;
+        if recent >= MAX_CHALLENGES_PER_HOUR {
+            return Err(ApiError::TooManyRequests);
+        }
+    }
 
+    // 有効期限は上限を超えないよう切り詰める
+    let max_expires_at = Utc::now() + chrono::Duration::hours(CHALLENGE_TTL_HOURS);
     let expires_at = payload
         .expires_at
-        .unwrap_or_else(|| Utc::now() + chrono::Duration::hours(24))
+        .map_or(max_expires_at, |t| t.min(max_expires_at))
         .naive_utc();
 
     if let Some(user) = found {
@@ -154,32 +145,41 @@ pub async fn post_challenge(
         };
         match challenge.insert(&db).await {
             Ok(res) => {
+                let url =
+                    mailer::public_url(&format!("/email/verify?code={}", res.verification_code));
+                let mail = Template::EmailVerification {
+                    name: &user.name,
+                    url: &url,
+                    expires_at: res.expires_at,
+                }
+                .to_mail(&user.email, Locale::from_env());
+                mailer::queue::enqueue(&db, mail).await?;
                 return Ok((
                     StatusCode::CREATED,
                     Json(EmailVerificationResponse::from(res)),
                 ));
             }
-            Err(_) => {
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            Err(e) => {
+                return Err(e.into());
             }
         }
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ユーザー"))
 }
 
 /// ユーザーのEmail検証チャレンジを削除するための関数
 #[utoipa::path(
     delete,
-    path = "/users/{uid}/email_verify/{id}",
+    path = "/users/{uid}/email_verify/{code}",
     tag = "users",
     params(
         ("uid" = String, Path, description = "ユーザーID"),
-        ("id" = String, Path, description = "Email検証ID or 検証コード")
+        ("code" = String, Path, description = "検証コード")
     ),
     responses(
         (status = 204, description = "Email検証削除成功"),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "Email検証が見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Email検証が見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -187,54 +187,22 @@ pub async fn post_challenge(
 )]
 pub async fn delete_email_verification(
     State(db): State<DbConn>,
-    Path((uid, id)): Path<(String, String)>,
+    Path((uid, code)): Path<(String, String)>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     // Self-only access
     if auth_user.user_id != uid {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::Forbidden);
     }
 
-    let found = User::find_by_id(uid)
-        .one(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    if let Some(user) = found {
-        if id.chars().all(|c| c.is_numeric()) {
-            email_verification::Entity::delete_by_id(
-                id.parse::<i32>().map_err(|_| StatusCode::BAD_REQUEST)?,
-            )
-            .filter(email_verification::Column::UserId.eq(user.id))
-            .exec(&db)
-            .await
-            .map_err(|e| match e {
-                DbErr::RecordNotFound(_) => StatusCode::NOT_FOUND,
-                _ => StatusCode::INTERNAL_SERVER_ERROR,
-            })?;
+    let res = email_verification::Entity::delete_many()
+        .filter(email_verification::Column::UserId.eq(uid))
+        .filter(email_verification::Column::VerificationCode.eq(code))
+        .exec(&db)
+        .await?;
 
-            return Ok((
-                StatusCode::NO_CONTENT,
-                Json::<Option<email_verification::Model>>(None),
-            ));
-        }
-        let found = email_verification::Entity::find()
-            .filter(email_verification::Column::UserId.eq(user.id))
-            .filter(email_verification::Column::VerificationCode.eq(id))
-            .one(&db)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        if let Some(found) = found {
-            let am: email_verification::ActiveModel = found.into();
-            am.delete(&db)
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            return Ok((
-                StatusCode::NO_CONTENT,
-                Json::<Option<email_verification::Model>>(None),
-            ));
-        }
+    if res.rows_affected == 0 {
+        return Err(ApiError::NotFound("Email検証"));
     }
-
-    Err(StatusCode::NOT_FOUND)
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,138 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::*,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::*;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    lockout::{self, Subject},
+    middleware::{auth::AuthUser, permission_check},
+    models::user::Entity as User,
+};
+
+/// =======================
+/// DTO（レスポンス専用）
+/// =======================
+
+#[derive(Serialize, ToSchema)]
+pub struct LockoutResponse {
+    /// 現在ロック中（待ち時間中を含む）かどうか
+    pub locked: bool,
+    /// 連続した認証の失敗回数
+    pub failures: u32,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub last_failure_at: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+pub fn routes() -> Router<DbConn> {
+    Router::new().route(
+        "/users/{id}/lockout",
+        get(get_lockout).delete(delete_lockout),
+    )
+}
+
+/// ユーザーの認証失敗・ロック状態を取得するための関数
+/// USER_UPDATE 権限が必要です
+#[utoipa::path(
+    get,
+    path = "/users/{id}/lockout",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    responses(
+        (status = 200, description = "ロック状態の取得に成功", body = LockoutResponse),
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn get_lockout(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission(&auth_user, Permission::USER_UPDATE, &db).await?;
+    let user = User::find_active_by_id(id)
+        .one(&db)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+
+    let subject = Subject::Account(&user.id);
+    let state = lockout::lockout().status(subject).await?;
+    let now = Utc::now();
+    let res = match state {
+        Some(state) => {
+            let locked_until = lockout::lockout().locked_until(&subject, &state);
+            LockoutResponse {
+                locked: locked_until > now,
+                failures: state.failures,
+                last_failure_at: Some(state.last_failure_at),
+                locked_until: Some(locked_until),
+            }
+        }
+        None => LockoutResponse {
+            locked: false,
+            failures: 0,
+            last_failure_at: None,
+            locked_until: None,
+        },
+    };
+    Ok((StatusCode::OK, Json(res)))
+}
+
+/// ユーザーのロックを解除し、認証の失敗回数をリセットするための関数
+/// USER_UPDATE 権限が必要です
+///
+/// 接続元 IP 単位のロックは解除されません。
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/lockout",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    responses(
+        (status = 204, description = "ロック解除成功"),
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn delete_lockout(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission(&auth_user, Permission::USER_UPDATE, &db).await?;
+    let user = User::find_active_by_id(id)
+        .one(&db)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+
+    let subject = Subject::Account(&user.id);
+    let state = lockout::lockout().status(subject).await?;
+    lockout::lockout().unlock(subject).await?;
+    if let Some(state) = state {
+        AuditEntry::new(AuditAction::AccountUnlock, &user.id)
+            .with_before(serde_json::to_value(state).map_err(anyhow::Error::from)?)
+            .record(&db, &auth_user)
+            .await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
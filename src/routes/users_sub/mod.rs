@@ -1,7 +1,15 @@
+pub mod bulk;
+pub mod deletion;
 pub mod discord;
+pub mod email_change;
 pub mod email_verify;
+pub mod lockout;
 pub mod password;
 pub mod permissions;
+pub mod personal_data;
+pub mod restore;
 pub mod roles;
 pub mod search;
 pub mod sessions;
+pub mod transfer;
+pub mod update_policy;
@@ -1,25 +1,45 @@
 use axum::{
-    Json, Router,
+    Extension, Json, Router,
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
     routing::*,
 };
+use chrono::Utc;
 use sea_orm::*;
-use serde_json;
+use tracing::error;
 use utoipa::ToSchema;
+use validator::Validate;
 
-use crate::{middleware::auth::AuthUser, models::user, utils::password};
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    error::{ApiError, ProblemDetails},
+    extract::ValidatedJson,
+    lockout,
+    mailer::{self, Locale, Template},
+    middleware::auth::{AuthUser, ClientInfo},
+    models::{password_reset, session, user},
+    password_policy::{self, UserContext},
+    utils::{password, token},
+};
 //use crate::{db::DbConn, routes::users_sub};
 
+/// リセットトークンの有効期間（分）
+const RESET_TOKEN_TTL_MINUTES: i64 = 30;
+
 pub fn routes() -> Router<DbConn> {
     Router::new()
         .route("/users/{id}/password/change", put(password_change))
         .route("/users/password/reset", post(password_reset))
+        .route(
+            "/users/password/reset/confirm",
+            post(password_reset_confirm),
+        )
 }
 
-#[derive(serde::Deserialize, ToSchema)]
+#[derive(serde::Deserialize, ToSchema, Validate)]
 pub struct PasswordChange {
+    #[validate(length(min = 1))]
     pub current_password: String,
     pub new_password: String,
 }
@@ -35,9 +55,11 @@ pub struct PasswordChange {
     request_body = PasswordChange,
     responses(
         (status = 201, description = "パスワード変更成功"),
-        (status = 401, description = "現在のパスワードが不正確"),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "ユーザーが見つからない")
+        (status = 401, description = "現在のパスワードが不正確", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "失敗が続いたため一時的にロックされている", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -47,14 +69,17 @@ pub async fn password_change(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-    Json(payload): Json<PasswordChange>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ValidatedJson(payload): ValidatedJson<PasswordChange>,
+) -> Result<impl IntoResponse, ApiError> {
     // 自分のパスワード変更のみ許可
     if auth_user.user_id != id {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::Forbidden);
     }
 
-    let found = user::Entity::find_by_id(id).one(&db).await.unwrap();
+    let subjects = lockout::subjects(&id, &auth_user.client);
+    lockout::lockout().check(&subjects).await?;
+
+    let found = user::Entity::find_active_by_id(&id).one(&db).await?;
     if let Some(user) = found {
         // パスワードの検証
         let password_matches = user
@@ -63,19 +88,42 @@ pub async fn password_change(
             .map(|h| password::verify_password(&payload.current_password, h))
             .unwrap_or(false);
         if !password_matches {
-            return Err(StatusCode::UNAUTHORIZED);
+            lockout::lockout()
+                .record_failure(&db, &subjects, &auth_user.user_id, &auth_user.client)
+                .await?;
+            return Err(ApiError::Unauthorized);
         }
+        lockout::lockout().record_success(&id).await?;
+
+        password_policy::check(
+            &db,
+            "new_password",
+            &payload.new_password,
+            &UserContext {
+                user_id: Some(&user.id),
+                current_hash: user.password_hash.as_deref(),
+                custom_id: &user.custom_id,
+                name: &user.name,
+            },
+        )
+        .await?;
 
         // 新しいパスワードのハッシュ化
         let new_password_hash = password::hash_password(&payload.new_password);
 
         let mut am: user::ActiveModel = user.into();
-        am.password_hash = Set(Some(new_password_hash));
+        am.password_hash = Set(Some(new_password_hash.clone()));
         am.updated_at = Set(Some(chrono::Utc::now().naive_utc()));
-        am.update(&db).await.unwrap();
+        let txn = db.begin().await?;
+        let res = am.update(&txn).await?;
+        password_policy::record_history(&txn, &res.id, &new_password_hash).await?;
+        AuditEntry::new(AuditAction::PasswordChange, &res.id)
+            .record(&txn, &auth_user)
+            .await?;
+        txn.commit().await?;
         return Ok((StatusCode::CREATED, Json(serde_json::Value::Null)));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ユーザー"))
 }
 
 #[derive(serde::Deserialize, ToSchema)]
@@ -84,28 +132,154 @@ pub struct PasswordReset {
 }
 
 /// ユーザーのパスワードをリセットするための関数
+///
+/// ユーザーの存在有無に関わらず常に 200 を返します。
+/// ユーザーが存在する場合は外部メールアドレスへリセットリンクを送信します。
 #[utoipa::path(
     post,
     path = "/users/password/reset",
     tag = "users",
     request_body = PasswordReset,
     responses(
-        (status = 200, description = "パスワードリセットリンク送信成功"),
-        (status = 404, description = "ユーザーが見つからない")
+        (status = 200, description = "パスワードリセット要求受付"),
+        (status = 429, description = "リクエストが多すぎる", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "サーバーエラー", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
 pub async fn password_reset(
     State(db): State<DbConn>,
     Json(payload): Json<PasswordReset>,
-) -> impl IntoResponse {
-    let found = user::Entity::find()
+) -> Result<impl IntoResponse, ApiError> {
+    let found = user::Entity::find_active()
         .filter(user::Column::CustomId.eq(&payload.username))
         .one(&db)
-        .await
-        .unwrap();
+        .await?;
+
     if let Some(user) = found {
-        // TODO: 外部メールアドレスに対してリセットリンクを送信する処理を追加する
-        return (StatusCode::OK, Json(serde_json::Value::Null));
+        // 失敗をそのまま返すとユーザーの存在が分かってしまうため、記録だけして同じ応答を返す
+        if let Err(e) = send_reset_link(&db, &user).await {
+            error!("Failed to send password reset link to {}: {:?}", user.id, e);
+        }
     }
-    (StatusCode::NOT_FOUND, Json(serde_json::Value::Null))
+
+    Ok((StatusCode::OK, Json(serde_json::Value::Null)))
+}
+
+/// リセットトークンを発行し、外部メールアドレスへリンクを送る
+async fn send_reset_link(db: &DbConn, user: &user::Model) -> Result<(), DbErr> {
+    // 発行済みのトークンは無効化する
+    password_reset::Entity::delete_many()
+        .filter(password_reset::Column::UserId.eq(&user.id))
+        .exec(db)
+        .await?;
+
+    let reset_token = token::generate_token();
+    let now = Utc::now();
+    let am = password_reset::ActiveModel {
+        user_id: Set(user.id.clone()),
+        token_hash: Set(token::hash_token(&reset_token)),
+        created_at: Set(Some(now.naive_utc())),
+        expires_at: Set((now + chrono::Duration::minutes(RESET_TOKEN_TTL_MINUTES)).naive_utc()),
+        ..Default::default()
+    };
+    am.insert(db).await?;
+
+    let url = mailer::public_url(&format!("/password/reset?token={}", reset_token));
+    let mail = Template::PasswordReset {
+        name: &user.name,
+        url: &url,
+        ttl_minutes: RESET_TOKEN_TTL_MINUTES,
+    }
+    .to_mail(&user.external_email, Locale::from_env());
+    mailer::queue::enqueue(db, mail).await?;
+    Ok(())
+}
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct PasswordResetConfirm {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// リセットトークンを使って新しいパスワードを設定するための関数
+///
+/// 成功するとユーザーのすべてのセッションが削除されます。
+#[utoipa::path(
+    post,
+    path = "/users/password/reset/confirm",
+    tag = "users",
+    request_body = PasswordResetConfirm,
+    responses(
+        (status = 200, description = "パスワード再設定成功"),
+        (status = 400, description = "トークンが不正または期限切れ", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "パスワードがポリシーを満たさない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "リクエストが多すぎる", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "サーバーエラー", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn password_reset_confirm(
+    State(db): State<DbConn>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<PasswordResetConfirm>,
+) -> Result<impl IntoResponse, ApiError> {
+    let txn = db.begin().await?;
+
+    // 同じトークンでの同時のリクエストは、先のトランザクションが終わるまでここで待たされる
+    let found = password_reset::Entity::find()
+        .filter(password_reset::Column::TokenHash.eq(token::hash_token(&payload.token)))
+        .filter(password_reset::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+        .lock_exclusive()
+        .one(&txn)
+        .await?;
+    let Some(reset) = found else {
+        return Err(invalid_reset_token());
+    };
+
+    let user = user::Entity::find_active_by_id(&reset.user_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(invalid_reset_token)?;
+
+    password_policy::check(
+        &txn,
+        "new_password",
+        &payload.new_password,
+        &UserContext {
+            user_id: Some(&user.id),
+            current_hash: user.password_hash.as_deref(),
+            custom_id: &user.custom_id,
+            name: &user.name,
+        },
+    )
+    .await?;
+
+    let new_password_hash = password::hash_password(&payload.new_password);
+    let mut am: user::ActiveModel = user.into();
+    am.password_hash = Set(Some(new_password_hash.clone()));
+    am.updated_at = Set(Some(Utc::now().naive_utc()));
+    am.update(&txn).await?;
+    password_policy::record_history(&txn, &reset.user_id, &new_password_hash).await?;
+
+    // トークンは使い捨て
+    password_reset::Entity::delete_many()
+        .filter(password_reset::Column::UserId.eq(&reset.user_id))
+        .exec(&txn)
+        .await?;
+
+    // 既存のセッションはすべて破棄する
+    session::Entity::delete_many()
+        .filter(session::Column::UserId.eq(&reset.user_id))
+        .exec(&txn)
+        .await?;
+
+    AuditEntry::new(AuditAction::PasswordReset, &reset.user_id)
+        .record_as(&txn, &reset.user_id, &client)
+        .await?;
+    txn.commit().await?;
+
+    Ok((StatusCode::OK, Json(serde_json::Value::Null)))
+}
+
+fn invalid_reset_token() -> ApiError {
+    ApiError::BadRequest("トークンが不正か、有効期限が切れています".to_string())
 }
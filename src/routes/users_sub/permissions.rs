@@ -1,5 +1,8 @@
 use crate::{
-    constants::permissions::Permission, middleware::auth::AuthUser, models::user::Entity as User,
+    constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    middleware::auth::AuthUser,
+    models::user::Entity as User,
 };
 use axum::{
     Json, Router,
@@ -36,8 +39,8 @@ pub fn routes() -> Router<DbConn> {
     ),
     responses(
         (status = 200, description = "権限情報取得成功", body = PermissionsResponse),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "ユーザーが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -47,15 +50,14 @@ pub async fn get_permissions_bit(
     State(db): State<DbConn>,
     Path(id): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     // Self-only access OR read permission
     if auth_user.user_id != id {
         let user_roles = crate::models::user_role::Entity::find()
             .filter(crate::models::user_role::Column::UserId.eq(&auth_user.user_id))
             .find_with_related(crate::models::role::Entity)
             .all(&db)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .await?;
 
         let has_read_permission = user_roles.iter().any(|(_, roles)| {
             roles
@@ -64,21 +66,17 @@ pub async fn get_permissions_bit(
         });
 
         if !has_read_permission {
-            return Err(StatusCode::FORBIDDEN);
+            return Err(ApiError::Forbidden);
         }
     }
 
-    let user = User::find_by_id(id)
-        .one(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let user = User::find_active_by_id(id).one(&db).await?;
 
     if let Some(user) = user {
         let roles = user
             .find_related(crate::models::role::Entity)
             .all(&db)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .await?;
 
         let mut permissions_bit: i64 = 0;
         for role in roles {
@@ -106,5 +104,5 @@ pub async fn get_permissions_bit(
             }),
         ));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ユーザー"))
 }
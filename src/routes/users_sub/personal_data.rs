@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::*,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sea_orm::*;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    jobs::SYSTEM_ACTOR,
+    middleware::{auth::AuthUser, permission_check},
+    models::{
+        access_tokens, app, audit_event, auths, consents, discord, email_change_request,
+        email_verification, id_tokens, oidc_authorizations, password_history, password_reset,
+        refresh_tokens, role, session, user::Entity as User, user_app,
+    },
+    routes::{
+        roles::RoleResponse, users::DetailedUserResponse, users_sub::discord::DiscordResponse,
+    },
+};
+
+pub fn routes() -> Router<DbConn> {
+    Router::new().route("/users/{id}/export", get(export_personal_data))
+}
+
+// =======================
+// DTO（レスポンス専用）
+// =======================
+
+/// セッション（ID は Cookie の値そのものなので含めない）
+#[derive(Serialize, ToSchema)]
+pub struct SessionExport {
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub expires_at: Option<DateTime<Utc>>,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub is_enable: bool,
+}
+
+/// OIDC でログインを許可したアプリ
+#[derive(Serialize, ToSchema)]
+pub struct AuthorizedAppExport {
+    pub app_id: String,
+    pub app_name: Option<String>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub authorized_at: Option<DateTime<Utc>>,
+    pub is_enable: bool,
+    /// 同意したスコープ
+    pub scopes: Vec<String>,
+}
+
+/// 所属しているアプリ
+#[derive(Serialize, ToSchema)]
+pub struct AppExport {
+    pub id: String,
+    pub name: String,
+}
+
+/// 発行されたトークンの情報（トークンの値やハッシュは含めない）
+#[derive(Serialize, ToSchema)]
+pub struct TokenExport {
+    pub id: String,
+    /// `access` / `refresh` / `id`
+    pub kind: &'static str,
+    pub client_id: String,
+    pub scope: Option<String>,
+    #[schema(value_type = String, format = "date-time")]
+    pub issued_at: DateTime<Utc>,
+    #[schema(value_type = String, format = "date-time")]
+    pub expires_at: DateTime<Utc>,
+    pub revoked: bool,
+}
+
+/// メール確認・パスワード再設定・メールアドレス変更の申請（コードやハッシュは含めない）
+#[derive(Serialize, ToSchema)]
+pub struct PendingRequestExport {
+    /// `email_verification` / `password_reset` / `email_change`
+    pub kind: &'static str,
+    /// メールアドレス変更の場合の変更先
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_address: Option<String>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub created_at: Option<NaiveDateTime>,
+    #[schema(value_type = String, format = "date-time")]
+    pub expires_at: NaiveDateTime,
+}
+
+/// 監査ログ
+/// 他人が行った操作では操作者を明かさず、IP・User-Agent も含めない.
+/// 本人が他人に対して行った操作では、相手の値（`before` / `after`）を含めない.
+#[derive(Serialize, ToSchema)]
+pub struct AuditEventExport {
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    /// 操作者（`self`: 本人 / `system`: API キー・定期ジョブ / `other`: 他のユーザー）
+    pub actor: &'static str,
+    #[schema(value_type = Object)]
+    pub before: Option<serde_json::Value>,
+    #[schema(value_type = Object)]
+    pub after: Option<serde_json::Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: NaiveDateTime,
+}
+
+impl AuditEventExport {
+    fn new(user_id: &str, event: audit_event::Model) -> Self {
+        let by_self = event.actor_id == user_id;
+        let on_self = event.target_type == "user" && event.target_id == user_id;
+        let actor = if by_self {
+            "self"
+        } else if event.actor_id == SYSTEM_ACTOR {
+            "system"
+        } else {
+            "other"
+        };
+        Self {
+            action: event.action,
+            target_type: event.target_type,
+            target_id: event.target_id,
+            actor,
+            before: event.before.filter(|_| on_self),
+            after: event.after.filter(|_| on_self),
+            ip_address: event.ip_address.filter(|_| by_self),
+            user_agent: event.user_agent.filter(|_| by_self),
+            created_at: event.created_at,
+        }
+    }
+}
+
+/// ユーザーについて保持しているすべてのデータ
+#[derive(Serialize, ToSchema)]
+pub struct PersonalDataExport {
+    #[schema(value_type = String, format = "date-time")]
+    pub generated_at: DateTime<Utc>,
+    /// プロフィール（Discord の連携とロールを含む）
+    pub user: DetailedUserResponse,
+    pub sessions: Vec<SessionExport>,
+    pub authorized_apps: Vec<AuthorizedAppExport>,
+    pub apps: Vec<AppExport>,
+    pub tokens: Vec<TokenExport>,
+    pub pending_requests: Vec<PendingRequestExport>,
+    /// パスワードを変更した日時
+    #[schema(value_type = Vec<String>, format = "date-time")]
+    pub password_changes: Vec<NaiveDateTime>,
+    /// ユーザーが行った操作と、ユーザーに対して行われた操作
+    pub audit_events: Vec<AuditEventExport>,
+}
+
+/// ログインを許可したアプリと同意したスコープ
+async fn authorized_apps(db: &DbConn, user_id: &str) -> Result<Vec<AuthorizedAppExport>, DbErr> {
+    let grants = auths::Entity::find()
+        .filter(auths::Column::AuthUserId.eq(user_id))
+        .order_by_asc(auths::Column::Id)
+        .all(db)
+        .await?;
+    let authorizations = oidc_authorizations::Entity::find()
+        .filter(oidc_authorizations::Column::AuthId.is_in(grants.iter().map(|g| g.id)))
+        .find_also_related(consents::Entity)
+        .all(db)
+        .await?;
+    let app_names: HashMap<String, String> = app::Entity::find()
+        .filter(app::Column::Id.is_in(grants.iter().map(|g| g.app_id.clone())))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|a| (a.id, a.name))
+        .collect();
+
+    Ok(grants
+        .into_iter()
+        .map(|grant| {
+            let mut scopes: Vec<String> = authorizations
+                .iter()
+                .filter(|(a, _)| a.auth_id == grant.id)
+                .filter_map(|(_, consent)| consent.as_ref()?.scope.as_deref())
+                .flat_map(str::split_whitespace)
+                .map(str::to_string)
+                .collect();
+            scopes.sort();
+            scopes.dedup();
+            AuthorizedAppExport {
+                app_name: app_names.get(&grant.app_id).cloned(),
+                app_id: grant.app_id,
+                authorized_at: grant.created_at,
+                is_enable: grant.is_enable != 0,
+                scopes,
+            }
+        })
+        .collect())
+}
+
+/// 発行されたアクセストークン・リフレッシュトークン・ID トークン
+async fn tokens(db: &DbConn, user_id: &str) -> Result<Vec<TokenExport>, DbErr> {
+    let mut tokens: Vec<TokenExport> = Vec::new();
+    for t in access_tokens::Entity::find()
+        .filter(access_tokens::Column::UserId.eq(user_id))
+        .all(db)
+        .await?
+    {
+        tokens.push(TokenExport {
+            id: t.id,
+            kind: "access",
+            client_id: t.client_id,
+            scope: Some(t.scope),
+            issued_at: t.issued_at,
+            expires_at: t.exp,
+            revoked: t.revoked != 0,
+        });
+    }
+    for t in refresh_tokens::Entity::find()
+        .filter(refresh_tokens::Column::UserId.eq(user_id))
+        .all(db)
+        .await?
+    {
+        tokens.push(TokenExport {
+            id: t.id,
+            kind: "refresh",
+            client_id: t.client_id,
+            scope: None,
+            issued_at: t.issued_at,
+            expires_at: t.exp,
+            revoked: t.revoked != 0,
+        });
+    }
+    for t in id_tokens::Entity::find()
+        .filter(id_tokens::Column::UserId.eq(user_id))
+        .all(db)
+        .await?
+    {
+        tokens.push(TokenExport {
+            id: t.id,
+            kind: "id",
+            client_id: t.client_id,
+            scope: None,
+            issued_at: t.issued_at,
+            expires_at: t.exp,
+            revoked: t.revoked != 0,
+        });
+    }
+    tokens.sort_by_key(|t| t.issued_at);
+    Ok(tokens)
+}
+
+/// 未完了のメール確認・パスワード再設定・メールアドレス変更
+async fn pending_requests(db: &DbConn, user_id: &str) -> Result<Vec<PendingRequestExport>, DbErr> {
+    let mut requests: Vec<PendingRequestExport> = Vec::new();
+    for r in email_verification::Entity::find()
+        .filter(email_verification::Column::UserId.eq(user_id))
+        .all(db)
+        .await?
+    {
+        requests.push(PendingRequestExport {
+            kind: "email_verification",
+            new_address: None,
+            created_at: r.created_at,
+            expires_at: r.expires_at,
+        });
+    }
+    for r in password_reset::Entity::find()
+        .filter(password_reset::Column::UserId.eq(user_id))
+        .all(db)
+        .await?
+    {
+        requests.push(PendingRequestExport {
+            kind: "password_reset",
+            new_address: None,
+            created_at: r.created_at,
+            expires_at: r.expires_at,
+        });
+    }
+    for r in email_change_request::Entity::find()
+        .filter(email_change_request::Column::UserId.eq(user_id))
+        .all(db)
+        .await?
+    {
+        requests.push(PendingRequestExport {
+            kind: "email_change",
+            new_address: Some(r.new_address),
+            created_at: r.created_at,
+            expires_at: r.expires_at,
+        });
+    }
+    requests.sort_by_key(|r| r.created_at);
+    Ok(requests)
+}
+
+/// ユーザーについて保持しているデータをすべて書き出すための関数
+/// 本人、または USER_READ 権限が必要です
+///
+/// プロフィール・Discord の連携・ロール・セッション・ログインを許可したアプリ・所属しているアプリ・
+/// トークンの情報・未完了の申請・パスワードの変更日時・監査ログを 1 つの JSON にまとめて返します。
+/// パスワードのハッシュ、トークンやコードの値、セッション ID は含みません。
+///
+/// > [!NOTE]
+/// > 書き出しは監査ログに `user.export` として記録されます。
+#[utoipa::path(
+    get,
+    path = "/users/{id}/export",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    responses(
+        (status = 200, description = "書き出しに成功", body = PersonalDataExport),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "リクエストが多すぎる", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn export_personal_data(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission_or_self(&auth_user, Permission::USER_READ, &id, &db)
+        .await?;
+    let user = User::find_active_by_id(&id)
+        .one(&db)
+        .await?
+        .ok_or(ApiError::NotFound("ユーザー"))?;
+
+    let discords = user.find_related(discord::Entity).all(&db).await?;
+    let roles = user.find_related(role::Entity).all(&db).await?;
+    let sessions = user
+        .find_related(session::Entity)
+        .order_by_asc(session::Column::CreatedAt)
+        .all(&db)
+        .await?;
+    let app_ids: Vec<String> = user_app::Entity::find()
+        .filter(user_app::Column::UserId.eq(&user.id))
+        .all(&db)
+        .await?
+        .into_iter()
+        .filter_map(|m| m.app_id)
+        .collect();
+    let apps = app::Entity::find()
+        .filter(app::Column::Id.is_in(app_ids))
+        .order_by_asc(app::Column::Id)
+        .all(&db)
+        .await?;
+    let password_changes = password_history::Entity::find()
+        .filter(password_history::Column::UserId.eq(&user.id))
+        .order_by_asc(password_history::Column::CreatedAt)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|h| h.created_at)
+        .collect();
+    let audit_events = audit_event::Entity::find()
+        .filter(
+            Condition::any()
+                .add(audit_event::Column::ActorId.eq(&user.id))
+                .add(
+                    Condition::all()
+                        .add(audit_event::Column::TargetType.eq("user"))
+                        .add(audit_event::Column::TargetId.eq(&user.id)),
+                ),
+        )
+        .order_by_asc(audit_event::Column::Id)
+        .all(&db)
+        .await?;
+
+    let mut profile = DetailedUserResponse::from(user.clone());
+    profile.discords = discords.into_iter().map(DiscordResponse::from).collect();
+    profile.roles = Some(roles.into_iter().map(RoleResponse::from).collect());
+
+    let export = PersonalDataExport {
+        generated_at: Utc::now(),
+        user: profile,
+        sessions: sessions
+            .into_iter()
+            .map(|s| SessionExport {
+                created_at: s.created_at,
+                expires_at: s.expires_at,
+                ip_address: s.ip_address,
+                user_agent: s.user_agent,
+                is_enable: s.is_enable,
+            })
+            .collect(),
+        authorized_apps: authorized_apps(&db, &user.id).await?,
+        apps: apps
+            .into_iter()
+            .map(|a| AppExport {
+                id: a.id,
+                name: a.name,
+            })
+            .collect(),
+        tokens: tokens(&db, &user.id).await?,
+        pending_requests: pending_requests(&db, &user.id).await?,
+        password_changes,
+        audit_events: audit_events
+            .into_iter()
+            .map(|e| AuditEventExport::new(&user.id, e))
+            .collect(),
+    };
+
+    AuditEntry::new(AuditAction::UserExport, &user.id)
+        .record(&db, &auth_user)
+        .await?;
+
+    let disposition = format!("attachment; filename=\"user-{}.json\"", user.id);
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_DISPOSITION, disposition)],
+        Json(export),
+    ))
+}
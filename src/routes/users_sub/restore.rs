@@ -0,0 +1,70 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::*,
+};
+use chrono::Utc;
+use sea_orm::*;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    middleware::{auth::AuthUser, permission_check},
+    models::user::{self, Entity as User},
+    routes::users::DetailedUserResponse,
+    search,
+};
+
+pub fn routes() -> Router<DbConn> {
+    Router::new().route("/users/{id}/restore", post(restore_user))
+}
+
+/// 削除したユーザーを復元するための関数
+/// USER_DELETE 権限が必要です
+///
+/// 保持期間を過ぎて完全に削除されたユーザーは復元できません。
+/// 削除時に削除したセッションや失効させたトークンは戻りません。
+#[utoipa::path(
+    post,
+    path = "/users/{id}/restore",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ユーザーID")
+    ),
+    responses(
+        (status = 200, description = "ユーザーの復元に成功", body = DetailedUserResponse),
+        (status = 403, description = "権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "削除済みのユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn restore_user(
+    State(db): State<DbConn>,
+    Path(id): Path<String>,
+    auth_user: axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission(&auth_user, Permission::USER_DELETE, &db).await?;
+    let user = User::find_by_id(id)
+        .filter(user::Column::DeletedAt.is_not_null())
+        .one(&db)
+        .await?
+        .ok_or(ApiError::NotFound("削除済みのユーザー"))?;
+
+    let mut am: user::ActiveModel = user.clone().into();
+    am.deleted_at = Set(None);
+    am.updated_at = Set(Some(Utc::now().naive_utc()));
+    let txn = db.begin().await?;
+    let restored = am.update(&txn).await?;
+    AuditEntry::updated(AuditAction::UserRestore, &user.id, &user, &restored)
+        .record(&txn, &auth_user)
+        .await?;
+    txn.commit().await?;
+
+    search::index().upsert(&restored);
+    Ok((StatusCode::OK, Json(DetailedUserResponse::from(restored))))
+}
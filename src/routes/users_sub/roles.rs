@@ -6,9 +6,12 @@ use axum::{
     routing::*,
 };
 use sea_orm::*;
+use serde_json::json;
 
 use crate::{
+    audit::{AuditAction, AuditEntry},
     constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
     middleware::{auth::AuthUser, permission_check},
     models::role::Entity as Role,
     models::user::Entity as User,
@@ -32,7 +35,7 @@ pub fn routes() -> Router<DbConn> {
     ),
     responses(
         (status = 200, description = "ユーザーのロール一覧取得成功"),
-        (status = 404, description = "ユーザーが見つからない")
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -42,7 +45,7 @@ pub async fn get_all_roles(
     State(db): State<DbConn>,
     Path(uid): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission_or_self(
         &auth_user,
         Permission::PERMISSION_MANAGE,
@@ -51,13 +54,13 @@ pub async fn get_all_roles(
     )
     .await?;
 
-    let user = User::find_by_id(uid).one(&db).await.unwrap();
+    let user = User::find_active_by_id(uid).one(&db).await?;
     if let Some(user) = user {
-        let roles = user.find_related(Role).all(&db).await.unwrap();
+        let roles = user.find_related(Role).all(&db).await?;
         let responses: Vec<RoleResponse> = roles.into_iter().map(RoleResponse::from).collect();
-        return Ok((StatusCode::OK, Json(ApiResponse { data: responses })));
+        return Ok((StatusCode::OK, Json(ApiResponse::new(responses))));
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ユーザー"))
 }
 
 // ロール付与
@@ -71,8 +74,9 @@ pub async fn get_all_roles(
     ),
     responses(
         (status = 201, description = "ロール付与成功", body = crate::routes::roles::RoleResponse),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "ユーザーまたはロールが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーまたはロールが見つからない", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "既に付与済み", body = ProblemDetails, content_type = "application/problem+json"),
     ),
     security(
         ("session_token" = [])
@@ -82,27 +86,33 @@ pub async fn put_role(
     State(db): State<DbConn>,
     Path((uid, id)): Path<(String, String)>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::PERMISSION_MANAGE, &db).await?;
 
     // user と role を同時に取りに行く（並列）
     let (user_res, role_res) = futures::join!(
-        User::find_by_id(uid.clone()).one(&db),
+        User::find_active_by_id(uid.clone()).one(&db),
         Role::find_by_id(id.clone()).one(&db)
     );
 
-    match (user_res.unwrap(), role_res.unwrap()) {
+    match (user_res?, role_res?) {
         (Some(user), Some(role)) => {
             let user_role = crate::models::user_role::ActiveModel {
                 user_id: Set(user.id.clone()),
                 role_id: Set(role.id.clone()),
                 ..Default::default()
             };
-            // 既にある場合はエラーになるかもしれないから、必要なら重複チェックを追加
-            let _ = user_role.insert(&db).await.unwrap();
+            // 既に付与済みの場合は一意制約違反として 409 になる
+            let txn = db.begin().await?;
+            user_role.insert(&txn).await?;
+            AuditEntry::new(AuditAction::RoleGrant, &user.id)
+                .with_after(json!({ "role_id": role.id }))
+                .record(&txn, &auth_user)
+                .await?;
+            txn.commit().await?;
             Ok((StatusCode::CREATED, Json(RoleResponse::from(role))))
         }
-        _ => Err(StatusCode::NOT_FOUND),
+        _ => Err(ApiError::NotFound("ユーザーまたはロール")),
     }
 }
 
@@ -119,8 +129,8 @@ pub async fn put_role(
     ),
     responses(
         (status = 204, description = "ロール削除成功"),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "ユーザーまたはロールが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーまたはロールが見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -130,27 +140,32 @@ pub async fn delete_role(
     State(db): State<DbConn>,
     Path((uid, id)): Path<(String, String)>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission(&auth_user, Permission::PERMISSION_MANAGE, &db).await?;
 
     // まず role の存在は確認しておくとレスポンスに role を返せる（現在の実装と同じ振る舞い）
-    if let Some(role) = Role::find_by_id(id.clone()).one(&db).await.unwrap() {
+    if let Some(role) = Role::find_by_id(id.clone()).one(&db).await? {
         // 中間テーブルの該当行を直接削除
+        let txn = db.begin().await?;
         let res = crate::models::user_role::Entity::delete_many()
             .filter(
                 crate::models::user_role::Column::UserId
                     .eq(uid.clone())
                     .and(crate::models::user_role::Column::RoleId.eq(id.clone())),
             )
-            .exec(&db)
-            .await
-            .unwrap();
+            .exec(&txn)
+            .await?;
 
         if res.rows_affected > 0 {
+            AuditEntry::new(AuditAction::RoleRevoke, &uid)
+                .with_before(json!({ "role_id": role.id }))
+                .record(&txn, &auth_user)
+                .await?;
+            txn.commit().await?;
             return Ok((StatusCode::NO_CONTENT, Json(RoleResponse::from(role))));
         } else {
-            return Err(StatusCode::NOT_FOUND);
+            return Err(ApiError::NotFound("ユーザーまたはロール"));
         }
     }
-    Err(StatusCode::NOT_FOUND)
+    Err(ApiError::NotFound("ユーザーまたはロール"))
 }
@@ -1,20 +1,26 @@
-use axum::{
-    Json, Router,
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::get,
-};
+use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
 use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use sea_orm::sea_query::{Expr, ExprTrait, Order, Query, SimpleExpr};
 use sea_orm::*;
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 use crate::{
     constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
+    extract::ValidatedQuery,
     middleware::{auth::AuthUser, permission_check},
-    models::user::{self, Entity as User},
-    routes::users::PublicUserResponse,
+    models::{
+        discord, role,
+        user::{self, Entity as User},
+        user_role,
+    },
+    routes::{
+        roles::RoleResponse, users::DetailedUserResponse, users_sub::discord::DiscordResponse,
+    },
+    search::{self, Backend},
+    utils::filter,
 };
 
 /// =======================
@@ -31,20 +37,70 @@ pub struct SearchMetadata {
 
 #[derive(Serialize, ToSchema)]
 pub struct SearchUsersResponse {
-    pub data: Vec<crate::routes::users::PublicUserResponse>,
+    /// `fields` を指定した場合は指定したフィールド（と `id`）のみを含む
+    #[schema(value_type = Vec<DetailedUserResponse>)]
+    pub data: Vec<serde_json::Value>,
     pub meta: SearchMetadata,
 }
 
+/// `sort` に指定できるフィールド
+const SORTABLE_FIELDS: &[(&str, user::Column)] = &[
+    ("id", user::Column::Id),
+    ("custom_id", user::Column::CustomId),
+    ("name", user::Column::Name),
+    ("name_kana", user::Column::NameKana),
+    ("email", user::Column::Email),
+    ("period", user::Column::Period),
+    ("joined_at", user::Column::JoinedAt),
+    ("created_at", user::Column::CreatedAt),
+    ("updated_at", user::Column::UpdatedAt),
+    ("suspended_until", user::Column::SuspendedUntil),
+];
+
+/// `fields` に指定できるフィールド（`DetailedUserResponse` のフィールド）
+const SELECTABLE_FIELDS: &[&str] = &[
+    "id",
+    "custom_id",
+    "name",
+    "name_kana",
+    "email",
+    "external_email",
+    "birthdate",
+    "email_verified",
+    "period",
+    "joined_at",
+    "is_system",
+    "is_enable",
+    "is_suspended",
+    "suspended_until",
+    "suspended_reason",
+    "created_at",
+    "updated_at",
+    "discords",
+    "roles",
+];
+
 pub fn routes() -> Router<DbConn> {
     Router::new().route("/users/search", get(search_users))
 }
 
-#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+#[derive(Debug, Default, Deserialize, IntoParams, ToSchema, Validate)]
 #[serde(default)]
 pub struct SearchParams {
+    #[validate(range(min = 1))]
     pub page: Option<usize>,
+    #[validate(range(min = 1, max = 100))]
     pub per_page: Option<usize>,
+    /// キーワード検索（名前・読み・メール・カスタムID）
+    /// 検索インデックスが有効な場合はかな・ローマ字・誤字にも一致し、`sort` を省略すると関連度順になる
     pub q: Option<String>,
+    /// `key:value,key:value` 形式でまとめて指定する個別フィルタ
+    /// 空白や `,` を含む値は `"..."` で囲む（例: `is_enable:true,name:"Yui Tanaka"`）
+    pub filter: Option<String>,
+    /// 並び順。`,` 区切りで複数指定でき、先頭に `-` を付けると降順（例: `-created_at,name`）
+    pub sort: Option<String>,
+    /// 返すフィールドを `,` 区切りで指定する（`id` は常に含む。例: `custom_id,name,roles`）
+    pub fields: Option<String>,
     // 個別フィルタ
     pub is_enable: Option<bool>,
     pub is_suspended: Option<bool>,
@@ -58,6 +114,17 @@ pub struct SearchParams {
     pub created_after: Option<String>,
     pub suspended_before: Option<String>,
     pub suspended_after: Option<String>,
+    pub email_verified: Option<bool>,
+    /// パスワードが設定されているかどうか
+    pub has_password: Option<bool>,
+    /// このロールを持つユーザー
+    pub role_id: Option<String>,
+    pub role_custom_id: Option<String>,
+    /// この権限（`PermissionString` の名前）をいずれかのロールで持つユーザー
+    /// `,` 区切りで複数指定するとすべてを持つユーザーに絞り込む（例: `SESSION_MANAGE`）
+    pub permission: Option<String>,
+    /// この Discord ID と連携しているユーザー
+    pub discord_id: Option<String>,
 }
 
 impl SearchParams {
@@ -71,68 +138,202 @@ impl SearchParams {
     pub fn per_page(&self) -> usize {
         self.per_page.unwrap_or(Self::DEFAULT_PER_PAGE)
     }
+
+    /// `filter` の内容を個別フィルタに展開する
+    /// 未知のキーや、個別パラメータと重複するキーは 400 にする.
+    pub fn expand_filter(&mut self) -> Result<(), ApiError> {
+        let Some(input) = self.filter.take() else {
+            return Ok(());
+        };
+        let pairs = filter::parse(&input).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        for (key, value) in pairs {
+            match key.as_str() {
+                "is_enable" => set_once(&mut self.is_enable, &key, parse_bool(&key, &value)?)?,
+                "is_suspended" => {
+                    set_once(&mut self.is_suspended, &key, parse_bool(&key, &value)?)?
+                }
+                "name" => set_once(&mut self.name, &key, value)?,
+                "email" => set_once(&mut self.email, &key, value)?,
+                "external_email" => set_once(&mut self.external_email, &key, value)?,
+                "period" => set_once(&mut self.period, &key, value)?,
+                "joined_before" => set_once(&mut self.joined_before, &key, value)?,
+                "joined_after" => set_once(&mut self.joined_after, &key, value)?,
+                "created_before" => set_once(&mut self.created_before, &key, value)?,
+                "created_after" => set_once(&mut self.created_after, &key, value)?,
+                "suspended_before" => set_once(&mut self.suspended_before, &key, value)?,
+                "suspended_after" => set_once(&mut self.suspended_after, &key, value)?,
+                "email_verified" => {
+                    set_once(&mut self.email_verified, &key, parse_bool(&key, &value)?)?
+                }
+                "has_password" => {
+                    set_once(&mut self.has_password, &key, parse_bool(&key, &value)?)?
+                }
+                "role_id" => set_once(&mut self.role_id, &key, value)?,
+                "role_custom_id" => set_once(&mut self.role_custom_id, &key, value)?,
+                "permission" => set_once(&mut self.permission, &key, value)?,
+                "discord_id" => set_once(&mut self.discord_id, &key, value)?,
+                _ => {
+                    return Err(ApiError::BadRequest(format!(
+                        "filter に未知のキー `{}` が含まれています",
+                        key
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
-impl Default for SearchParams {
-    fn default() -> Self {
-        Self {
-            page: None,
-            per_page: None,
-            q: None,
-            is_enable: None,
-            is_suspended: None,
-            name: None,
-            email: None,
-            external_email: None,
-            period: None,
-            joined_before: None,
-            joined_after: None,
-            created_before: None,
-            created_after: None,
-            suspended_before: None,
-            suspended_after: None,
+/// `sort` を並び順に変換する（未指定の場合は名前の昇順）
+fn parse_sort(sort: Option<&str>) -> Result<Vec<(user::Column, Order)>, ApiError> {
+    let Some(sort) = sort.filter(|s| !s.trim().is_empty()) else {
+        return Ok(vec![(user::Column::Name, Order::Asc)]);
+    };
+    let mut orders: Vec<(user::Column, Order)> = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for item in sort.split(',').map(str::trim) {
+        let (name, order) = match item.strip_prefix('-') {
+            Some(name) => (name, Order::Desc),
+            None => (item.strip_prefix('+').unwrap_or(item), Order::Asc),
+        };
+        let column = SORTABLE_FIELDS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, c)| *c)
+            .ok_or_else(|| ApiError::BadRequest(format!("`{}` では並び替えできません", name)))?;
+        if seen.contains(&name) {
+            return Err(ApiError::BadRequest(format!(
+                "sort のフィールド `{}` が重複しています",
+                name
+            )));
         }
+        seen.push(name);
+        orders.push((column, order));
     }
+    Ok(orders)
 }
 
-#[utoipa::path(
-    get,
-    path = "/users/search",
-    tag = "users",
-    params(SearchParams),
-    responses(
-        (status = 200, description = "ユーザー検索成功", body = SearchUsersResponse),
-        (status = 403, description = "アクセス権限なし")
-    ),
-    security(
-        ("session_token" = [])
+/// `fields` を検証してフィールド名の一覧にする（未指定の場合は `None` = すべて）
+fn parse_fields(fields: Option<&str>) -> Result<Option<Vec<&str>>, ApiError> {
+    let Some(fields) = fields.filter(|s| !s.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let mut selected = vec!["id"];
+    for name in fields.split(',').map(str::trim) {
+        if !SELECTABLE_FIELDS.contains(&name) {
+            return Err(ApiError::BadRequest(format!(
+                "fields に未知のフィールド `{}` が含まれています",
+                name
+            )));
+        }
+        if !selected.contains(&name) {
+            selected.push(name);
+        }
+    }
+    Ok(Some(selected))
+}
+
+/// `roles` と結合した `user_role` のうち、条件に合うロールを持つユーザーに絞り込む
+fn has_role_where(cond: SimpleExpr) -> SimpleExpr {
+    user::Column::Id.in_subquery(
+        Query::select()
+            .column((user_role::Entity, user_role::Column::UserId))
+            .from(user_role::Entity)
+            .inner_join(
+                role::Entity,
+                Expr::col((role::Entity, role::Column::Id))
+                    .equals((user_role::Entity, user_role::Column::RoleId)),
+            )
+            .and_where(cond)
+            .to_owned(),
     )
-)]
-pub async fn search_users(
-    State(db): State<DbConn>,
-    Query(params): Query<SearchParams>,
-    axum::Extension(auth_user): axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
-    permission_check::require_permission(&auth_user, Permission::USER_READ, &db).await?;
+}
 
-    let page = params.page();
-    let per_page = params.per_page();
-    let page_index = page.saturating_sub(1);
+/// `permission` を権限ごとの条件に変換する（未知の名前は 400）
+fn permission_conditions(value: &str) -> Result<Vec<SimpleExpr>, ApiError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .map(|name| {
+            let permission = Permission::from_str(name).ok_or_else(|| {
+                ApiError::BadRequest(format!("`{}` は権限の名前ではありません", name))
+            })?;
+            Ok(has_role_where(
+                Expr::col((role::Entity, role::Column::Permission))
+                    .bit_and(permission.bits() as i32)
+                    .ne(0),
+            ))
+        })
+        .collect()
+}
+
+fn set_once<T>(slot: &mut Option<T>, key: &str, value: T) -> Result<(), ApiError> {
+    if slot.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "`{}` が filter と個別のパラメータの両方で指定されています",
+            key
+        )));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, ApiError> {
+    value.parse().map_err(|_| {
+        ApiError::BadRequest(format!(
+            "`{}` には true または false を指定してください",
+            key
+        ))
+    })
+}
+
+/// `YYYY-MM-DD` または `YYYY-MM-DD HH:MM:SS` を UTC の日時として解釈する
+fn parse_datetime(key: &str, value: &str) -> Result<DateTime<Utc>, ApiError> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .map(|d| Utc.from_utc_datetime(&d))
+        .map_err(|_| {
+            ApiError::BadRequest(format!(
+                "`{}` は YYYY-MM-DD または YYYY-MM-DD HH:MM:SS の形式で指定してください",
+                key
+            ))
+        })
+}
+
+/// 検索条件（`filter` を含む）と並び順を反映したクエリを組み立てる
+/// ページングは呼び出し側で行う（エクスポートでも使う）.
+pub fn build_select(params: &mut SearchParams) -> Result<Select<User>, ApiError> {
+    params.expand_filter()?;
 
     // 検索条件をためる Condition
     let mut cond = Condition::all();
 
-    // q 検索（部分一致：名前・メール・カスタムID）
+    // q 検索
+    // - like: 名前・読み・メール・カスタムIDの部分一致
+    // - index: 検索インデックスで候補を絞り、sort の指定がなければ関連度順に並べる
+    let mut ranked_ids = None;
     if let Some(ref kw) = params.q {
         let kw = kw.trim();
         if !kw.is_empty() {
-            let like_kw = format!("%{}%", kw);
-            cond = cond.add(
-                Condition::any()
-                    .add(user::Column::Name.like(&like_kw))
-                    .add(user::Column::Email.like(&like_kw))
-                    .add(user::Column::CustomId.like(&like_kw)),
-            );
+            match search::index().backend() {
+                Backend::Like => {
+                    let like_kw = format!("%{}%", kw);
+                    cond = cond.add(
+                        Condition::any()
+                            .add(user::Column::Name.like(&like_kw))
+                            .add(user::Column::NameKana.like(&like_kw))
+                            .add(user::Column::Email.like(&like_kw))
+                            .add(user::Column::CustomId.like(&like_kw)),
+                    );
+                }
+                Backend::Index => {
+                    let ids = search::index().search(kw, search::MAX_CANDIDATES);
+                    cond = cond.add(user::Column::Id.is_in(ids.clone()));
+                    ranked_ids = Some(ids);
+                }
+            }
         }
     }
 
@@ -161,65 +362,160 @@ pub async fn search_users(
         cond = cond.add(user::Column::Period.contains(v));
     }
 
-    // ヘルパー関数：文字列から日時をパース
-    let parse_datetime = |s: &str| -> Option<DateTime<Utc>> {
-        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
-            .or_else(|_| {
-                NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap())
-            })
-            .ok()
-            .map(|d| Utc.from_utc_datetime(&d))
-    };
-
     if let Some(ref v) = params.joined_before {
-        if let Some(date) = parse_datetime(v) {
-            cond = cond.add(user::Column::JoinedAt.lt(date));
-        }
+        cond = cond.add(user::Column::JoinedAt.lt(parse_datetime("joined_before", v)?));
     }
 
     if let Some(ref v) = params.joined_after {
-        if let Some(date) = parse_datetime(v) {
-            cond = cond.add(user::Column::JoinedAt.gt(date));
-        }
+        cond = cond.add(user::Column::JoinedAt.gt(parse_datetime("joined_after", v)?));
     }
 
     if let Some(ref v) = params.created_before {
-        if let Some(date) = parse_datetime(v) {
-            cond = cond.add(user::Column::CreatedAt.lt(date));
-        }
+        cond = cond.add(user::Column::CreatedAt.lt(parse_datetime("created_before", v)?));
     }
 
     if let Some(ref v) = params.created_after {
-        if let Some(date) = parse_datetime(v) {
-            cond = cond.add(user::Column::CreatedAt.gt(date));
-        }
+        cond = cond.add(user::Column::CreatedAt.gt(parse_datetime("created_after", v)?));
     }
 
     if let Some(ref v) = params.suspended_before {
-        if let Some(date) = parse_datetime(v) {
-            cond = cond.add(user::Column::SuspendedUntil.lt(date));
-        }
+        cond = cond.add(user::Column::SuspendedUntil.lt(parse_datetime("suspended_before", v)?));
     }
 
     if let Some(ref v) = params.suspended_after {
-        if let Some(date) = parse_datetime(v) {
-            cond = cond.add(user::Column::SuspendedUntil.gt(date));
+        cond = cond.add(user::Column::SuspendedUntil.gt(parse_datetime("suspended_after", v)?));
+    }
+
+    if let Some(v) = params.email_verified {
+        cond = cond.add(user::Column::EmailVerified.eq(v));
+    }
+
+    match params.has_password {
+        Some(true) => cond = cond.add(user::Column::PasswordHash.is_not_null()),
+        Some(false) => cond = cond.add(user::Column::PasswordHash.is_null()),
+        None => {}
+    }
+
+    if let Some(ref v) = params.role_id {
+        cond = cond.add(has_role_where(
+            Expr::col((role::Entity, role::Column::Id)).eq(v),
+        ));
+    }
+
+    if let Some(ref v) = params.role_custom_id {
+        cond = cond.add(has_role_where(
+            Expr::col((role::Entity, role::Column::CustomId)).eq(v),
+        ));
+    }
+
+    if let Some(ref v) = params.permission {
+        for c in permission_conditions(v)? {
+            cond = cond.add(c);
         }
     }
 
+    if let Some(ref v) = params.discord_id {
+        cond = cond.add(
+            user::Column::Id.in_subquery(
+                Query::select()
+                    .column(discord::Column::UserId)
+                    .from(discord::Entity)
+                    .and_where(discord::Column::DiscordId.eq(v))
+                    .to_owned(),
+            ),
+        );
+    }
+
+    let orders = parse_sort(params.sort.as_deref())?;
+
     // 条件を反映
-    let select = User::find().filter(cond).order_by_asc(user::Column::Name);
+    let mut select = User::find_active().filter(cond);
+    match ranked_ids {
+        Some(ids) if params.sort.is_none() && !ids.is_empty() => {
+            let placeholders = vec!["?"; ids.len()].join(", ");
+            select = select.order_by(
+                Expr::cust_with_values(format!("FIELD(`users`.`id`, {})", placeholders), ids),
+                Order::Asc,
+            );
+        }
+        _ => {
+            for (column, order) in orders {
+                select = select.order_by(column, order);
+            }
+        }
+    }
+    // 同じ値のユーザーがページをまたいで重複・欠落しないよう ID で順序を確定させる
+    select = select.order_by_asc(user::Column::Id);
+    Ok(select)
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/search",
+    tag = "users",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "ユーザー検索成功", body = SearchUsersResponse),
+        (status = 400, description = "filter / sort / fields の指定、権限の名前、または日時の形式が不正", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "入力内容に誤りがある", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "リクエストが多すぎる", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(
+        ("session_token" = [])
+    )
+)]
+pub async fn search_users(
+    State(db): State<DbConn>,
+    ValidatedQuery(mut params): ValidatedQuery<SearchParams>,
+    axum::Extension(auth_user): axum::Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    permission_check::require_permission(&auth_user, Permission::USER_READ, &db).await?;
+    let select = build_select(&mut params)?;
+    let fields = parse_fields(params.fields.as_deref())?;
+    let wants = |name: &str| fields.as_ref().is_none_or(|f| f.contains(&name));
+
+    let page = params.page();
+    let per_page = params.per_page();
+    let page_index = page.saturating_sub(1);
 
     // ページング
     let paginator = select.paginate(&db, per_page as u64);
-    let users = paginator
-        .fetch_page(page_index as u64)
-        .await
-        .unwrap_or_default();
-    let total = paginator.num_items().await.unwrap_or(0);
-
-    let total_pages = (total as usize + per_page - 1) / per_page;
-    let data: Vec<PublicUserResponse> = users.into_iter().map(PublicUserResponse::from).collect();
+    let users = paginator.fetch_page(page_index as u64).await?;
+    let total = paginator.num_items().await?;
+
+    // 関連はページ内のユーザーについてまとめて読み込む（ユーザーごとにクエリを発行しない）
+    let discords = if wants("discords") {
+        users.load_many(discord::Entity, &db).await?
+    } else {
+        vec![Vec::new(); users.len()]
+    };
+    let mut roles = if wants("roles") {
+        Some(
+            users
+                .load_many_to_many(role::Entity, user_role::Entity, &db)
+                .await?
+                .into_iter(),
+        )
+    } else {
+        None
+    };
+
+    let total_pages = (total as usize).div_ceil(per_page);
+    let mut data = Vec::with_capacity(users.len());
+    for (user, discords) in users.into_iter().zip(discords) {
+        let mut detailed = DetailedUserResponse::from(user);
+        detailed.discords = discords.into_iter().map(DiscordResponse::from).collect();
+        detailed.roles = roles
+            .as_mut()
+            .and_then(Iterator::next)
+            .map(|roles| roles.into_iter().map(RoleResponse::from).collect());
+        let mut value = serde_json::to_value(detailed).map_err(anyhow::Error::from)?;
+        if let (Some(fields), Some(object)) = (&fields, value.as_object_mut()) {
+            object.retain(|key, _| fields.contains(&key.as_str()));
+        }
+        data.push(value);
+    }
 
     Ok((
         StatusCode::OK,
@@ -8,7 +8,9 @@ use axum::{
 use sea_orm::*;
 
 use crate::{
+    audit::AuditEntry,
     constants::permissions::Permission,
+    error::{ApiError, ProblemDetails},
     middleware::{auth::AuthUser, permission_check},
     models::{
         session::{self, Entity as Session},
@@ -19,9 +21,9 @@ use crate::{
     },
 };
 
-/// =======================
-/// Router
-/// =======================
+// =======================
+// Router
+// =======================
 
 pub fn routes() -> Router<DbConn> {
     Router::new()
@@ -32,9 +34,9 @@ pub fn routes() -> Router<DbConn> {
         )
 }
 
-/// =======================
-/// handlers
-/// =======================
+// =======================
+// handlers
+// =======================
 
 /// ユーザーの全セッション取得
 #[utoipa::path(
@@ -46,8 +48,8 @@ pub fn routes() -> Router<DbConn> {
     ),
     responses(
         (status = 200, description = "ユーザーのセッション一覧取得成功"),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "ユーザーが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "ユーザーが見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -57,17 +59,17 @@ pub async fn get_all_sessions(
     State(db): State<DbConn>,
     Path(uid): Path<String>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission_or_self(&auth_user, Permission::SESSION_MANAGE, &uid, &db)
         .await?;
 
-    let user = User::find_by_id(&uid).one(&db).await.unwrap();
+    let user = User::find_active_by_id(&uid).one(&db).await?;
     let Some(user) = user else {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::NotFound("ユーザー"));
     };
 
     // セッション取得
-    let sessions = user.find_related(Session).all(&db).await.unwrap();
+    let sessions = user.find_related(Session).all(&db).await?;
 
     // DTO 変換
     let responses: Vec<SessionResponse> = sessions
@@ -90,7 +92,7 @@ pub async fn get_all_sessions(
         })
         .collect();
 
-    Ok((StatusCode::OK, Json(ApiResponse { data: responses })))
+    Ok((StatusCode::OK, Json(ApiResponse::new(responses))))
 }
 
 /// 特定セッション取得（単体なので ApiResponse で包まない）
@@ -104,8 +106,8 @@ pub async fn get_all_sessions(
     ),
     responses(
         (status = 200, description = "セッション取得成功", body = crate::routes::sessions::SessionResponse),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "セッションが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "セッションが見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -115,7 +117,7 @@ pub async fn get_session(
     State(db): State<DbConn>,
     Path((uid, id)): Path<(String, String)>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission_or_self(&auth_user, Permission::SESSION_MANAGE, &uid, &db)
         .await?;
 
@@ -124,13 +126,12 @@ pub async fn get_session(
         .filter(session::Column::UserId.eq(&uid))
         .find_with_related(User)
         .all(&db)
-        .await
-        .unwrap();
+        .await?;
 
     let Some((session, mut users)) = joined.into_iter().next() else {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::NotFound("セッション"));
     };
-    let user = users.pop().unwrap();
+    let user = users.pop().ok_or(ApiError::NotFound("ユーザー"))?;
 
     let response = SessionResponse {
         id: session.id,
@@ -163,8 +164,8 @@ pub async fn get_session(
     ),
     responses(
         (status = 204, description = "セッション削除成功"),
-        (status = 403, description = "アクセス権限なし"),
-        (status = 404, description = "セッションが見つからない")
+        (status = 403, description = "アクセス権限なし", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "セッションが見つからない", body = ProblemDetails, content_type = "application/problem+json")
     ),
     security(
         ("session_token" = [])
@@ -174,7 +175,7 @@ pub async fn delete_session(
     State(db): State<DbConn>,
     Path((uid, id)): Path<(String, String)>,
     auth_user: axum::Extension<AuthUser>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, ApiError> {
     permission_check::require_permission_or_self(&auth_user, Permission::SESSION_MANAGE, &uid, &db)
         .await?;
 
@@ -182,15 +183,19 @@ pub async fn delete_session(
         .filter(session::Column::Id.eq(id))
         .filter(session::Column::UserId.eq(uid))
         .one(&db)
-        .await
-        .unwrap();
+        .await?;
 
     let Some(session) = found else {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::NotFound("セッション"));
     };
 
-    let am: session::ActiveModel = session.into();
-    am.delete(&db).await.unwrap();
+    let am: session::ActiveModel = session.clone().into();
+    let txn = db.begin().await?;
+    am.delete(&txn).await?;
+    AuditEntry::session_deleted(&session)
+        .record(&txn, &auth_user)
+        .await?;
+    txn.commit().await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
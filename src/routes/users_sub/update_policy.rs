@@ -0,0 +1,69 @@
+use crate::{
+    constants::permissions::Permission,
+    db::DbConn,
+    error::ApiError,
+    middleware::{auth::AuthUser, permission_check},
+};
+
+/// 本人が書き換え可能なフィールド
+const SELF_SERVICE_FIELDS: &[&str] = &["name", "name_kana", "external_email"];
+
+/// システム（API キー）からのみ書き換え可能なフィールド
+const SYSTEM_ONLY_FIELDS: &[&str] = &["is_system"];
+
+/// ユーザー情報を更新しようとしている主体
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdatePrincipal {
+    /// API キーによるシステムからの呼び出し
+    System,
+    /// USER_UPDATE 権限を持つユーザー
+    Admin,
+    /// 権限を持たない本人
+    SelfService,
+}
+
+impl UpdatePrincipal {
+    /// 対象ユーザーに対する主体の種別を判定する（本人でも権限保持者でもなければ 403）
+    pub async fn resolve(
+        auth_user: &AuthUser,
+        target_user_id: &str,
+        db: &DbConn,
+    ) -> Result<Self, ApiError> {
+        if auth_user.is_system.unwrap_or(false) {
+            return Ok(UpdatePrincipal::System);
+        }
+        if permission_check::require_permission(auth_user, Permission::USER_UPDATE, db)
+            .await
+            .is_ok()
+        {
+            return Ok(UpdatePrincipal::Admin);
+        }
+        if auth_user.user_id == target_user_id {
+            return Ok(UpdatePrincipal::SelfService);
+        }
+        Err(ApiError::Forbidden)
+    }
+
+    /// 指定のフィールドを書き換えられるか
+    pub fn may_change(&self, field: &str) -> bool {
+        match self {
+            UpdatePrincipal::System => true,
+            UpdatePrincipal::Admin => !SYSTEM_ONLY_FIELDS.contains(&field),
+            UpdatePrincipal::SelfService => SELF_SERVICE_FIELDS.contains(&field),
+        }
+    }
+
+    /// 変更しようとしているフィールドのうち、許可されていないものがあれば 403 にする
+    pub fn check(&self, changed: &[&'static str]) -> Result<(), ApiError> {
+        let forbidden: Vec<&'static str> = changed
+            .iter()
+            .copied()
+            .filter(|f| !self.may_change(f))
+            .collect();
+        if forbidden.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::ForbiddenFields(forbidden))
+        }
+    }
+}
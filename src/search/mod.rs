@@ -0,0 +1,300 @@
+pub mod normalize;
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock, RwLock},
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDateTime;
+use sea_orm::*;
+use tracing::{error, info};
+
+use crate::{db::DbConn, models::user};
+
+use normalize::{edit_distance, hiragana_to_romaji, normalize, romaji_to_hiragana};
+
+/// 1回の検索で返す候補の上限
+/// これを超えて一致した場合は結果を切り詰めずにエラーとする.
+pub const MAX_CANDIDATES: usize = 1000;
+
+/// 差分の取り込みで、前回の取り込み位置より遡って読み直す秒数
+/// コミットの遅れやサーバー間の時刻のずれで取りこぼさないようにする.
+const SYNC_OVERLAP_SECS: i64 = 60;
+
+/// 部分一致を試す最短の語長（これより短い語は前方一致のみ）
+const MIN_INFIX_LEN: usize = 2;
+
+/// `q` 検索の方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// 名前・メール・カスタムIDの部分一致（`LIKE`）
+    Like,
+    /// プロセス内の検索インデックス（読み・ローマ字・表記ゆれ・誤字に対応し、関連度順）
+    Index,
+}
+
+/// 1ユーザー分の検索対象の語（正規化済み）
+struct Document {
+    terms: Vec<Vec<char>>,
+}
+
+impl Document {
+    fn from_user(user: &user::Model) -> Self {
+        let mut terms: Vec<String> = Vec::new();
+        // 名前は全体と空白区切りの各部分（姓・名）を入れ、
+        // ローマ字ならかなに、かなならローマ字にしたものも入れる
+        for text in std::iter::once(user.name.as_str()).chain(user.name_kana.as_deref()) {
+            terms.push(normalize(text));
+            for part in text.split_whitespace() {
+                let part = normalize(part);
+                terms.extend(romaji_to_hiragana(&part));
+                terms.extend(hiragana_to_romaji(&part));
+                terms.push(part);
+            }
+        }
+        terms.push(normalize(&user.custom_id));
+        terms.push(normalize(&user.email));
+        terms.extend(user.email.split('@').next().map(normalize));
+
+        terms.retain(|t| !t.is_empty());
+        terms.sort();
+        terms.dedup();
+        Self {
+            terms: terms.into_iter().map(|t| t.chars().collect()).collect(),
+        }
+    }
+
+    /// 検索語 1 つに対する一致度（0.0〜1.0、一致しなければ `None`）
+    fn score_word(&self, word: &[char]) -> Option<f64> {
+        let typo_limit = match word.len() {
+            0..=2 => 0,
+            3..=5 => 1,
+            _ => 2,
+        };
+        self.terms
+            .iter()
+            .filter_map(|term| {
+                if term == word {
+                    Some(1.0)
+                } else if term.starts_with(word) {
+                    Some(0.9)
+                } else if word.len() >= MIN_INFIX_LEN && term.windows(word.len()).any(|w| w == word)
+                {
+                    Some(0.7)
+                } else if typo_limit > 0 {
+                    // 語全体との編集距離で誤字を許容する
+                    // 4文字以上なら同じ長さの先頭部分とも比べる（入力途中の誤字）
+                    let prefix = (word.len() >= 4).then(|| &term[..term.len().min(word.len())]);
+                    std::iter::once(term.as_slice())
+                        .chain(prefix)
+                        .filter_map(|t| edit_distance(word, t, typo_limit))
+                        .min()
+                        .map(|d| 0.6 - 0.1 * d as f64)
+                } else {
+                    None
+                }
+            })
+            .max_by(f64::total_cmp)
+    }
+}
+
+/// 検索語の表記の候補（正規化したものと、ローマ字をかなにしたもの）
+fn word_variants(word: &str) -> Vec<Vec<char>> {
+    let normalized = normalize(word);
+    let mut variants = vec![normalized.chars().collect::<Vec<_>>()];
+    if let Some(kana) = romaji_to_hiragana(&normalized) {
+        variants.push(kana.chars().collect());
+    }
+    variants
+}
+
+/// プロセス内のユーザー検索インデックス
+/// NOTE: 全件を走査するため、数万件程度までを想定している.
+pub struct SearchIndex {
+    backend: Backend,
+    refresh_interval: Duration,
+    sync_interval: Duration,
+    documents: RwLock<HashMap<String, Document>>,
+    /// 差分の取り込みを済ませた `updated_at` の位置
+    synced_until: Mutex<Option<NaiveDateTime>>,
+}
+
+static INDEX: OnceLock<SearchIndex> = OnceLock::new();
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self {
+            backend: Backend::Like,
+            refresh_interval: Duration::from_secs(300),
+            sync_interval: Duration::from_secs(10),
+            documents: RwLock::new(HashMap::new()),
+            synced_until: Mutex::new(None),
+        }
+    }
+}
+
+/// 環境変数からユーザー検索を初期化する
+/// - `USER_SEARCH_BACKEND`: `like` / `index`（既定: `like`）
+/// - `USER_SEARCH_REFRESH_SECS`: インデックスを DB から作り直す間隔（既定: 300）
+/// - `USER_SEARCH_SYNC_SECS`: 他のサーバーでの変更を `updated_at` から取り込む間隔（既定: 10）
+pub fn init_from_env() -> anyhow::Result<()> {
+    let default = SearchIndex::default();
+    let backend = match std::env::var("USER_SEARCH_BACKEND").as_deref() {
+        Ok("like") | Err(_) => Backend::Like,
+        Ok("index") => Backend::Index,
+        Ok(other) => anyhow::bail!("Unknown USER_SEARCH_BACKEND: {}", other),
+    };
+    let secs = |key: &str, default: Duration| -> anyhow::Result<Duration> {
+        match std::env::var(key) {
+            Ok(v) => match v.parse() {
+                Ok(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+                _ => anyhow::bail!("{} must be a positive integer", key),
+            },
+            Err(_) => Ok(default),
+        }
+    };
+    let refresh_interval = secs("USER_SEARCH_REFRESH_SECS", default.refresh_interval)?;
+    let sync_interval = secs("USER_SEARCH_SYNC_SECS", default.sync_interval)?;
+    info!("User search backend: {:?}", backend);
+    let index = SearchIndex {
+        backend,
+        refresh_interval,
+        sync_interval,
+        ..default
+    };
+    if INDEX.set(index).is_err() {
+        info!("User search is already initialized");
+    }
+    Ok(())
+}
+
+/// 登録済みの検索インデックスを取得する（未登録の場合は `LIKE` 検索）
+pub fn index() -> &'static SearchIndex {
+    INDEX.get_or_init(SearchIndex::default)
+}
+
+impl SearchIndex {
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// DB の全ユーザーからインデックスを作り直す
+    pub async fn rebuild(&self, db: &DbConn) -> Result<(), DbErr> {
+        let started_at = chrono::Utc::now().naive_utc();
+        let mut documents = HashMap::new();
+        let mut pages = user::Entity::find_active()
+            .order_by_asc(user::Column::Id)
+            .paginate(db, 1000);
+        while let Some(users) = pages.fetch_and_next().await? {
+            for user in users {
+                documents.insert(user.id.clone(), Document::from_user(&user));
+            }
+        }
+        *self.documents.write().unwrap() = documents;
+        *self.synced_until.lock().unwrap() = Some(started_at);
+        Ok(())
+    }
+
+    /// 前回の取り込み以降に更新されたユーザーをインデックスに反映する
+    /// 他のサーバーで行われた変更もここで反映される. 完全に削除されたユーザーは作り直しまで残るが、
+    /// 検索結果は DB で絞り込むため返ることはない.
+    pub async fn sync(&self, db: &DbConn) -> Result<usize, DbErr> {
+        let Some(since) = *self.synced_until.lock().unwrap() else {
+            return Ok(0);
+        };
+        let users = user::Entity::find()
+            .filter(
+                user::Column::UpdatedAt.gte(since - chrono::Duration::seconds(SYNC_OVERLAP_SECS)),
+            )
+            .order_by_asc(user::Column::UpdatedAt)
+            .all(db)
+            .await?;
+
+        let mut latest = since;
+        for user in &users {
+            if user.deleted_at.is_some() {
+                self.remove(&user.id);
+            } else {
+                self.upsert(user);
+            }
+            latest = latest.max(user.updated_at.unwrap_or(latest));
+        }
+        let mut synced_until = self.synced_until.lock().unwrap();
+        *synced_until = (*synced_until).max(Some(latest));
+        Ok(users.len())
+    }
+
+    /// ユーザーの作成・更新をインデックスに反映する
+    pub fn upsert(&self, user: &user::Model) {
+        if self.backend != Backend::Index {
+            return;
+        }
+        self.documents
+            .write()
+            .unwrap()
+            .insert(user.id.clone(), Document::from_user(user));
+    }
+
+    /// 削除したユーザーをインデックスから取り除く
+    pub fn remove(&self, user_id: &str) {
+        if self.backend != Backend::Index {
+            return;
+        }
+        self.documents.write().unwrap().remove(user_id);
+    }
+
+    /// 空白区切りの各語がすべて一致するユーザーを関連度の高い順に最大 `limit` 件返す
+    pub fn search(&self, query: &str, limit: usize) -> Vec<String> {
+        let words: Vec<Vec<Vec<char>>> = query.split_whitespace().map(word_variants).collect();
+        if words.is_empty() {
+            return Vec::new();
+        }
+        let documents = self.documents.read().unwrap();
+        let mut hits: Vec<(String, f64)> = documents
+            .iter()
+            .filter_map(|(id, doc)| {
+                let mut total = 0.0;
+                for variants in &words {
+                    total += variants
+                        .iter()
+                        .filter_map(|w| doc.score_word(w))
+                        .max_by(f64::total_cmp)?;
+                }
+                Some((id.clone(), total / words.len() as f64))
+            })
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hits.truncate(limit);
+        hits.into_iter().map(|(id, _)| id).collect()
+    }
+}
+
+/// 起動時にインデックスを作り、以降は短い間隔で差分を取り込みつつ、一定間隔で DB から作り直す
+/// 他のサーバーでの変更は差分の取り込みで、完全な削除や `updated_at` を変えない
+/// DB の直接編集は作り直しで反映される.
+pub async fn run_worker(db: DbConn) {
+    let index = index();
+    if index.backend != Backend::Index {
+        return;
+    }
+    let mut interval = tokio::time::interval(index.sync_interval);
+    let mut rebuilt_at: Option<Instant> = None;
+    loop {
+        interval.tick().await;
+        if rebuilt_at.is_none_or(|t| t.elapsed() >= index.refresh_interval) {
+            match index.rebuild(&db).await {
+                Ok(()) => {
+                    rebuilt_at = Some(Instant::now());
+                    info!(
+                        "Rebuilt user search index: {} users",
+                        index.documents.read().unwrap().len()
+                    );
+                }
+                Err(e) => error!("Failed to rebuild user search index: {:?}", e),
+            }
+        } else if let Err(e) = index.sync(&db).await {
+            error!("Failed to sync user search index: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,387 @@
+/// 半角カタカナ（U+FF66〜U+FF9D）に対応する全角カタカナ
+const HALFWIDTH_KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+/// ローマ字とひらがなの対応
+/// ローマ字は長いものから順に照合し、ひらがなからの変換には先に現れる表記（ヘボン式）を使う.
+const ROMAJI: &[(&str, &str)] = &[
+    ("kya", "きゃ"),
+    ("kyu", "きゅ"),
+    ("kyo", "きょ"),
+    ("gya", "ぎゃ"),
+    ("gyu", "ぎゅ"),
+    ("gyo", "ぎょ"),
+    ("sha", "しゃ"),
+    ("shi", "し"),
+    ("shu", "しゅ"),
+    ("she", "しぇ"),
+    ("sho", "しょ"),
+    ("sya", "しゃ"),
+    ("syu", "しゅ"),
+    ("syo", "しょ"),
+    ("cha", "ちゃ"),
+    ("chi", "ち"),
+    ("chu", "ちゅ"),
+    ("che", "ちぇ"),
+    ("cho", "ちょ"),
+    ("tya", "ちゃ"),
+    ("tyu", "ちゅ"),
+    ("tyo", "ちょ"),
+    ("tsu", "つ"),
+    ("nya", "にゃ"),
+    ("nyu", "にゅ"),
+    ("nyo", "にょ"),
+    ("hya", "ひゃ"),
+    ("hyu", "ひゅ"),
+    ("hyo", "ひょ"),
+    ("bya", "びゃ"),
+    ("byu", "びゅ"),
+    ("byo", "びょ"),
+    ("pya", "ぴゃ"),
+    ("pyu", "ぴゅ"),
+    ("pyo", "ぴょ"),
+    ("mya", "みゃ"),
+    ("myu", "みゅ"),
+    ("myo", "みょ"),
+    ("rya", "りゃ"),
+    ("ryu", "りゅ"),
+    ("ryo", "りょ"),
+    ("ja", "じゃ"),
+    ("ji", "じ"),
+    ("ju", "じゅ"),
+    ("je", "じぇ"),
+    ("jo", "じょ"),
+    ("jya", "じゃ"),
+    ("jyu", "じゅ"),
+    ("jyo", "じょ"),
+    ("zya", "じゃ"),
+    ("zyu", "じゅ"),
+    ("zyo", "じょ"),
+    ("ka", "か"),
+    ("ki", "き"),
+    ("ku", "く"),
+    ("ke", "け"),
+    ("ko", "こ"),
+    ("ga", "が"),
+    ("gi", "ぎ"),
+    ("gu", "ぐ"),
+    ("ge", "げ"),
+    ("go", "ご"),
+    ("sa", "さ"),
+    ("si", "し"),
+    ("su", "す"),
+    ("se", "せ"),
+    ("so", "そ"),
+    ("za", "ざ"),
+    ("zi", "じ"),
+    ("zu", "ず"),
+    ("ze", "ぜ"),
+    ("zo", "ぞ"),
+    ("ta", "た"),
+    ("ti", "ち"),
+    ("tu", "つ"),
+    ("te", "て"),
+    ("to", "と"),
+    ("da", "だ"),
+    ("di", "ぢ"),
+    ("du", "づ"),
+    ("de", "で"),
+    ("do", "ど"),
+    ("na", "な"),
+    ("ni", "に"),
+    ("nu", "ぬ"),
+    ("ne", "ね"),
+    ("no", "の"),
+    ("ha", "は"),
+    ("hi", "ひ"),
+    ("fu", "ふ"),
+    ("hu", "ふ"),
+    ("he", "へ"),
+    ("ho", "ほ"),
+    ("ba", "ば"),
+    ("bi", "び"),
+    ("bu", "ぶ"),
+    ("be", "べ"),
+    ("bo", "ぼ"),
+    ("pa", "ぱ"),
+    ("pi", "ぴ"),
+    ("pu", "ぷ"),
+    ("pe", "ぺ"),
+    ("po", "ぽ"),
+    ("ma", "ま"),
+    ("mi", "み"),
+    ("mu", "む"),
+    ("me", "め"),
+    ("mo", "も"),
+    ("ya", "や"),
+    ("yu", "ゆ"),
+    ("yo", "よ"),
+    ("ra", "ら"),
+    ("ri", "り"),
+    ("ru", "る"),
+    ("re", "れ"),
+    ("ro", "ろ"),
+    ("wa", "わ"),
+    ("wo", "を"),
+    ("a", "あ"),
+    ("i", "い"),
+    ("u", "う"),
+    ("e", "え"),
+    ("o", "お"),
+];
+
+/// 半角カタカナを全角に変換する（濁点・半濁点は直前の文字と合成する）
+fn widen_katakana(c: char, out: &mut String) {
+    match c {
+        '\u{FF66}'..='\u{FF9D}' => {
+            let index = c as usize - 0xFF66;
+            out.extend(HALFWIDTH_KATAKANA.chars().nth(index));
+        }
+        // 濁点: か → が など（コードポイントが 1 つ後ろ）
+        '\u{FF9E}' => match out.pop() {
+            Some('ウ') => out.push('ヴ'),
+            Some(p) if is_voiceable(p) => out.extend(char::from_u32(p as u32 + 1)),
+            Some(p) => out.push(p),
+            None => {}
+        },
+        // 半濁点: は → ぱ（コードポイントが 2 つ後ろ）
+        '\u{FF9F}' => match out.pop() {
+            Some(p) if ('ハ'..='ホ').contains(&p) && (p as u32 - 'ハ' as u32).is_multiple_of(3) => {
+                out.extend(char::from_u32(p as u32 + 2))
+            }
+            Some(p) => out.push(p),
+            None => {}
+        },
+        _ => out.push(c),
+    }
+}
+
+/// 濁点を付けられるカタカナか（カ〜ト・ハ〜ホの清音）
+fn is_voiceable(c: char) -> bool {
+    match c {
+        'カ'..='ヂ' => (c as u32 - 'カ' as u32).is_multiple_of(2),
+        'ツ' | 'テ' | 'ト' => true,
+        'ハ'..='ホ' => (c as u32 - 'ハ' as u32).is_multiple_of(3),
+        _ => false,
+    }
+}
+
+/// 検索用に文字列を正規化する
+///
+/// - 全角英数字・記号を半角に、半角カタカナを全角にそろえる
+/// - カタカナをひらがなに、英字を小文字にそろえる
+/// - 空白と区切り記号（`・` `.` `-` `_`）を取り除く
+pub fn normalize(input: &str) -> String {
+    let mut widened = String::with_capacity(input.len());
+    for c in input.chars() {
+        let c = match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            '\u{3000}' => ' ',
+            _ => c,
+        };
+        widen_katakana(c, &mut widened);
+    }
+    widened
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '・' | '･' | '.' | '-' | '_'))
+        .map(|c| match c {
+            // ァ〜ヶ をひらがなへ（ー はそのまま）
+            'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// ローマ字（ヘボン式・訓令式）をひらがなに変換する
+/// 英字以外を含む場合や、ローマ字として読めない場合は `None` を返す.
+pub fn romaji_to_hiragana(input: &str) -> Option<String> {
+    let input = input.to_ascii_lowercase();
+    if input.is_empty() || !input.bytes().all(|b| b.is_ascii_lowercase()) {
+        return None;
+    }
+    let bytes = input.as_bytes();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &input[i..];
+        let next = bytes.get(i + 1).copied();
+        // 子音の重複は促音（ただし nn は ん）
+        if next == Some(bytes[i]) && bytes[i] != b'n' && !b"aiueo".contains(&bytes[i]) {
+            out.push('っ');
+            i += 1;
+            continue;
+        }
+        // n の後に母音・y が続かなければ ん（nn の後に母音が続く場合は2つ目の n を次の音に使う）
+        let is_vowel_or_y =
+            |b: Option<u8>| matches!(b, Some(b'a' | b'i' | b'u' | b'e' | b'o' | b'y'));
+        if bytes[i] == b'n' && !is_vowel_or_y(next) {
+            out.push('ん');
+            i += if next == Some(b'n') && !is_vowel_or_y(bytes.get(i + 2).copied()) {
+                2
+            } else {
+                1
+            };
+            continue;
+        }
+        let (romaji, kana) = ROMAJI.iter().find(|(r, _)| rest.starts_with(r))?;
+        out.push_str(kana);
+        i += romaji.len();
+    }
+    Some(out)
+}
+
+/// ひらがなをローマ字（ヘボン式）に変換する
+/// ひらがな以外を含む場合は `None` を返す.
+pub fn hiragana_to_romaji(input: &str) -> Option<String> {
+    let chars: Vec<char> = input.chars().collect();
+    if chars.is_empty() {
+        return None;
+    }
+    let mut out = String::new();
+    let mut double_next = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            'っ' => {
+                double_next = true;
+                i += 1;
+                continue;
+            }
+            'ん' => {
+                out.push('n');
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        // 拗音（きゃ など）を優先する
+        let (kana_len, romaji) = [2, 1]
+            .into_iter()
+            .filter(|len| i + len <= chars.len())
+            .find_map(|len| {
+                let kana: String = chars[i..i + len].iter().collect();
+                ROMAJI
+                    .iter()
+                    .find(|(_, k)| *k == kana)
+                    .map(|(r, _)| (len, *r))
+            })?;
+        if std::mem::take(&mut double_next) {
+            out.extend(romaji.chars().next());
+        }
+        out.push_str(romaji);
+        i += kana_len;
+    }
+    Some(out)
+}
+
+/// 文字単位の編集距離（隣り合う文字の入れ替えは 1 と数える）（`limit` を超えた時点で打ち切り、`None` を返す）
+pub fn edit_distance(a: &[char], b: &[char], limit: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+    let mut before_prev: Vec<usize> = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for i in 0..a.len() {
+        curr[0] = i + 1;
+        for j in 0..b.len() {
+            let cost = usize::from(a[i] != b[j]);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                curr[j + 1] = curr[j + 1].min(before_prev[j - 1] + 1);
+            }
+        }
+        if curr.iter().min().is_some_and(|&m| m > limit) {
+            return None;
+        }
+        std::mem::swap(&mut before_prev, &mut prev);
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    Some(prev[b.len()]).filter(|&d| d <= limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: &str, b: &str, limit: usize) -> Option<usize> {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        edit_distance(&a, &b, limit)
+    }
+
+    #[test]
+    fn normalizes_width_and_kana() {
+        assert_eq!(normalize("ﾕｲ"), "ゆい");
+        assert_eq!(normalize("ﾀﾞﾝｺﾞ"), "だんご");
+        assert_eq!(normalize("ﾊﾟﾝ"), "ぱん");
+        assert_eq!(normalize("ユイ"), "ゆい");
+        assert_eq!(normalize("ＹＵＩ"), "yui");
+    }
+
+    #[test]
+    fn removes_separators() {
+        assert_eq!(normalize("Sato Yui"), "satoyui");
+        assert_eq!(normalize("さとう・ゆい"), "さとうゆい");
+        assert_eq!(normalize("yui.sato_01-a"), "yuisato01a");
+    }
+
+    #[test]
+    fn converts_romaji_to_hiragana() {
+        assert_eq!(romaji_to_hiragana("yui").as_deref(), Some("ゆい"));
+        assert_eq!(romaji_to_hiragana("kitte").as_deref(), Some("きって"));
+        assert_eq!(
+            romaji_to_hiragana("shinjuku").as_deref(),
+            Some("しんじゅく")
+        );
+        assert_eq!(
+            romaji_to_hiragana("sinzyuku").as_deref(),
+            Some("しんじゅく")
+        );
+        assert_eq!(romaji_to_hiragana("kyouko").as_deref(), Some("きょうこ"));
+        assert_eq!(romaji_to_hiragana("yui1"), None);
+        assert_eq!(romaji_to_hiragana("xqz"), None);
+    }
+
+    #[test]
+    fn converts_n_to_syllabic_n() {
+        assert_eq!(romaji_to_hiragana("kenta").as_deref(), Some("けんた"));
+        assert_eq!(romaji_to_hiragana("ken").as_deref(), Some("けん"));
+        assert_eq!(romaji_to_hiragana("kenn").as_deref(), Some("けん"));
+        assert_eq!(romaji_to_hiragana("kanna").as_deref(), Some("かんな"));
+        assert_eq!(romaji_to_hiragana("kenichi").as_deref(), Some("けにち"));
+        assert_eq!(
+            romaji_to_hiragana("konnichiha").as_deref(),
+            Some("こんにちは")
+        );
+        assert_eq!(romaji_to_hiragana("junya").as_deref(), Some("じゅにゃ"));
+        assert_eq!(romaji_to_hiragana("jun'ya"), None);
+    }
+
+    #[test]
+    fn converts_hiragana_to_romaji() {
+        assert_eq!(hiragana_to_romaji("ゆい").as_deref(), Some("yui"));
+        assert_eq!(hiragana_to_romaji("きって").as_deref(), Some("kitte"));
+        assert_eq!(
+            hiragana_to_romaji("しんじゅく").as_deref(),
+            Some("shinjuku")
+        );
+        assert_eq!(hiragana_to_romaji("きょうこ").as_deref(), Some("kyouko"));
+        assert_eq!(hiragana_to_romaji("ゆい1"), None);
+    }
+
+    #[test]
+    fn counts_edits() {
+        assert_eq!(distance("suzuki", "suzuki", 2), Some(0));
+        assert_eq!(distance("suzuky", "suzuki", 2), Some(1));
+        assert_eq!(distance("suzki", "suzuki", 2), Some(1));
+        assert_eq!(distance("tanaka", "sato", 2), None);
+    }
+
+    #[test]
+    fn counts_transposition_as_one_edit() {
+        assert_eq!(distance("suzuik", "suzuki", 1), Some(1));
+        assert_eq!(distance("ytnaaka", "tanaka", 2), Some(2));
+        assert_eq!(distance("ab", "ba", 0), None);
+    }
+}
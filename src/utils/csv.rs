@@ -0,0 +1,187 @@
+/// CSV の構文エラー
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum CsvError {
+    #[error("{0} 行目の引用符が閉じられていません")]
+    UnterminatedQuote(usize),
+    #[error("{0} 行目の引用符の後に余分な文字があります")]
+    TrailingCharacters(usize),
+}
+
+/// RFC 4180 形式の CSV をレコードごとに分解する
+///
+/// - `"..."` で囲んだフィールドには `,` や改行を含められ、`""` で `"` を表す
+/// - 改行は `\n` と `\r\n` のどちらでもよい。先頭の BOM と空行は無視する
+pub fn parse(input: &str) -> Result<Vec<Vec<String>>, CsvError> {
+    let input = input.strip_prefix('\u{FEFF}').unwrap_or(input);
+    let mut records = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut chars = input.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => return Err(CsvError::UnterminatedQuote(start)),
+                    }
+                }
+                if !matches!(chars.peek(), None | Some(',' | '\r' | '\n')) {
+                    return Err(CsvError::TrailingCharacters(line));
+                }
+            }
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                line += 1;
+                record.push(std::mem::take(&mut field));
+                if !(record.len() == 1 && record[0].is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    record.push(field);
+    if !(record.len() == 1 && record[0].is_empty()) {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// 1レコード分を CSV の行として書き出す（必要なフィールドだけ `"..."` で囲む）
+pub fn write_record<'a>(out: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// 表計算ソフトで数式として解釈されるセルの先頭文字
+const FORMULA_PREFIXES: &[char] = &['=', '+', '-', '@', '\t', '\r'];
+
+/// 数式として解釈されないよう、先頭に `'` を付ける（CSV インジェクション対策）
+///
+/// 元から `'` で始まる値にも付けて、`unescape_formula` で元に戻せるようにする.
+pub fn escape_formula(cell: String) -> String {
+    if cell.starts_with(FORMULA_PREFIXES) || cell.starts_with('\'') {
+        format!("'{}", cell)
+    } else {
+        cell
+    }
+}
+
+/// `escape_formula` で付けた `'` を取り除く
+pub fn unescape_formula(cell: &str) -> &str {
+    match cell.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(FORMULA_PREFIXES) || rest.starts_with('\'') => rest,
+        _ => cell,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_quoted_fields() {
+        let input = "a,\"b,c\",\"say \"\"hi\"\"\"\n\"line1\nline2\",,\"\"\n";
+        assert_eq!(
+            parse(input),
+            Ok(vec![
+                record(&["a", "b,c", "say \"hi\""]),
+                record(&["line1\nline2", "", ""]),
+            ])
+        );
+    }
+
+    #[test]
+    fn accepts_crlf_bom_and_blank_lines() {
+        let input = "\u{FEFF}a,b\r\n\r\n\"x\r\ny\",z\r\nlast,row";
+        assert_eq!(
+            parse(input),
+            Ok(vec![
+                record(&["a", "b"]),
+                record(&["x\r\ny", "z"]),
+                record(&["last", "row"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_malformed_quotes() {
+        assert_eq!(
+            parse("a,b\n\"open,c\n"),
+            Err(CsvError::UnterminatedQuote(2))
+        );
+        assert_eq!(
+            parse("a,b\n\"x\"y,c\n"),
+            Err(CsvError::TrailingCharacters(2))
+        );
+    }
+
+    #[test]
+    fn writes_quoted_fields_with_crlf() {
+        let mut out = String::new();
+        write_record(&mut out, ["plain", "a,b", "say \"hi\"", "l1\nl2"]);
+        assert_eq!(out, "plain,\"a,b\",\"say \"\"hi\"\"\",\"l1\nl2\"\r\n");
+    }
+
+    #[test]
+    fn escapes_formulas() {
+        assert_eq!(escape_formula("=1+1".to_string()), "'=1+1");
+        assert_eq!(escape_formula("@SUM(A1)".to_string()), "'@SUM(A1)");
+        assert_eq!(escape_formula("'quoted".to_string()), "''quoted");
+        assert_eq!(escape_formula("plain".to_string()), "plain");
+    }
+
+    #[test]
+    fn round_trips_through_export_and_import() {
+        let values = [
+            "=HYPERLINK(\"http://example.com\")",
+            "+81-90-0000-0000",
+            "-1",
+            "@admin",
+            "'quoted",
+            "'=already",
+            "''double",
+            "plain, \"with\" quotes\r\nand lines",
+            "",
+        ];
+        let mut out = String::new();
+        let cells: Vec<String> = values
+            .iter()
+            .map(|v| escape_formula(v.to_string()))
+            .collect();
+        write_record(&mut out, cells.iter().map(String::as_str));
+
+        let records = parse(&out).unwrap();
+        assert_eq!(records.len(), 1);
+        let imported: Vec<&str> = records[0].iter().map(|c| unescape_formula(c)).collect();
+        assert_eq!(imported, values);
+    }
+}
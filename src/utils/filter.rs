@@ -0,0 +1,165 @@
+/// `filter=` クエリの構文エラー
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum FilterError {
+    #[error("filter の {0} 番目の条件に `:` がありません")]
+    MissingColon(usize),
+    #[error("filter の {0} 番目の条件のキーが空です")]
+    EmptyKey(usize),
+    #[error("filter の {0} 番目の条件の引用符が閉じられていません")]
+    UnterminatedQuote(usize),
+    #[error("filter の {0} 番目の条件の引用符の後に余分な文字があります")]
+    TrailingCharacters(usize),
+    #[error("filter のキー `{0}` が重複しています")]
+    DuplicateKey(String),
+}
+
+/// `key:value,key:value` 形式のフィルタを分解する
+///
+/// - キーは最初の `:` までで、値にはそれ以降の `:` を含められる（`12:00:00` など）
+/// - 空白や `,` を含む値は `"..."` で囲む。引用符の中では `\"` と `\\` でエスケープする
+/// - 各条件の前後の空白と、末尾の `,` は無視する
+pub fn parse(input: &str) -> Result<Vec<(String, String)>, FilterError> {
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    let mut chars = input.chars().peekable();
+    let mut index = 0;
+
+    loop {
+        // 末尾の `,` の後が空白だけの場合は条件として扱わない
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        index += 1;
+
+        let mut key = String::new();
+        loop {
+            match chars.next() {
+                Some(':') => break,
+                Some(',') | None => return Err(FilterError::MissingColon(index)),
+                Some(c) => key.push(c),
+            }
+        }
+        let key = key.trim().to_string();
+        if key.is_empty() {
+            return Err(FilterError::EmptyKey(index));
+        }
+
+        while chars.next_if(|c| *c == ' ').is_some() {}
+        let value = if chars.next_if_eq(&'"').is_some() {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some(c) => value.push(c),
+                        None => return Err(FilterError::UnterminatedQuote(index)),
+                    },
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => return Err(FilterError::UnterminatedQuote(index)),
+                }
+            }
+            while chars.next_if(|c| *c == ' ').is_some() {}
+            match chars.next() {
+                Some(',') | None => {}
+                Some(_) => return Err(FilterError::TrailingCharacters(index)),
+            }
+            value
+        } else {
+            let mut value = String::new();
+            for c in chars.by_ref() {
+                if c == ',' {
+                    break;
+                }
+                value.push(c);
+            }
+            value.trim().to_string()
+        };
+
+        if pairs.iter().any(|(k, _)| *k == key) {
+            return Err(FilterError::DuplicateKey(key));
+        }
+        pairs.push((key, value));
+    }
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(v: &[(&str, &str)]) -> Vec<(String, String)> {
+        v.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_plain_values() {
+        assert_eq!(
+            parse("is_enable:true, period:2024").unwrap(),
+            pairs(&[("is_enable", "true"), ("period", "2024")])
+        );
+        assert_eq!(parse("").unwrap(), pairs(&[]));
+    }
+
+    #[test]
+    fn keeps_colons_after_the_key() {
+        assert_eq!(
+            parse("joined_at:2024-04-01T12:00:00").unwrap(),
+            pairs(&[("joined_at", "2024-04-01T12:00:00")])
+        );
+    }
+
+    #[test]
+    fn parses_quoted_values() {
+        assert_eq!(
+            parse(r#"name:"Yamada, Taro" , period:2024"#).unwrap(),
+            pairs(&[("name", "Yamada, Taro"), ("period", "2024")])
+        );
+        assert_eq!(parse(r#"name:"""#).unwrap(), pairs(&[("name", "")]));
+    }
+
+    #[test]
+    fn unescapes_quoted_values() {
+        assert_eq!(
+            parse(r#"name:"say \"hi\" \\ bye""#).unwrap(),
+            pairs(&[("name", r#"say "hi" \ bye"#)])
+        );
+    }
+
+    #[test]
+    fn ignores_trailing_separator() {
+        assert_eq!(
+            parse("is_enable:true,").unwrap(),
+            pairs(&[("is_enable", "true")])
+        );
+        assert_eq!(
+            parse("is_enable:true, ").unwrap(),
+            pairs(&[("is_enable", "true")])
+        );
+        assert_eq!(parse(r#"name:"a" ,  "#).unwrap(), pairs(&[("name", "a")]));
+    }
+
+    #[test]
+    fn rejects_malformed_conditions() {
+        assert_eq!(
+            parse("is_enable:true,period"),
+            Err(FilterError::MissingColon(2))
+        );
+        assert_eq!(parse("a:1,,b:2"), Err(FilterError::MissingColon(2)));
+        assert_eq!(parse(" :true"), Err(FilterError::EmptyKey(1)));
+        assert_eq!(
+            parse(r#"name:"open"#),
+            Err(FilterError::UnterminatedQuote(1))
+        );
+        assert_eq!(parse(r#"name:"a\"#), Err(FilterError::UnterminatedQuote(1)));
+        assert_eq!(
+            parse(r#"name:"a"b,period:1"#),
+            Err(FilterError::TrailingCharacters(1))
+        );
+        assert_eq!(
+            parse("a:1,a:2"),
+            Err(FilterError::DuplicateKey("a".to_string()))
+        );
+    }
+}
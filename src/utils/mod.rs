@@ -1 +1,6 @@
+pub mod csv;
+pub mod filter;
 pub mod password;
+pub mod retry_queue;
+pub mod token;
+pub mod validation;
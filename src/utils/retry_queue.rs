@@ -0,0 +1,73 @@
+use std::{future::Future, time::Duration};
+
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{sea_query::Expr, *};
+use tracing::error;
+
+/// 送信を諦めるまでの試行回数
+pub const MAX_ATTEMPTS: i32 = 8;
+/// キューを確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// 一度に処理する件数
+const BATCH_SIZE: u64 = 50;
+
+/// 失敗した送信を間隔を空けて再試行するキューのテーブル
+/// `attempts`（試行回数）と `next_attempt_at`（次に試行する日時）の列を持つこと.
+pub trait RetryQueue: EntityTrait {
+    fn id_column() -> Self::Column;
+    fn attempts_column() -> Self::Column;
+    fn next_attempt_at_column() -> Self::Column;
+}
+
+/// 失敗回数に応じた再送までの待ち時間（30秒から倍々、最大6時間）
+pub fn backoff(attempts: i32) -> chrono::Duration {
+    let secs = 30i64.saturating_mul(1i64 << attempts.clamp(0, 16));
+    chrono::Duration::seconds(secs.min(6 * 60 * 60))
+}
+
+/// 試行時刻を迎え、試行回数が上限に達していない行（古い順、1回分）
+/// 送信済みなど、キューごとの条件は呼び出し側で加える.
+pub fn due<E: RetryQueue>(now: NaiveDateTime) -> Select<E> {
+    E::find()
+        .filter(E::attempts_column().lt(MAX_ATTEMPTS))
+        .filter(E::next_attempt_at_column().lte(now))
+        .order_by_asc(E::next_attempt_at_column())
+        .limit(BATCH_SIZE)
+}
+
+/// 試行回数と次回試行時刻を先に進め、この行を処理する権利を得る
+/// 送信中に落ちても重複送信しないよう、送信の前に呼ぶ.
+/// 他のワーカーが先に進めていた場合は `false` を返す.
+pub async fn claim<E, C>(db: &C, id: i32, attempts: i32) -> Result<bool, DbErr>
+where
+    E: RetryQueue,
+    C: ConnectionTrait,
+{
+    let next = attempts + 1;
+    let claimed = E::update_many()
+        .col_expr(E::attempts_column(), Expr::value(next))
+        .col_expr(
+            E::next_attempt_at_column(),
+            Expr::value(Utc::now().naive_utc() + backoff(next)),
+        )
+        .filter(E::id_column().eq(id))
+        .filter(E::attempts_column().eq(attempts))
+        .exec(db)
+        .await?;
+    Ok(claimed.rows_affected > 0)
+}
+
+/// `process` を一定間隔で呼び出し続ける
+pub async fn run<F, Fut>(name: &str, mut process: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), DbErr>>,
+{
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = process().await {
+            error!("Failed to process {}: {:?}", name, e);
+        }
+    }
+}
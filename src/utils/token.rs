@@ -0,0 +1,12 @@
+use sha2::Digest;
+
+/// 推測困難なランダムトークンを生成する
+pub fn generate_token() -> String {
+    let uuid = uuid::Uuid::new_v4().to_string();
+    hex::encode(sha2::Sha256::digest(uuid.as_bytes()))
+}
+
+/// DB 保存用にトークンをハッシュ化する
+pub fn hash_token(token: &str) -> String {
+    hex::encode(sha2::Sha256::digest(token.as_bytes()))
+}
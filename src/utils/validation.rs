@@ -0,0 +1,53 @@
+use std::borrow::Cow;
+
+use chrono::{NaiveDate, Utc};
+use validator::ValidationError;
+
+/// custom_id の長さ
+const CUSTOM_ID_MIN_LEN: usize = 3;
+const CUSTOM_ID_MAX_LEN: usize = 32;
+
+fn error(code: &'static str, message: &'static str) -> ValidationError {
+    ValidationError::new(code).with_message(Cow::Borrowed(message))
+}
+
+/// custom_id は英数字・`_`・`-` のみ、3〜32文字
+pub fn custom_id(value: &str) -> Result<(), ValidationError> {
+    let len = value.chars().count();
+    if !(CUSTOM_ID_MIN_LEN..=CUSTOM_ID_MAX_LEN).contains(&len) {
+        return Err(error("length", "3〜32文字で入力してください"));
+    }
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(error(
+            "charset",
+            "英数字・アンダースコア・ハイフンのみ使用できます",
+        ));
+    }
+    Ok(())
+}
+
+/// 生年月日は 1900-01-01 から今日まで
+pub fn birthdate(value: &NaiveDate) -> Result<(), ValidationError> {
+    let min = NaiveDate::from_ymd_opt(1900, 1, 1).unwrap();
+    if *value < min || *value > Utc::now().date_naive() {
+        return Err(error(
+            "range",
+            "1900-01-01 から今日までの日付を入力してください",
+        ));
+    }
+    Ok(())
+}
+
+/// Discord の ID（snowflake）は17〜20桁の数字
+pub fn discord_snowflake(value: &str) -> Result<(), ValidationError> {
+    if !(17..=20).contains(&value.len()) || !value.chars().all(|c| c.is_ascii_digit()) {
+        return Err(error(
+            "snowflake",
+            "Discord の ID（17〜20桁の数字）を入力してください",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::{sync::OnceLock, time::Duration};
+
+use reqwest::{Client, StatusCode, Url, header};
+
+/// 接続から応答までの上限
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 接続を使い回すため、クライアントはプロセスで1つだけ作る
+fn client() -> anyhow::Result<&'static Client> {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent("UniQUE-API-Webhook")
+        // 送信先の指定どおりに届け、リダイレクト先へ本文と署名を送らない
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+/// JSON を POST し、ステータスコードを返す
+pub async fn post_json(
+    url: &Url,
+    headers: &[(&str, String)],
+    body: String,
+) -> anyhow::Result<StatusCode> {
+    let mut request = client()?
+        .post(url.clone())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body);
+    for (name, value) in headers {
+        request = request.header(*name, value);
+    }
+    Ok(request.send().await?.status())
+}
@@ -0,0 +1,177 @@
+mod client;
+
+use std::sync::OnceLock;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sea_orm::*;
+use serde_json::{Value, json};
+use sha2::Sha256;
+use tracing::{error, info, warn};
+
+use crate::{
+    db::DbConn,
+    models::webhook_delivery,
+    utils::retry_queue::{self, MAX_ATTEMPTS, RetryQueue},
+};
+
+impl RetryQueue for webhook_delivery::Entity {
+    fn id_column() -> Self::Column {
+        webhook_delivery::Column::Id
+    }
+    fn attempts_column() -> Self::Column {
+        webhook_delivery::Column::Attempts
+    }
+    fn next_attempt_at_column() -> Self::Column {
+        webhook_delivery::Column::NextAttemptAt
+    }
+}
+
+/// Webhook の送信先
+struct WebhookConfig {
+    url: Url,
+    /// 署名に使う鍵（未設定の場合は署名しない）
+    secret: Option<String>,
+}
+
+static CONFIG: OnceLock<Option<WebhookConfig>> = OnceLock::new();
+
+/// 環境変数から送信先を読み込む
+pub fn init_from_env() -> anyhow::Result<()> {
+    let config = match std::env::var("WEBHOOK_URL") {
+        Ok(url) if !url.is_empty() => {
+            let url =
+                Url::parse(&url).map_err(|e| anyhow::anyhow!("Invalid WEBHOOK_URL: {}", e))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                anyhow::bail!("WEBHOOK_URL must be an http or https URL");
+            }
+            let secret = std::env::var("WEBHOOK_SECRET")
+                .ok()
+                .filter(|s| !s.is_empty());
+            if secret.is_none() {
+                warn!("WEBHOOK_SECRET is not set; webhooks will be sent unsigned");
+            }
+            info!("Webhook endpoint: {}", url);
+            Some(WebhookConfig { url, secret })
+        }
+        _ => None,
+    };
+    if CONFIG.set(config).is_err() {
+        info!("Webhook is already initialized");
+    }
+    Ok(())
+}
+
+fn config() -> Option<&'static WebhookConfig> {
+    CONFIG.get().and_then(Option::as_ref)
+}
+
+/// イベントを送信キューに積む（送信先が未設定の場合は何もしない）
+/// 実際の送信は `run_worker` が行う.
+pub async fn enqueue(db: &DbConn, event: &str, data: Value) -> Result<(), DbErr> {
+    if config().is_none() {
+        return Ok(());
+    }
+    let now = Utc::now().naive_utc();
+    let am = webhook_delivery::ActiveModel {
+        event_id: Set(ulid::Ulid::new().to_string()),
+        event: Set(event.to_string()),
+        payload: Set(data),
+        attempts: Set(0),
+        last_error: Set(None),
+        next_attempt_at: Set(now),
+        delivered_at: Set(None),
+        created_at: Set(now),
+        ..Default::default()
+    };
+    am.insert(db).await?;
+    Ok(())
+}
+
+/// `{timestamp}.{body}` の HMAC-SHA256（16進）
+/// 送信時刻も署名に含め、受信側で古い送信の再利用（リプレイ）を拒めるようにする.
+fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// 1件を送信する（2xx 以外は失敗として扱う）
+async fn deliver(config: &WebhookConfig, item: &webhook_delivery::Model) -> anyhow::Result<()> {
+    let body = json!({
+        "id": item.event_id,
+        "event": item.event,
+        "created_at": item.created_at.and_utc(),
+        "data": item.payload,
+    })
+    .to_string();
+    let timestamp = Utc::now().timestamp();
+    let mut headers = vec![
+        ("X-Webhook-Event", item.event.clone()),
+        ("X-Webhook-Id", item.event_id.clone()),
+        ("X-Webhook-Timestamp", timestamp.to_string()),
+    ];
+    if let Some(secret) = &config.secret {
+        headers.push((
+            "X-Webhook-Signature",
+            format!("sha256={}", sign(secret, timestamp, &body)),
+        ));
+    }
+    let status = client::post_json(&config.url, &headers, body).await?;
+    if !status.is_success() {
+        anyhow::bail!("Webhook endpoint responded with {}", status);
+    }
+    Ok(())
+}
+
+/// 送信キューを定期的に処理し続ける
+pub async fn run_worker(db: DbConn) {
+    let Some(config) = config() else {
+        return;
+    };
+    retry_queue::run("webhook queue", || process_due(&db, config)).await;
+}
+
+/// 送信時刻を迎えたイベントを送信する
+async fn process_due(db: &DbConn, config: &WebhookConfig) -> Result<(), DbErr> {
+    let due = retry_queue::due::<webhook_delivery::Entity>(Utc::now().naive_utc())
+        .filter(webhook_delivery::Column::DeliveredAt.is_null())
+        .all(db)
+        .await?;
+
+    for item in due {
+        if !retry_queue::claim::<webhook_delivery::Entity, _>(db, item.id, item.attempts).await? {
+            continue;
+        }
+        let attempts = item.attempts + 1;
+
+        let result = deliver(config, &item).await;
+        let mut am: webhook_delivery::ActiveModel = item.clone().into();
+        am.attempts = Set(attempts);
+        match result {
+            Ok(()) => {
+                info!("Webhook {} ({}) delivered", item.event_id, item.event);
+                am.delivered_at = Set(Some(Utc::now().naive_utc()));
+                am.last_error = Set(None);
+            }
+            Err(e) => {
+                if attempts >= MAX_ATTEMPTS {
+                    error!("Giving up delivering webhook {}: {:?}", item.event_id, e);
+                } else {
+                    warn!(
+                        "Failed to deliver webhook {} (attempt {}): {:?}",
+                        item.event_id, attempts, e
+                    );
+                }
+                am.last_error = Set(Some(e.to_string()));
+                am.next_attempt_at = Set(Utc::now().naive_utc() + retry_queue::backoff(attempts));
+            }
+        }
+        am.update(db).await?;
+    }
+    Ok(())
+}